@@ -0,0 +1,56 @@
+//! Compares decoding every instruction at each fetch with the recompiler, over frames of PONG
+//! run headless at the default speed and at a speed leaving most of each frame to its delay timer
+//! wait.
+//!
+//! Run with `cargo bench --bench interpreter`.
+
+use chip8_emulator_rust::chip8::Chip8;
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+const FRAMES: usize = 600;
+const INSTRUCTIONS_PER_FRAME: [usize; 2] = [10, 100];
+
+/// Selects an execution path on a machine.
+type Backend = fn(&mut Chip8);
+
+/// Returns PONG, seeded so every run draws the same random numbers.
+fn pong() -> Chip8 {
+    let rom = std::fs::read("files/roms/PONG").expect("files/roms/PONG is missing");
+    let mut chip8 = Chip8::start(rom);
+    chip8.seed_rng(0);
+    chip8
+}
+
+fn bench_interpreter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pong_600_frames");
+
+    let backends: [(&str, Backend); 2] = [
+        ("decode_each_fetch", |_| ()),
+        ("recompiler", |chip8| chip8.set_recompiler(true)),
+    ];
+    for instructions in INSTRUCTIONS_PER_FRAME {
+        for (name, enable) in backends {
+            group.bench_function(BenchmarkId::new(name, instructions), |b| {
+                b.iter_batched(
+                    || {
+                        let mut chip8 = pong();
+                        enable(&mut chip8);
+                        chip8
+                    },
+                    |mut chip8| {
+                        for _ in 0..FRAMES {
+                            chip8.run_frame(instructions);
+                        }
+                        black_box(chip8)
+                    },
+                    criterion::BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_interpreter);
+criterion_main!(benches);
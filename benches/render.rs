@@ -0,0 +1,69 @@
+//! Compares repainting the whole framebuffer with repainting only the dirty regions
+//! after a typical frame that draws a single 8x5 sprite.
+//!
+//! Run with `cargo bench --bench render`.
+
+use chip8_emulator_rust::{
+    chip8::Chip8,
+    screen::{self, DisplayOptions, Persistence},
+};
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+/// Returns a machine whose last instruction drew the font sprite for "0" at (10, 10).
+fn chip8_after_sprite_draw() -> Chip8 {
+    let mut chip8 = Chip8::start(vec![0x00, 0xE0]);
+    chip8.clear_redraw_state();
+    chip8.v_registers[0] = 10;
+    chip8.v_registers[1] = 10;
+    chip8.i_register = 0;
+    chip8.decode_execute(0xD015); // DRW V0, V1, 5
+    chip8
+}
+
+fn bench_redraw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("redraw_after_sprite");
+
+    for scale in [10, 20, 40] {
+        let options = DisplayOptions {
+            scale,
+            ..DisplayOptions::default()
+        };
+        let mut buffer = screen::initialize_buffer(&options);
+        let mut persistence = Persistence::new(&options);
+
+        let dirty = chip8_after_sprite_draw();
+        let mut full = chip8_after_sprite_draw();
+        // Without dirty regions the renderer falls back to repainting the whole screen
+        full.dirty_regions.clear();
+
+        group.bench_with_input(BenchmarkId::new("full_frame", scale), &full, |b, chip8| {
+            b.iter(|| {
+                screen::draw_screen_if_needed(
+                    black_box(&mut buffer),
+                    chip8,
+                    &options,
+                    &mut persistence,
+                )
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("dirty_regions", scale),
+            &dirty,
+            |b, chip8| {
+                b.iter(|| {
+                    screen::draw_screen_if_needed(
+                        black_box(&mut buffer),
+                        chip8,
+                        &options,
+                        &mut persistence,
+                    )
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_redraw);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+//! Plays episodes of a ROM with uniformly random actions and prints their returns, a baseline
+//! for agents: `cargo run -p chip8_core --release --example random_agent -- files/roms/PONG 10`
+
+use std::{env, path::Path, time::Instant};
+
+use chip8_core::env::{Chip8Env, EnvOptions, rules_for_rom};
+use rand::Rng;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().expect("usage: random_agent <rom> [episodes]");
+    let episodes: u64 = args.next().map_or(10, |count| count.parse().unwrap());
+
+    let rom = std::fs::read(&path).unwrap();
+    let name = Path::new(&path).file_name().unwrap().to_string_lossy();
+    let options = EnvOptions {
+        max_steps: Some(10_000),
+        ..EnvOptions::default()
+    };
+    let mut env = Chip8Env::new(rom, rules_for_rom(&name), options);
+    let mut rng = rand::thread_rng();
+
+    let start = Instant::now();
+    let mut total_steps = 0;
+    for episode in 0..episodes {
+        env.reset(episode);
+        let mut episode_return = 0.0;
+        loop {
+            let (_, reward, done) = env.step(rng.gen_range(0..env.action_count()));
+            episode_return += reward;
+            if done {
+                break;
+            }
+        }
+        total_steps += env.steps();
+        println!(
+            "episode {}: return {}, {} steps",
+            episode,
+            episode_return,
+            env.steps()
+        );
+    }
+    println!(
+        "{:.0} steps per second",
+        total_steps as f64 / start.elapsed().as_secs_f64()
+    );
+}
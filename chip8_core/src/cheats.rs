@@ -0,0 +1,330 @@
+use std::{fmt, str::FromStr};
+
+use crate::{chip8::Chip8, chip8_util::Chip8Util, constants::CHIP8_RAM_MEMORY_SIZE};
+
+/// Bytes written to RAM before every frame, e.g. to freeze a number of lives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub name: String,
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03X} ", self.address)?;
+        for byte in &self.bytes {
+            write!(f, "{:02X}", byte)?;
+        }
+        if !self.name.is_empty() {
+            write!(f, " {}", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    /// Parses `<address> <bytes> [name]`, with the address and bytes in hex, e.g. `2F3 09 score`.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.trim().splitn(3, char::is_whitespace);
+        let address = parts.next().unwrap_or_default();
+        let address = u16::from_str_radix(address, 16)
+            .map_err(|_| format!("invalid address '{}'", address))?;
+
+        let hex = parts.next().ok_or("missing bytes")?;
+        let bytes: Vec<u8> = Chip8Util::parse_hex_bytes(hex, false)?
+            .into_iter()
+            .flatten()
+            .collect();
+        if address as usize + bytes.len() > CHIP8_RAM_MEMORY_SIZE {
+            return Err(format!(
+                "{} bytes at {:03X} do not fit in RAM",
+                bytes.len(),
+                address
+            ));
+        }
+
+        Ok(Self {
+            address,
+            bytes,
+            name: parts.next().unwrap_or_default().trim().to_string(),
+        })
+    }
+}
+
+/// The cheats of a session, applied by [`crate::frontend::RunLoop`] before each frame.
+///
+/// The text format has one cheat per line, as `<address> <bytes> [name]` in hex.
+/// Lines starting with `#` are comments:
+///
+/// ```text
+/// # PONG: the left player always has 9 points
+/// 2F3 09 left score
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheats {
+    pub cheats: Vec<Cheat>,
+    pub enabled: bool,
+}
+
+impl Default for Cheats {
+    fn default() -> Self {
+        Self {
+            cheats: Vec::new(),
+            enabled: true,
+        }
+    }
+}
+
+impl Cheats {
+    /// Parses the text format described on [`Cheats`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cheats = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cheat = line
+                .parse()
+                .map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+            cheats.push(cheat);
+        }
+        Ok(Self {
+            cheats,
+            enabled: true,
+        })
+    }
+
+    /// Adds a cheat, replacing the one at the same address if any.
+    pub fn add(&mut self, cheat: Cheat) {
+        self.remove(cheat.address);
+        self.cheats.push(cheat);
+    }
+
+    /// Removes the cheat at `address`, returning whether there was one.
+    pub fn remove(&mut self, address: u16) -> bool {
+        let count = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.address != address);
+        self.cheats.len() != count
+    }
+
+    /// Writes the bytes of every cheat, if cheats are enabled.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        if !self.enabled {
+            return;
+        }
+        for cheat in &self.cheats {
+            let start = cheat.address as usize;
+            chip8.ram[start..start + cheat.bytes.len()].copy_from_slice(&cheat.bytes);
+        }
+    }
+}
+
+/// How a [`CheatSearch`] compares each candidate byte with its value at the previous search step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal(u8),
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Comparison::Equal(value) => current == value,
+            Comparison::Unchanged => current == previous,
+            Comparison::Changed => current != previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    /// Parses `equal <value>` (decimal, or hex with `0x`), `unchanged`, `changed`, `increased`
+    /// or `decreased`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut words = value.split_whitespace();
+        let comparison = match words.next().unwrap_or_default() {
+            "equal" => {
+                let number = words.next().ok_or("equal needs a value")?;
+                let parsed = match number.strip_prefix("0x") {
+                    Some(hex) => u8::from_str_radix(hex, 16),
+                    None => number.parse(),
+                };
+                Comparison::Equal(parsed.map_err(|_| format!("invalid byte '{}'", number))?)
+            }
+            "unchanged" => Comparison::Unchanged,
+            "changed" => Comparison::Changed,
+            "increased" => Comparison::Increased,
+            "decreased" => Comparison::Decreased,
+            other => {
+                return Err(format!(
+                    "unknown comparison '{}', use equal <value>, unchanged, changed, increased or decreased",
+                    other
+                ));
+            }
+        };
+        match words.next() {
+            Some(extra) => Err(format!("unexpected '{}'", extra)),
+            None => Ok(comparison),
+        }
+    }
+}
+
+/// Finds where a game keeps a value by narrowing the candidate addresses while playing:
+/// start a search, let the value change in the game, keep the addresses whose byte changed the
+/// same way, and repeat until a few remain.
+///
+/// ```
+/// use chip8_core::{cheats::{CheatSearch, Comparison}, chip8::Chip8};
+///
+/// let mut chip8 = Chip8::new();
+/// let mut search = CheatSearch::start(&chip8);
+/// chip8.ram[0x300] = 3;
+/// search.narrow(&chip8, Comparison::Increased);
+/// assert_eq!(search.candidates(), &[0x300]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CheatSearch {
+    previous: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl CheatSearch {
+    /// Starts a search with every address of RAM as a candidate.
+    pub fn start(chip8: &Chip8) -> Self {
+        Self {
+            previous: chip8.ram.to_vec(),
+            candidates: (0..CHIP8_RAM_MEMORY_SIZE as u16).collect(),
+        }
+    }
+
+    /// Keeps the candidates whose byte matches `comparison`, and remembers the current RAM for
+    /// the next step. Returns the number of candidates left.
+    pub fn narrow(&mut self, chip8: &Chip8, comparison: Comparison) -> usize {
+        let previous = &self.previous;
+        self.candidates.retain(|&address| {
+            let address = address as usize;
+            comparison.matches(previous[address], chip8.ram[address])
+        });
+        self.previous = chip8.ram.to_vec();
+        self.candidates.len()
+    }
+
+    /// The addresses still matching every step.
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+}
+
+impl fmt::Display for CheatSearch {
+    /// The number of candidates and the first few with their last values.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHOWN: usize = 8;
+
+        let count = self.candidates.len();
+        write!(
+            f,
+            "{} candidate{}",
+            count,
+            if count == 1 { "" } else { "s" }
+        )?;
+        for &address in self.candidates.iter().take(SHOWN) {
+            write!(
+                f,
+                " {:03X}={:02X}",
+                address, self.previous[address as usize]
+            )?;
+        }
+        if self.candidates.len() > SHOWN {
+            write!(f, " ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// A cheat search action requested with a hotkey.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchStep {
+    Start,
+    Narrow(Comparison),
+}
+
+impl SearchStep {
+    /// Performs the step on `search`. Narrowing without a search in progress starts one.
+    pub fn apply(self, search: &mut Option<CheatSearch>, chip8: &Chip8) {
+        match (self, search.as_mut()) {
+            (SearchStep::Narrow(comparison), Some(search)) => {
+                search.narrow(chip8, comparison);
+            }
+            _ => *search = Some(CheatSearch::start(chip8)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cheat_files_are_parsed_and_applied_when_enabled() {
+        let mut cheats = Cheats::parse("# lives\n2F3 09 left score\n\n300 ABCD\n").unwrap();
+        assert_eq!(cheats.cheats.len(), 2);
+        assert_eq!(cheats.cheats[0].name, "left score");
+        assert_eq!(cheats.cheats[1].to_string(), "300 ABCD");
+
+        let mut chip8 = Chip8::new();
+        cheats.apply(&mut chip8);
+        assert_eq!(chip8.ram[0x2F3], 0x09);
+        assert_eq!(&chip8.ram[0x300..0x302], &[0xAB, 0xCD]);
+
+        cheats.enabled = false;
+        chip8.ram[0x2F3] = 0;
+        cheats.apply(&mut chip8);
+        assert_eq!(chip8.ram[0x2F3], 0);
+
+        assert!(Cheats::parse("FFF 0102").unwrap_err().starts_with("line 1"));
+        assert!("2F3 9".parse::<Cheat>().is_err());
+        assert!("2F3 +9".parse::<Cheat>().is_err());
+        assert!("200 aéa".parse::<Cheat>().is_err());
+        assert!("200 éa name".parse::<Cheat>().is_err());
+        assert_eq!(
+            "200 0a ünïcode name".parse::<Cheat>().unwrap().name,
+            "ünïcode name"
+        );
+    }
+
+    #[test]
+    fn searches_narrow_the_candidates_at_each_step() {
+        let mut chip8 = Chip8::new();
+        chip8.ram[0x300] = 3;
+        chip8.ram[0x301] = 3;
+        let mut search = CheatSearch::start(&chip8);
+
+        assert_eq!(search.narrow(&chip8, Comparison::Equal(3)), 2);
+        chip8.ram[0x300] = 2;
+        chip8.ram[0x301] = 4;
+        assert_eq!(search.narrow(&chip8, Comparison::Changed), 2);
+        chip8.ram[0x300] = 1;
+        chip8.ram[0x301] = 5;
+        assert_eq!(search.narrow(&chip8, Comparison::Decreased), 1);
+        assert_eq!(search.candidates(), &[0x300]);
+        assert_eq!(search.to_string(), "1 candidate 300=01");
+    }
+
+    #[test]
+    fn comparisons_are_parsed() {
+        assert_eq!("equal 0x2F".parse(), Ok(Comparison::Equal(0x2F)));
+        assert_eq!("increased".parse(), Ok(Comparison::Increased));
+        assert!("equal".parse::<Comparison>().is_err());
+        assert!("bigger".parse::<Comparison>().is_err());
+    }
+}
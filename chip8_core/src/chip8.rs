@@ -1,47 +1,112 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 
 use crate::{
     chip8_util::Chip8Util,
     constants::{
         CHIP8_RAM_MEMORY_SIZE, CHIP8_REGISTER_COUNT, CHIP8_STACK_MEMORY_SIZE, CLEANED_SCREEN,
-        FONT_SPRITES, FONT_SPRITES_SIZE, KEYBOARD_KEYS_COUNT, SCREEN_HEIGHT, SCREEN_WIDTH,
-        START_RAM_ADDRESS,
+        FONT_SPRITES, FONT_SPRITES_SIZE, KEYBOARD_KEYS_COUNT, MAX_DIRTY_REGIONS, MAX_ROM_SIZE,
+        SCREEN_HEIGHT, SCREEN_WIDTH, START_RAM_ADDRESS,
     },
+    coverage::{Access, Coverage},
+    idle,
+    profiler::Profiler,
+    recompiler::Recompiler,
+    symbols::Symbols,
 };
 
+/// A rectangle of the screen, in Chip-8 pixels, that changed since the last redraw.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirtyRegion {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRegion {
+    /// The region covering the whole screen.
+    pub const FULL_SCREEN: DirtyRegion = DirtyRegion {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+    };
+}
+
+/// The last sprite drawn by Dxyn, for sprite viewers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteDraw {
+    pub address: u16, // The I register, where the rows were read from
+    pub height: u8,   // Number of rows, the n of Dxyn
+    pub x: u8,        // Position on the screen, from Vx and Vy
+    pub y: u8,
+}
+
+/// Executes a decoded instruction, given its opcode for the operands.
+pub type Handler = fn(&mut Chip8, u16);
+
+#[derive(Clone)]
 pub struct Chip8 {
     pub ram: [u8; CHIP8_RAM_MEMORY_SIZE],
     pub pc: u16,         // Program Counter
     pub i_register: u16, // This register is generally used to store memory addresses, so only the lowest (rightmost) 12 bits are usually used
     pub stack: [u16; CHIP8_STACK_MEMORY_SIZE],
     pub stack_pointer: usize,
+    pub stack_depth: usize, // Return addresses on the circular stack, at most 16, see `return_addresses`
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub v_registers: [u8; CHIP8_REGISTER_COUNT],
     pub screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
     pub needs_redraw: bool,
-    pub debug_mode: bool, // Flag to indicate if the emulator is in debug mode
-    pub instructions_executed: usize, // Count of instructions executed
+    pub dirty_regions: Vec<DirtyRegion>, // Areas changed since the last redraw, the whole screen is redrawn when this is empty
+    pub debug_mode: bool,                // Flag to indicate if the emulator is in debug mode
+    pub instructions_executed: usize,    // Count of instructions executed
     pub keyboard: [bool; KEYBOARD_KEYS_COUNT],
+    pub last_sprite: Option<SpriteDraw>, // Not saved in snapshots
+    pub(crate) rng: ChaCha12Rng, // Source of the Cxkk random numbers, seeded to replay a session exactly
+    pub(crate) rng_seed: u64,
+    recompiler: Option<Box<Recompiler>>, // Runs frames a basic block at a time, when enabled
+    profiler: Option<Box<Profiler>>,     // Counts the instructions run, when enabled
+    coverage: Option<Box<Coverage>>,     // Records how each byte of memory was used, when enabled
+    symbols: Option<Box<Symbols>>,       // Labels and source lines shown by the debug trace
+    idle_skip: bool, // Ends frames early once the program only waits, see `set_idle_skip`
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8 {
     /// Creates a new instance of the Chip-8 emulator with initialized memory and registers.
     pub fn new() -> Self {
+        let seed = rand::random();
         Self {
             ram: [0; CHIP8_RAM_MEMORY_SIZE],
             pc: START_RAM_ADDRESS, // Programs start at memory location 0x200
             i_register: 0,
             stack: [0; CHIP8_STACK_MEMORY_SIZE],
             stack_pointer: 0,
+            stack_depth: 0,
             delay_timer: 0,
             sound_timer: 0,
             v_registers: [0; CHIP8_REGISTER_COUNT],
             screen: CLEANED_SCREEN,
             needs_redraw: false,
+            dirty_regions: Vec::new(),
             debug_mode: false,
             instructions_executed: 0,
             keyboard: [false; KEYBOARD_KEYS_COUNT],
+            last_sprite: None,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            rng_seed: seed,
+            recompiler: None,
+            profiler: None,
+            coverage: None,
+            symbols: None,
+            idle_skip: false,
         }
     }
 
@@ -50,13 +115,45 @@ impl Chip8 {
         let mut chip8 = Self::new();
         chip8.load_rom(rom_binary);
         chip8.load_font_slices();
-        return chip8;
+        chip8
+    }
+
+    /// Seeds the random number generator used by Cxkk, so runs with the same seed and input are identical.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.rng_seed = seed;
+    }
+
+    /// Runs one 60 Hz frame: executes `instructions` instructions, then updates the timers once.
+    /// Returns true if the buzzer sounded during the frame, i.e. the sound timer was active.
+    pub fn run_frame(&mut self, instructions: usize) -> bool {
+        match self.recompiler.take() {
+            Some(mut recompiler) => {
+                recompiler.run(self, instructions);
+                self.recompiler = Some(recompiler);
+            }
+            None => {
+                for _ in 0..instructions {
+                    self.tick();
+                    if self.skips_idle() && idle::is_idle(self) {
+                        break;
+                    }
+                }
+            }
+        }
+        let buzzer_on = self.sound_timer > 0;
+        self.update_timers();
+        buzzer_on
     }
 
     /// Executes a single tick of the Chip-8 emulator, representing one cycle.
     pub fn tick(&mut self) {
         //FETCH
+        let address = self.pc;
+        let stack_depth = self.stack_depth;
         let op_code = self.fetch();
+        self.record_access(address as usize, Access::Executed);
+        self.record_access(address as usize + 1, Access::Executed);
 
         if self.debug_mode {
             Chip8Util::print_instruction(self, op_code);
@@ -65,16 +162,80 @@ impl Chip8 {
         //DECODE
         //EXECUTE
         self.decode_execute(op_code);
+
+        let next_pc = self.pc;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(address, op_code, stack_depth, next_pc);
+        }
+    }
+
+    /// Enables or disables the [`Recompiler`], which runs the instructions of frames a basic
+    /// block at a time for bulk headless runs. Single ticks are always interpreted. Disabled by
+    /// default.
+    pub fn set_recompiler(&mut self, enabled: bool) {
+        self.recompiler = enabled.then(|| Box::new(Recompiler::new()));
+    }
+
+    /// Enables the [`Profiler`] with empty counts, or disables it. Frames are interpreted while
+    /// it is enabled, so every instruction is counted.
+    pub fn set_profiler(&mut self, enabled: bool) {
+        self.profiler = enabled.then(|| Box::new(Profiler::new()));
+    }
+
+    /// Enables or disables ending frames early once the program only waits for the delay timer
+    /// or a key, see [`idle::is_idle`], so the host sleeps until the next frame instead of
+    /// running the wait loop. A wait can then end up to two instructions earlier or later than
+    /// when every instruction runs. Frames run in full while the profiler is enabled, so waits
+    /// are counted. Disabled by default.
+    pub fn set_idle_skip(&mut self, enabled: bool) {
+        self.idle_skip = enabled;
+    }
+
+    /// Whether the rest of the frame is skipped once the program is idle: idle skipping is
+    /// enabled and the profiler is not.
+    pub fn skips_idle(&self) -> bool {
+        self.idle_skip && self.profiler.is_none()
+    }
+
+    /// The counts of the profiler, if enabled.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Enables the [`Coverage`] map with no bytes used, or disables it. Frames are interpreted
+    /// while it is enabled, so every instruction is recorded.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = enabled.then(|| Box::new(Coverage::new()));
+    }
+
+    /// The coverage map, if enabled.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Sets the labels and source lines of the program, shown with each instruction traced in
+    /// debug mode.
+    pub fn set_symbols(&mut self, symbols: Option<Symbols>) {
+        self.symbols = symbols.map(Box::new);
+    }
+
+    /// The labels and source lines of the program, if set.
+    pub fn symbols(&self) -> Option<&Symbols> {
+        self.symbols.as_deref()
+    }
+
+    /// Records in the coverage map, if enabled, how the byte at `address` was used.
+    fn record_access(&mut self, address: usize, access: Access) {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(address, access);
+        }
     }
 
     /// Fetch the next opcode (2 bytes) from memory at the current program counter
     pub fn fetch(&mut self) -> u16 {
         let high_byte = self.ram.get(self.pc as usize);
-        let low_byte = self.ram.get((self.pc + 1) as usize);
-        if high_byte.is_some() && low_byte.is_some() {
-            let high_byte = high_byte.unwrap();
-            let low_byte = low_byte.unwrap();
-
+        let low_byte = self.ram.get(self.pc as usize + 1);
+        if let (Some(high_byte), Some(low_byte)) = (high_byte, low_byte) {
             self.pc += 2; // Move to the next instruction
 
             return u16::from_be_bytes([*high_byte, *low_byte]);
@@ -85,54 +246,99 @@ impl Chip8 {
 
     /// Decode and execute the given opcode
     pub fn decode_execute(&mut self, op_code: u16) {
+        Self::decode(op_code)(self, op_code);
+    }
+
+    /// Decodes an opcode into the function executing it, to call with the same opcode.
+    /// Operands are extracted by that function, so the result depends only on the opcode's kind
+    /// and can be kept with the instruction, see [`Recompiler`].
+    pub fn decode(op_code: u16) -> Handler {
         // nnn or addr - A 12-bit value, the lowest 12 bits of the instruction
         // n or nibble - A 4-bit value, the lowest 4 bits of the instruction
         // x - A 4-bit value, the lower 4 bits of the high byte of the instruction
         // y - A 4-bit value, the upper 4 bits of the low byte of the instruction
         // kk or byte - An 8-bit value, the lowest 8 bits of the instruction
 
-        let op_digits = Chip8Util::extract_nibbles(op_code);
-        let (digit1, digit2, digit3, digit4) = op_digits;
-
-        match (digit1, digit2, digit3, digit4) {
-            (0, 0, 0, 0) => return,
-            (0, 0, 0xe, 0) => self.clear_screen(),
-            (0, 0, 0xe, 0xe) => self.return_from_subroutine(),
-            (0, _, _, _) => self.sys_addr(op_code),
-            (0x1, _, _, _) => self.jump(op_code),
-            (0x2, _, _, _) => self.call_subroutine(op_code),
-            (0x3, _, _, _) => self.skip_if_equal(digit2, digit3, digit4),
-            (0x4, _, _, _) => self.skip_if_vx_not_eq_kk(digit2, digit3, digit4),
-            (0x5, _, _, 0) => self.skip_if_vx_eq_vy(digit2, digit3),
-            (0x6, _, _, _) => self.set_v_register(op_code),
-            (0x7, _, _, _) => self.add_value_to_v_register(op_code),
-            (0x8, _, _, 0) => self.store_vy_in_vx(digit2, digit3),
-            (0x8, _, _, 1) => self.set_vx_with_vx_or_vy(digit2, digit3),
-            (0x8, _, _, 2) => self.set_vx_with_vx_and_vy(digit2, digit3),
-            (0x8, _, _, 3) => self.set_vx_with_vx_xor_vy(digit2, digit3),
-            (0x8, _, _, 4) => self.add_vx_with_vy(digit2, digit3),
-            (0x8, _, _, 5) => self.subtract_vy_from_vx(digit2, digit3),
-            (0x8, _, _, 6) => self.shr_vx(digit2),
-            (0x8, _, _, 7) => self.subtract_vx_from_vy(digit2, digit3),
-            (0x8, _, _, 0xe) => self.shl_vx(digit2),
-            (0x9, _, _, 0) => self.skip_if_vx_ne_vy(digit2, digit3),
-            (0xa, _, _, _) => self.set_i_register(op_code),
-            (0xb, _, _, _) => self.jump_v0_addr(op_code),
-            (0xc, _, _, _) => self.rnd_vx_byte(op_code),
-            (0xd, _, _, _) => self.draw_sprite_to_screen(op_digits),
-            (0xe, _, 9, 0xe) => self.skp_vx(digit2),
-            (0xe, _, 0xa, 1) => self.sknp_vx(digit2),
-            (0xf, _, 0, 7) => self.ld_vx_dt(digit2),
-            (0xf, _, 0, 0xa) => self.ld_vx_k(digit2),
-            (0xf, _, 1, 5) => self.ld_dt_vx(digit2),
-            (0xf, _, 1, 8) => self.ld_st_vx(digit2),
-            (0xf, _, 1, 0xe) => self.add_vx_to_i(digit2),
-            (0xf, _, 2, 9) => self.ld_f_vx(digit2),
-            (0xf, _, 3, 3) => self.store_bcd_of_vx_in_memory(digit2),
-            (0xf, _, 5, 5) => self.fill_memory_with_v0_to_vx(digit2),
-            (0xf, _, 6, 5) => self.fill_v0_to_vx_starting_at_i(digit2),
-            _ => return,
+        // A function item rather than a variable, so the handlers capture nothing
+        fn nibbles(op_code: u16) -> (u16, u16, u16, u16) {
+            Chip8Util::extract_nibbles(op_code)
         }
+
+        let handler: Handler = match nibbles(op_code) {
+            (0, 0, 0, 0) => |_, _| (),
+            (0, 0, 0xe, 0) => |chip8, _| chip8.clear_screen(),
+            (0, 0, 0xe, 0xe) => |chip8, _| chip8.return_from_subroutine(),
+            (0, _, _, _) => |chip8, op_code| chip8.sys_addr(op_code),
+            (0x1, _, _, _) => |chip8, op_code| chip8.jump(op_code),
+            (0x2, _, _, _) => |chip8, op_code| chip8.call_subroutine(op_code),
+            (0x3, _, _, _) => |chip8, op_code| {
+                let (_, x, k1, k2) = nibbles(op_code);
+                chip8.skip_if_equal(x, k1, k2)
+            },
+            (0x4, _, _, _) => |chip8, op_code| {
+                let (_, x, k1, k2) = nibbles(op_code);
+                chip8.skip_if_vx_not_eq_kk(x, k1, k2)
+            },
+            (0x5, _, _, 0) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.skip_if_vx_eq_vy(x, y)
+            },
+            (0x6, _, _, _) => |chip8, op_code| chip8.set_v_register(op_code),
+            (0x7, _, _, _) => |chip8, op_code| chip8.add_value_to_v_register(op_code),
+            (0x8, _, _, 0) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.store_vy_in_vx(x, y)
+            },
+            (0x8, _, _, 1) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.set_vx_with_vx_or_vy(x, y)
+            },
+            (0x8, _, _, 2) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.set_vx_with_vx_and_vy(x, y)
+            },
+            (0x8, _, _, 3) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.set_vx_with_vx_xor_vy(x, y)
+            },
+            (0x8, _, _, 4) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.add_vx_with_vy(x, y)
+            },
+            (0x8, _, _, 5) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.subtract_vy_from_vx(x, y)
+            },
+            (0x8, _, _, 6) => |chip8, op_code| chip8.shr_vx(nibbles(op_code).1),
+            (0x8, _, _, 7) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.subtract_vx_from_vy(x, y)
+            },
+            (0x8, _, _, 0xe) => |chip8, op_code| chip8.shl_vx(nibbles(op_code).1),
+            (0x9, _, _, 0) => |chip8, op_code| {
+                let (_, x, y, _) = nibbles(op_code);
+                chip8.skip_if_vx_ne_vy(x, y)
+            },
+            (0xa, _, _, _) => |chip8, op_code| chip8.set_i_register(op_code),
+            (0xb, _, _, _) => |chip8, op_code| chip8.jump_v0_addr(op_code),
+            (0xc, _, _, _) => |chip8, op_code| chip8.rnd_vx_byte(op_code),
+            (0xd, _, _, _) => |chip8, op_code| chip8.draw_sprite_to_screen(nibbles(op_code)),
+            (0xe, _, 9, 0xe) => |chip8, op_code| chip8.skp_vx(nibbles(op_code).1),
+            (0xe, _, 0xa, 1) => |chip8, op_code| chip8.sknp_vx(nibbles(op_code).1),
+            (0xf, _, 0, 7) => |chip8, op_code| chip8.ld_vx_dt(nibbles(op_code).1),
+            (0xf, _, 0, 0xa) => |chip8, op_code| chip8.ld_vx_k(nibbles(op_code).1),
+            (0xf, _, 1, 5) => |chip8, op_code| chip8.ld_dt_vx(nibbles(op_code).1),
+            (0xf, _, 1, 8) => |chip8, op_code| chip8.ld_st_vx(nibbles(op_code).1),
+            (0xf, _, 1, 0xe) => |chip8, op_code| chip8.add_vx_to_i(nibbles(op_code).1),
+            (0xf, _, 2, 9) => |chip8, op_code| chip8.ld_f_vx(nibbles(op_code).1),
+            (0xf, _, 3, 3) => |chip8, op_code| chip8.store_bcd_of_vx_in_memory(nibbles(op_code).1),
+            (0xf, _, 5, 5) => |chip8, op_code| chip8.fill_memory_with_v0_to_vx(nibbles(op_code).1),
+            (0xf, _, 6, 5) => {
+                |chip8, op_code| chip8.fill_v0_to_vx_starting_at_i(nibbles(op_code).1)
+            }
+            _ => |_, _| (),
+        };
+        handler
     }
 
     /// Enables debug mode, allowing step-by-step execution and inspection of the emulator state.
@@ -156,16 +362,36 @@ impl Chip8 {
         }
     }
 
+    /// Asks the frontend to repaint the whole screen on the next redraw.
+    pub fn request_full_redraw(&mut self) {
+        self.mark_dirty(DirtyRegion::FULL_SCREEN);
+        self.needs_redraw = true;
+    }
+
+    /// Forgets the changed areas of the screen once they have been drawn.
+    pub fn clear_redraw_state(&mut self) {
+        self.needs_redraw = false;
+        self.dirty_regions.clear();
+    }
+
     /// Resets the keyboard state by setting all keys to unpressed (false).
     pub fn reset_keyboard(&mut self) {
         self.keyboard.fill(false);
     }
 
-    /// Loads the ROM binary data into the emulator's memory.
+    /// Loads the ROM binary data into the emulator's memory. Bytes past the end of memory are
+    /// dropped, frontends reject such ROMs before starting them.
     fn load_rom(&mut self, rom_binary: Vec<u8>) {
         let start_ram_address = START_RAM_ADDRESS as usize;
-        self.ram[start_ram_address..(start_ram_address + rom_binary.len())]
-            .copy_from_slice(&rom_binary);
+        let length = rom_binary.len().min(MAX_ROM_SIZE);
+        self.ram[start_ram_address..(start_ram_address + length)]
+            .copy_from_slice(&rom_binary[..length]);
+    }
+
+    /// The RAM index of `offset` bytes past the I register. Addresses past the end of memory
+    /// wrap around to its start.
+    fn i_address(&self, offset: usize) -> usize {
+        (self.i_register as usize + offset) % CHIP8_RAM_MEMORY_SIZE
     }
 
     /// Sets the I register to the lowest 12 bits of the opcode.
@@ -176,9 +402,54 @@ impl Chip8 {
     /// Clears the display and sets the redraw flag.
     fn clear_screen(&mut self) {
         self.screen = CLEANED_SCREEN;
+        self.mark_dirty(DirtyRegion::FULL_SCREEN);
         self.needs_redraw = true;
     }
 
+    /// Records that a region of the screen changed. When a frame touches too many regions
+    /// they are replaced by a single full screen region, which is cheaper to repaint.
+    fn mark_dirty(&mut self, region: DirtyRegion) {
+        if self.dirty_regions.first() == Some(&DirtyRegion::FULL_SCREEN) {
+            return;
+        }
+
+        if region == DirtyRegion::FULL_SCREEN || self.dirty_regions.len() >= MAX_DIRTY_REGIONS {
+            self.dirty_regions.clear();
+            self.dirty_regions.push(DirtyRegion::FULL_SCREEN);
+        } else {
+            self.dirty_regions.push(region);
+        }
+    }
+
+    /// Marks the area covered by a sprite as dirty. A sprite crossing the right or bottom edge
+    /// wraps around, so it can cover up to four separate rectangles.
+    fn mark_sprite_dirty(&mut self, x_coord: usize, y_coord: usize, sprite_height: usize) {
+        let x = x_coord % SCREEN_WIDTH;
+        let y = y_coord % SCREEN_HEIGHT;
+
+        let columns = [
+            Some((x, 8.min(SCREEN_WIDTH - x))),
+            (x + 8 > SCREEN_WIDTH).then(|| (0, x + 8 - SCREEN_WIDTH)),
+        ];
+        let rows = [
+            Some((y, sprite_height.min(SCREEN_HEIGHT - y))),
+            (y + sprite_height > SCREEN_HEIGHT).then(|| (0, y + sprite_height - SCREEN_HEIGHT)),
+        ];
+
+        for (x, width) in columns.into_iter().flatten() {
+            for (y, height) in rows.into_iter().flatten() {
+                if height > 0 {
+                    self.mark_dirty(DirtyRegion {
+                        x,
+                        y,
+                        width,
+                        height,
+                    });
+                }
+            }
+        }
+    }
+
     /// Sets the value of a V register to the lowest 8 bits of the opcode.
     fn set_v_register(&mut self, op_code: u16) {
         let v_register_index = ((op_code & 0x0f00) >> 8) as usize;
@@ -209,43 +480,37 @@ impl Chip8 {
 
         let x_coord = self.v_registers[vx_register] as usize;
         let y_coord = self.v_registers[vy_register] as usize;
-        let i_register_value = self.i_register as usize;
         let mut pixel_change_to_unset = false;
 
         // Loop through each row of the sprite (height determines number of rows)
         for row in 0..sprite_height {
             // Read one byte from memory starting at I register + current row offset
             // Each byte represents 8 pixels (one row of the sprite)
-            let sprite_byte = &self.ram[i_register_value + row];
-            // println!("Sprite byte (row {:2}): {:08b}", row, sprite_byte);
-
-            // Process each of the 8 bits in this byte (8 pixels per row)
-            for col in 0..8 {
-                // Extract the current bit from the sprite byte
-                // We start from the most significant bit (bit 7) and work down
-                // Right shift by (7 - col) to move desired bit to position 0
-                // Then AND with 1 to isolate just that bit (0 or 1)
-                let sprite_pixel = (sprite_byte >> (7 - col)) & 1;
-
-                // If the sprite is positioned so part of it is outside the coordinates of the display,
-                // it wraps around to the opposite side of the screen.
-                let screen_x = (col + x_coord) as usize % SCREEN_WIDTH;
-                let screen_y = (row + y_coord) as usize % SCREEN_HEIGHT;
-
-                // Get the pixel position in the screen as an array (formula: screen_y * SCREEN_WIDTH + screen_x)
-                // This is the index in the screen array where we will set the pixel, example: considering a 64x32 screen:
-                // For screen_y = 0 and screen_x = 0, index = 0
-                // For screen_y = 0 and screen_x = 63, index = 63
-                // For screen_y = 1 and screen_x = 0, index = 64
-                let screen_index = screen_y * SCREEN_WIDTH + screen_x;
+            self.record_access(self.i_address(row), Access::Sprite);
+            let sprite_byte = self.ram[self.i_address(row)];
+
+            // If the sprite is positioned so part of it is outside the coordinates of the display,
+            // it wraps around to the opposite side of the screen.
+            // The row starts at index screen_y * SCREEN_WIDTH in the screen array, for example
+            // index 64 for screen_y = 1 on a 64x32 screen.
+            let row_start = ((row + y_coord) % SCREEN_HEIGHT) * SCREEN_WIDTH;
+
+            // Each bit of the byte is a pixel, from the most significant bit (column 0) down.
+            // XOR with an unset bit leaves the screen unchanged, so only the set bits are visited:
+            // leading_zeros gives the column of the next one, which is then cleared from `bits`
+            let mut bits = sprite_byte;
+            while bits != 0 {
+                let col = bits.leading_zeros() as usize;
+                bits &= !(0x80 >> col);
+
+                let screen_index = row_start + (col + x_coord) % SCREEN_WIDTH;
                 let old_pixel = self.screen[screen_index];
 
                 // XOR the current value in the screen with true
-                let new_pixel = self.screen[screen_index] ^ (sprite_pixel == 1);
-                self.screen[screen_index] = new_pixel;
+                self.screen[screen_index] = !old_pixel;
 
                 // If the XOR causes any pixels to be erased (set from true to false), VF is set to 1, otherwise it is set to 0
-                if old_pixel == true && new_pixel == false {
+                if old_pixel {
                     pixel_change_to_unset = true;
                 }
             }
@@ -256,7 +521,14 @@ impl Chip8 {
         } else {
             self.v_registers[0xF] = 0;
         }
+        self.mark_sprite_dirty(x_coord, y_coord, sprite_height);
         self.needs_redraw = true;
+        self.last_sprite = Some(SpriteDraw {
+            address: self.i_address(0) as u16,
+            height: sprite_height as u8,
+            x: x_coord as u8,
+            y: y_coord as u8,
+        });
     }
 
     /// Sets the program counter to the address specified by the opcode.
@@ -310,18 +582,34 @@ impl Chip8 {
     /// Call subroutine at nnn.
     /// The interpreter puts the current PC on the top of the stack and increments the stack pointer. The PC is then set to nnn.
     /// Calls a subroutine at the address specified by the opcode, pushing the current PC to the stack.
+    /// The stack is circular: a 17th nested call overwrites the oldest return address.
     fn call_subroutine(&mut self, op_code: u16) {
-        self.stack[self.stack_pointer] = self.pc;
-        self.stack_pointer += 1;
+        self.stack[self.stack_pointer % CHIP8_STACK_MEMORY_SIZE] = self.pc;
+        self.stack_pointer = (self.stack_pointer + 1) % CHIP8_STACK_MEMORY_SIZE;
+        self.stack_depth = (self.stack_depth + 1).min(CHIP8_STACK_MEMORY_SIZE);
         self.pc = op_code & 0x0fff;
     }
 
     /// 00EE - Return from a subroutine.
     /// The interpreter subtracts 1 from the stack pointer and sets the program counter to the address at the top of the stack.
     /// Returns from a subroutine by popping the address from the stack into the program counter.
+    /// Returning with an empty stack pops the last entry of the circular stack.
     fn return_from_subroutine(&mut self) {
-        self.stack_pointer -= 1;
+        self.stack_pointer =
+            (self.stack_pointer + CHIP8_STACK_MEMORY_SIZE - 1) % CHIP8_STACK_MEMORY_SIZE;
         self.pc = self.stack[self.stack_pointer];
+        self.stack_depth = self.stack_depth.saturating_sub(1);
+    }
+
+    /// The return addresses on the stack, oldest first. The stack is circular, so these are the
+    /// `stack_depth` entries below the stack pointer, wrapping around after 16 nested calls.
+    pub fn return_addresses(&self) -> Vec<u16> {
+        (0..self.stack_depth)
+            .map(|age| {
+                let index = self.stack_pointer + CHIP8_STACK_MEMORY_SIZE - self.stack_depth + age;
+                self.stack[index % CHIP8_STACK_MEMORY_SIZE]
+            })
+            .collect()
     }
 
     /// 8xy0 - LD Vx, Vy.
@@ -331,6 +619,7 @@ impl Chip8 {
     ///
     /// * `x` - The index of the Vx register to store the value in.
     /// * `y` - The index of the Vy register to read the value from.
+    ///
     /// Stores the value of register Vy in register Vx.
     fn store_vy_in_vx(&mut self, x: u16, y: u16) {
         self.v_registers[x as usize] = self.v_registers[y as usize];
@@ -389,19 +678,20 @@ impl Chip8 {
 
     /// 8xy5 - SUB Vx, Vy
     /// Set Vx = Vx - Vy, set VF = NOT borrow.
-    /// If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+    /// If Vx >= Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
     /// Subtracts Vy from Vx, sets VF to 1 if no borrow, 0 otherwise.
+    /// VF is written last, so it holds the flag when x is F.
     fn subtract_vy_from_vx(&mut self, x: u16, y: u16) {
         let vx = self.v_registers[x as usize];
         let vy = self.v_registers[y as usize];
 
-        if vx > vy {
+        self.v_registers[x as usize] = vx.wrapping_sub(vy);
+
+        if vx >= vy {
             self.v_registers[0xF] = 1;
         } else {
             self.v_registers[0xF] = 0;
         }
-
-        self.v_registers[x as usize] = vx.wrapping_sub(vy);
     }
 
     /// 8xy6 - SHR Vx {, Vy}
@@ -409,26 +699,29 @@ impl Chip8 {
     /// If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
     /// Shifts Vx right by one, stores least significant bit in VF.
     fn shr_vx(&mut self, x: u16) {
-        self.v_registers[0xF] = self.v_registers[x as usize] & 0x01;
+        let vx = self.v_registers[x as usize];
 
-        self.v_registers[x as usize] >>= 1;
+        self.v_registers[x as usize] = vx >> 1;
+
+        self.v_registers[0xF] = vx & 0x01;
     }
 
     /// 8xy7 - SUBN Vx, Vy
     /// Set Vx = Vy - Vx, set VF = NOT borrow.
-    /// If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+    /// If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
     /// Sets Vx to Vy minus Vx, sets VF to 1 if no borrow, 0 otherwise.
+    /// VF is written last, so it holds the flag when x is F.
     fn subtract_vx_from_vy(&mut self, x: u16, y: u16) {
         let vx = self.v_registers[x as usize];
         let vy = self.v_registers[y as usize];
 
-        if vy > vx {
+        self.v_registers[x as usize] = vy.wrapping_sub(vx);
+
+        if vy >= vx {
             self.v_registers[0xF] = 1;
         } else {
             self.v_registers[0xF] = 0;
         }
-
-        self.v_registers[x as usize] = vy.wrapping_sub(vx);
     }
 
     /// 8xyE - SHL Vx {, Vy}
@@ -436,9 +729,11 @@ impl Chip8 {
     /// If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
     /// Shifts Vx left by one, stores most significant bit in VF.
     fn shl_vx(&mut self, x: u16) {
-        self.v_registers[0xF] = (self.v_registers[x as usize] >> 7) & 0x01;
+        let vx = self.v_registers[x as usize];
 
-        self.v_registers[x as usize] <<= 1;
+        self.v_registers[x as usize] = vx << 1;
+
+        self.v_registers[0xF] = (vx >> 7) & 0x01;
     }
 
     /// Fx1E - ADD I, Vx
@@ -467,7 +762,8 @@ impl Chip8 {
         }
 
         for (index, bcd) in bcd_vx.iter().enumerate() {
-            self.ram[index + self.i_register as usize] = *bcd;
+            self.ram[self.i_address(index)] = *bcd;
+            self.record_access(self.i_address(index), Access::Written);
         }
     }
 
@@ -481,11 +777,11 @@ impl Chip8 {
     /// Stores registers V0 through Vx in memory starting at address I, then updates I.
     fn fill_memory_with_v0_to_vx(&mut self, x: u16) {
         for v_register_index in 0..=x as usize {
-            self.ram[self.i_register as usize + v_register_index] =
-                self.v_registers[v_register_index];
+            self.ram[self.i_address(v_register_index)] = self.v_registers[v_register_index];
+            self.record_access(self.i_address(v_register_index), Access::Written);
         }
 
-        self.i_register += x + 1;
+        self.i_register = self.i_register.wrapping_add(x + 1);
     }
 
     /// Fx65 - LD Vx, [I]
@@ -498,19 +794,17 @@ impl Chip8 {
     /// Fills V0 through Vx with values from memory starting at address I, then updates I.
     fn fill_v0_to_vx_starting_at_i(&mut self, x: u16) {
         for v_register_index in 0..=x as usize {
-            self.v_registers[v_register_index] =
-                self.ram[self.i_register as usize + v_register_index];
+            self.v_registers[v_register_index] = self.ram[self.i_address(v_register_index)];
+            self.record_access(self.i_address(v_register_index), Access::Read);
         }
 
-        self.i_register += x + 1;
+        self.i_register = self.i_register.wrapping_add(x + 1);
     }
 
     /// 0nnn - SYS addr
     /// Jump to a machine code routine at nnn. Ignored by most modern interpreters.
     /// Jumps to a machine code routine at nnn (ignored by most interpreters).
-    fn sys_addr(&mut self, _op_code: u16) {
-        return;
-    }
+    fn sys_addr(&mut self, _op_code: u16) {}
 
     /// 5xy0 - SE Vx, Vy
     /// Skip next instruction if Vx = Vy.
@@ -550,8 +844,7 @@ impl Chip8 {
     /// The results are stored in Vx. See instruction 8xy2 for more information on AND.
     /// Sets Vx to a random byte ANDed with kk.
     fn rnd_vx_byte(&mut self, op_code: u16) {
-        let mut rng = rand::thread_rng();
-        let random_byte: u8 = rng.gen_range(0..=255);
+        let random_byte: u8 = self.rng.gen_range(0..=255);
 
         let v_register_index = ((op_code & 0x0f00) >> 8) as usize;
         let kk = (op_code & 0x00ff) as u8;
@@ -562,9 +855,9 @@ impl Chip8 {
     /// Ex9E - SKP Vx
     /// Skip next instruction if key with the value of Vx is pressed.
     /// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
-    /// Skips the next instruction if the key in Vx is pressed.
+    /// Skips the next instruction if the key in Vx is pressed. Only the low nibble of Vx is used.
     fn skp_vx(&mut self, x: u16) {
-        let vx = self.v_registers[x as usize] as usize;
+        let vx = (self.v_registers[x as usize] & 0xF) as usize;
         if self.keyboard[vx] {
             self.pc += 2;
         }
@@ -573,9 +866,9 @@ impl Chip8 {
     /// ExA1 - SKNP Vx
     /// Skip next instruction if key with the value of Vx is not pressed.
     /// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
-    /// Skips the next instruction if the key in Vx is not pressed.
+    /// Skips the next instruction if the key in Vx is not pressed. Only the low nibble of Vx is used.
     fn sknp_vx(&mut self, x: u16) {
-        let vx = self.v_registers[x as usize] as usize;
+        let vx = (self.v_registers[x as usize] & 0xF) as usize;
         if !self.keyboard[vx] {
             self.pc += 2;
         }
@@ -594,7 +887,7 @@ impl Chip8 {
     fn ld_vx_k(&mut self, x: u16) {
         let mut key_was_pressed = false;
         for key in 0..self.keyboard.len() {
-            if self.keyboard[key] == true {
+            if self.keyboard[key] {
                 self.v_registers[x as usize] = key as u8;
                 key_was_pressed = true;
             }
@@ -602,7 +895,7 @@ impl Chip8 {
 
         // Redo this instruction until a key is pressed
         if !key_was_pressed {
-            self.pc -= 2;
+            self.pc = self.pc.wrapping_sub(2);
         }
     }
 
@@ -632,19 +925,22 @@ impl Chip8 {
     /// - Each digit sprite is 5 bytes long.
     /// - The sprite for digit N is located at memory address: N * 5.
     /// - The font set occupies the memory range 0x000 to 0x1FF.
+    /// - Only the low nibble of Vx is used.
     ///
     /// # Example
     /// ```
+    /// # use chip8_core::chip8::Chip8;
+    /// # let mut chip8 = Chip8::new();
     /// // Suppose Vx contains 0xA (decimal 10)
     /// chip8.v_registers[0] = 0xA;
-    /// chip8.ld_f_vx(0);
+    /// chip8.decode_execute(0xF029); // LD F, V0
     /// assert_eq!(chip8.i_register, 0x32); // 0xA * 5 = 0x32
     /// ```
     ///
     /// This instruction is typically used before drawing a digit sprite to the screen.
     fn ld_f_vx(&mut self, x: u16) {
-        let vx = self.v_registers[x as usize];
-        self.i_register = (vx * 5) as u16;
+        let digit = self.v_registers[x as usize] & 0xF;
+        self.i_register = digit as u16 * 5;
     }
 
     /// Loads the font sprites into memory at the start of RAM.
@@ -652,3 +948,89 @@ impl Chip8 {
         self.ram[0x0..FONT_SPRITES_SIZE].copy_from_slice(FONT_SPRITES.as_slice());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `DRW V0, V1, height` with the sprite at `(x, y)` and returns the dirty regions.
+    fn draw_at(x: u8, y: u8, height: u8) -> Vec<DirtyRegion> {
+        let mut chip8 = Chip8::start(vec![0xD0, 0x10 | height]);
+        chip8.v_registers[0] = x;
+        chip8.v_registers[1] = y;
+        chip8.tick();
+        chip8.dirty_regions
+    }
+
+    fn region(x: usize, y: usize, width: usize, height: usize) -> DirtyRegion {
+        DirtyRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn sprites_inside_the_screen_mark_one_region() {
+        assert_eq!(draw_at(10, 20, 5), vec![region(10, 20, 8, 5)]);
+    }
+
+    #[test]
+    fn sprites_wrapping_horizontally_mark_two_regions() {
+        assert_eq!(
+            draw_at(60, 20, 5),
+            vec![region(60, 20, 4, 5), region(0, 20, 4, 5)]
+        );
+    }
+
+    #[test]
+    fn sprites_wrapping_vertically_mark_two_regions() {
+        assert_eq!(
+            draw_at(10, 30, 5),
+            vec![region(10, 30, 8, 2), region(10, 0, 8, 3)]
+        );
+    }
+
+    #[test]
+    fn sprites_wrapping_in_a_corner_mark_four_regions() {
+        assert_eq!(
+            draw_at(62, 29, 5),
+            vec![
+                region(62, 29, 2, 3),
+                region(62, 0, 2, 2),
+                region(0, 29, 6, 3),
+                region(0, 0, 6, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn idle_frames_run_in_full_while_profiling() {
+        let mut chip8 = Chip8::new();
+        chip8.set_idle_skip(true);
+        assert!(chip8.skips_idle());
+
+        chip8.set_profiler(true);
+        assert!(!chip8.skips_idle());
+    }
+
+    #[test]
+    fn too_many_regions_become_a_full_redraw() {
+        let mut rom = Vec::new();
+        for _ in 0..=MAX_DIRTY_REGIONS {
+            rom.extend_from_slice(&[0xD0, 0x11, 0x70, 0x01]); // DRW V0, V1, 1 and ADD V0, 1
+        }
+        let mut chip8 = Chip8::start(rom);
+
+        for _ in 0..MAX_DIRTY_REGIONS {
+            chip8.tick();
+            chip8.tick();
+        }
+        assert_eq!(chip8.dirty_regions.len(), MAX_DIRTY_REGIONS);
+        assert!(!chip8.dirty_regions.contains(&DirtyRegion::FULL_SCREEN));
+
+        chip8.tick();
+        assert_eq!(chip8.dirty_regions, vec![DirtyRegion::FULL_SCREEN]);
+    }
+}
@@ -1,5 +1,3 @@
-use std::{fs::File, io::{self, Read}};
-
 use crate::chip8::Chip8;
 
 pub struct Chip8Util;
@@ -127,6 +125,10 @@ impl Chip8Util {
             "Instruction Count: {} - PC: {:04x} - OpCode: {:04x}\n{}",
             chip8.instructions_executed, chip8.pc, op_code, instruction_desc
         );
+        if let Some(symbols) = chip8.symbols() {
+            // The program counter already points past the instruction
+            println!("At {}", symbols.describe(chip8.pc.wrapping_sub(2)));
+        }
         println!("-----------------------------------------------------");
         chip8.instructions_executed += 1;
     }
@@ -139,7 +141,8 @@ impl Chip8Util {
     ///
     /// # Example
     /// ```
-    /// let digits = extract_digits(153);
+    /// # use chip8_core::chip8_util::Chip8Util;
+    /// let digits = Chip8Util::extract_digits(153);
     /// assert_eq!(digits, vec![1, 5, 3]);
     /// ```
     pub fn extract_digits(mut n: u8) -> Vec<u8> {
@@ -156,24 +159,25 @@ impl Chip8Util {
 
         digits.reverse();
 
-        return digits;
+        digits
     }
 
-    /// Reads a ROM file from the specified path and returns its contents as a vector of bytes.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the file cannot be read.
-    pub fn read_rom(file_path: &str) -> io::Result<Vec<u8>> {
-        // Open the file
-        let file = File::open(file_path)?;
-        let mut reader = io::BufReader::new(file);
-
-        let mut buffer: Vec<u8> = Vec::new();
-
-        reader.read_to_end(&mut buffer)?;
-        // Collect the lines into a vector
-        // let lines: Vec<String> = buffer.iter().collect();
-        Ok(buffer)
+    /// Parses hex bytes such as `A2 F0` or `A2F0`, with `??` matching any byte when `wildcards`
+    /// is true.
+    pub fn parse_hex_bytes(text: &str, wildcards: bool) -> Result<Vec<Option<u8>>, String> {
+        let hex: String = text.split_whitespace().collect();
+        if hex.is_empty() || !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return Err(format!("'{}' is not a sequence of hex bytes", text));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| match &hex[index..index + 2] {
+                "??" if wildcards => Ok(None),
+                pair if pair.bytes().all(|byte| byte.is_ascii_hexdigit()) => {
+                    Ok(u8::from_str_radix(pair, 16).ok())
+                }
+                pair => Err(format!("'{}' is not a hex byte", pair)),
+            })
+            .collect()
     }
 }
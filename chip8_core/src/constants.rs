@@ -0,0 +1,38 @@
+pub const CHIP8_RAM_MEMORY_SIZE: usize = 4096;
+pub const CHIP8_STACK_MEMORY_SIZE: usize = 16;
+pub const CHIP8_REGISTER_COUNT: usize = 16;
+
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+pub const START_RAM_ADDRESS: u16 = 0x200;
+pub const MAX_ROM_SIZE: usize = CHIP8_RAM_MEMORY_SIZE - START_RAM_ADDRESS as usize;
+/// Above this many changed rectangles in a frame the whole screen is redrawn instead.
+pub const MAX_DIRTY_REGIONS: usize = 32;
+pub const CLEANED_SCREEN: [bool; SCREEN_WIDTH * SCREEN_HEIGHT] =
+    [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+pub const FONT_SPRITES_SIZE: usize = 80;
+
+pub const FONT_SPRITES: [u8; FONT_SPRITES_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+pub const KEYBOARD_KEYS_COUNT: usize = 16;
+
+/// Rate of the delay and sound timers, and of the frames of the run loop.
+pub const FRAMES_PER_SECOND: usize = 60;
@@ -0,0 +1,334 @@
+use std::fmt::Write;
+
+use crate::{
+    constants::{CHIP8_RAM_MEMORY_SIZE, START_RAM_ADDRESS},
+    disassembler::{self, Line},
+};
+
+/// Colours of the hex view, by CSS class.
+const HEX_VIEW_STYLE: &str = "\
+body { background: #1e1e1e; color: #808080; font-family: monospace; }
+span { padding: 0 2px; }
+.code { color: #ffffff; background: #2a5a8a; }
+.sprite { color: #000000; background: #6ac46a; }
+.read { color: #000000; background: #e0c050; }
+.written { color: #ffffff; background: #b04040; }
+.rom { color: #c8c8c8; }";
+
+/// A way a program used a byte of memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Fetched as part of an instruction.
+    Executed,
+    /// Drawn as a sprite row by Dxyn.
+    Sprite,
+    /// Read into a register by Fx65.
+    Read,
+    /// Written by Fx33 or Fx55.
+    Written,
+}
+
+impl Access {
+    pub const ALL: [Access; 4] = [
+        Access::Executed,
+        Access::Sprite,
+        Access::Read,
+        Access::Written,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// The name used in reports and the CSS class of the hex view.
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Executed => "code",
+            Access::Sprite => "sprite",
+            Access::Read => "read",
+            Access::Written => "written",
+        }
+    }
+}
+
+/// The bytes of memory each kind of [`Access`] used while a program ran, once enabled with
+/// [`crate::chip8::Chip8::set_coverage`]. Tells ROM authors which code never ran and which bytes
+/// are sprites or variables, and separates code from data in disassemblies.
+#[derive(Clone)]
+pub struct Coverage {
+    accesses: Box<[u8]>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            accesses: vec![0; CHIP8_RAM_MEMORY_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Records an access to the byte at `address`, wrapped to the size of memory.
+    pub fn record(&mut self, address: usize, access: Access) {
+        self.accesses[address % CHIP8_RAM_MEMORY_SIZE] |= access.bit();
+    }
+
+    /// Whether the byte at `address` was used by `access`.
+    pub fn has(&self, address: u16, access: Access) -> bool {
+        self.accesses[address as usize % CHIP8_RAM_MEMORY_SIZE] & access.bit() != 0
+    }
+
+    /// Whether the byte at `address` was used at all.
+    pub fn is_used(&self, address: u16) -> bool {
+        self.accesses[address as usize % CHIP8_RAM_MEMORY_SIZE] != 0
+    }
+
+    /// Whether the byte at `address` is data rather than code: it was used but not executed, or
+    /// it was not used and comes before an executed or data byte that a two-byte instruction
+    /// would overlap.
+    pub fn is_data(&self, address: u16) -> bool {
+        let next = address.wrapping_add(1);
+        let executed = self.has(address, Access::Executed);
+        !executed
+            && (self.is_used(address)
+                || self.has(next, Access::Executed)
+                || (self.is_used(next) && !self.has(next, Access::Executed)))
+    }
+
+    /// A report of how the `rom_length` bytes of the ROM were used, with the number of bytes of
+    /// each kind of access and the ranges of bytes used the same way, in the ROM or elsewhere.
+    pub fn report(&self, rom_length: usize) -> String {
+        let rom = rom_addresses(rom_length);
+        let percent = |count: usize| 100.0 * count as f64 / rom.len().max(1) as f64;
+        let mut report = String::new();
+
+        writeln!(
+            report,
+            "Coverage of the {} bytes of the ROM ({:03X}-{:03X})",
+            rom.len(),
+            rom.start,
+            rom.end.max(rom.start + 1) - 1
+        )
+        .unwrap();
+        for access in Access::ALL {
+            let count = rom.clone().filter(|&a| self.has(a, access)).count();
+            writeln!(
+                report,
+                "  {:<8} {:>5}  {:>6.2}%",
+                access.name(),
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+        let unused = rom.clone().filter(|&a| !self.is_used(a)).count();
+        writeln!(
+            report,
+            "  {:<8} {:>5}  {:>6.2}%",
+            "unused",
+            unused,
+            percent(unused)
+        )
+        .unwrap();
+
+        writeln!(report, "\nRanges:").unwrap();
+        let mut address = 0;
+        while address < CHIP8_RAM_MEMORY_SIZE as u16 {
+            let accesses = self.accesses[address as usize];
+            let in_rom = rom.contains(&address);
+            let mut end = address + 1;
+            while end < CHIP8_RAM_MEMORY_SIZE as u16
+                && self.accesses[end as usize] == accesses
+                && rom.contains(&end) == in_rom
+            {
+                end += 1;
+            }
+            if accesses != 0 || in_rom {
+                writeln!(
+                    report,
+                    "  {:03X}-{:03X}  {}{}",
+                    address,
+                    end - 1,
+                    self.names(address),
+                    if in_rom { "" } else { " (outside the ROM)" }
+                )
+                .unwrap();
+            }
+            address = end;
+        }
+        report
+    }
+
+    /// The ROM as instructions and data bytes: bytes that were never executed but drawn, read
+    /// or written are listed as data, sprite rows with their pixels. Unused bytes are marked.
+    pub fn disassembly(&self, ram: &[u8], rom_length: usize) -> String {
+        let rom = rom_addresses(rom_length);
+        let mut listing = String::new();
+        for line in disassembler::listing(ram, rom.start, rom.end, |a| self.is_data(a)) {
+            match line {
+                Line::Instruction { address, op_code } => writeln!(
+                    listing,
+                    "{:<8} {:03X}  {:04X}  {}",
+                    self.names(address),
+                    address,
+                    op_code,
+                    disassembler::disassemble(op_code)
+                ),
+                Line::Data { address, byte } => {
+                    let pixels = if self.has(address, Access::Sprite) {
+                        format!("  ; {:08b}", byte)
+                            .replace('0', ".")
+                            .replace('1', "#")
+                    } else {
+                        String::new()
+                    };
+                    writeln!(
+                        listing,
+                        "{:<8} {:03X}  {:02X}    DB 0x{:02X}{}",
+                        self.names(address),
+                        address,
+                        byte,
+                        byte,
+                        pixels
+                    )
+                }
+            }
+            .unwrap();
+        }
+        listing
+    }
+
+    /// An HTML page showing the rows of 16 bytes of `ram` that hold the ROM or were used,
+    /// with each byte coloured by its accesses, the first one when there are several.
+    pub fn hex_view(&self, ram: &[u8], rom_length: usize) -> String {
+        let rom = rom_addresses(rom_length);
+        let mut html = String::new();
+        writeln!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+        )
+        .unwrap();
+        writeln!(html, "<title>CHIP-8 coverage</title>").unwrap();
+        writeln!(
+            html,
+            "<style>\n{}\n</style>\n</head>\n<body>\n<pre>",
+            HEX_VIEW_STYLE
+        )
+        .unwrap();
+        for access in Access::ALL {
+            write!(html, "<span class=\"{0}\">{0}</span> ", access.name()).unwrap();
+        }
+        writeln!(html, "<span class=\"rom\">unused ROM</span>\n").unwrap();
+
+        for row in (0..CHIP8_RAM_MEMORY_SIZE as u16).step_by(16) {
+            let addresses = row..row + 16;
+            if !addresses
+                .clone()
+                .any(|a| self.is_used(a) || rom.contains(&a))
+            {
+                continue;
+            }
+            write!(html, "{:03X} ", row).unwrap();
+            for address in addresses {
+                let class = match Access::ALL.iter().find(|&&a| self.has(address, a)) {
+                    Some(access) => access.name(),
+                    None if rom.contains(&address) => "rom",
+                    None => "",
+                };
+                write!(
+                    html,
+                    " <span class=\"{}\">{:02X}</span>",
+                    class, ram[address as usize]
+                )
+                .unwrap();
+            }
+            writeln!(html).unwrap();
+        }
+        writeln!(html, "</pre>\n</body>\n</html>").unwrap();
+        html
+    }
+
+    /// The names of the accesses to the byte at `address`, e.g. `sprite+read`, or `unused`.
+    fn names(&self, address: u16) -> String {
+        let names: Vec<&str> = Access::ALL
+            .into_iter()
+            .filter(|&access| self.has(address, access))
+            .map(Access::name)
+            .collect();
+        if names.is_empty() {
+            String::from("unused")
+        } else {
+            names.join("+")
+        }
+    }
+}
+
+/// The addresses a ROM of `rom_length` bytes was loaded at.
+fn rom_addresses(rom_length: usize) -> std::ops::Range<u16> {
+    let end = (START_RAM_ADDRESS as usize + rom_length).min(CHIP8_RAM_MEMORY_SIZE);
+    START_RAM_ADDRESS..end as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    /// LD I, 0x20A, DRW V0, V0, 2, LD I, 0x20C, LD B, V0, JP 0x208, then 2 sprite rows,
+    /// the 3 BCD digits and an unused byte.
+    fn run() -> (Chip8, usize) {
+        let rom = vec![
+            0xA2, 0x0A, 0xD0, 0x02, 0xA2, 0x0C, 0xF0, 0x33, 0x12, 0x08, 0x3C, 0x42, 0x00, 0x00,
+            0x00, 0xFF,
+        ];
+        let rom_length = rom.len();
+        let mut chip8 = Chip8::start(rom);
+        chip8.set_coverage(true);
+        chip8.run_frame(10);
+        (chip8, rom_length)
+    }
+
+    #[test]
+    fn accesses_are_recorded_by_byte() {
+        let (chip8, _) = run();
+        let coverage = chip8.coverage().unwrap();
+
+        assert!((0x200..0x20A).all(|a| coverage.has(a, Access::Executed)));
+        assert!(coverage.has(0x20A, Access::Sprite) && coverage.has(0x20B, Access::Sprite));
+        assert!((0x20C..0x20F).all(|a| coverage.has(a, Access::Written)));
+        assert!(!coverage.is_used(0x20F));
+        assert!(!coverage.is_used(0x210));
+    }
+
+    #[test]
+    fn reports_list_the_ranges_used_the_same_way() {
+        let (chip8, rom_length) = run();
+        let report = chip8.coverage().unwrap().report(rom_length);
+
+        assert!(report.starts_with("Coverage of the 16 bytes of the ROM (200-20F)\n"));
+        assert!(report.contains("  code        10   62.50%\n"));
+        assert!(report.contains("  200-209  code\n  20A-20B  sprite\n  20C-20E  written\n"));
+        assert!(report.contains("  20F-20F  unused\n"));
+    }
+
+    #[test]
+    fn disassemblies_separate_code_from_data() {
+        let (chip8, rom_length) = run();
+        let coverage = chip8.coverage().unwrap();
+        let listing = coverage.disassembly(&chip8.ram, rom_length);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(lines[4], "code     208  1208  JP 0x208");
+        assert_eq!(lines[5], "sprite   20A  3C    DB 0x3C  ; ..####..");
+        assert_eq!(lines[7], "written  20C  00    DB 0x00");
+        assert_eq!(lines.len(), 11);
+
+        let html = coverage.hex_view(&chip8.ram, rom_length);
+        assert!(html.contains("200  <span class=\"code\">A2</span>"));
+        assert!(html.contains("<span class=\"rom\">FF</span>"));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::chip8_util::Chip8Util;
+
+/// The instruction pattern of an opcode, e.g. `8xy4` for 8124, or `data` when it is not an
+/// instruction.
+pub fn pattern(op_code: u16) -> &'static str {
+    match Chip8Util::extract_nibbles(op_code) {
+        (0, 0, 0xe, 0) => "00E0",
+        (0, 0, 0xe, 0xe) => "00EE",
+        (0, _, _, _) => "0nnn",
+        (0x1, _, _, _) => "1nnn",
+        (0x2, _, _, _) => "2nnn",
+        (0x3, _, _, _) => "3xkk",
+        (0x4, _, _, _) => "4xkk",
+        (0x5, _, _, 0) => "5xy0",
+        (0x6, _, _, _) => "6xkk",
+        (0x7, _, _, _) => "7xkk",
+        (0x8, _, _, 0) => "8xy0",
+        (0x8, _, _, 1) => "8xy1",
+        (0x8, _, _, 2) => "8xy2",
+        (0x8, _, _, 3) => "8xy3",
+        (0x8, _, _, 4) => "8xy4",
+        (0x8, _, _, 5) => "8xy5",
+        (0x8, _, _, 6) => "8xy6",
+        (0x8, _, _, 7) => "8xy7",
+        (0x8, _, _, 0xe) => "8xyE",
+        (0x9, _, _, 0) => "9xy0",
+        (0xa, _, _, _) => "Annn",
+        (0xb, _, _, _) => "Bnnn",
+        (0xc, _, _, _) => "Cxkk",
+        (0xd, _, _, _) => "Dxyn",
+        (0xe, _, 9, 0xe) => "Ex9E",
+        (0xe, _, 0xa, 1) => "ExA1",
+        (0xf, _, 0, 7) => "Fx07",
+        (0xf, _, 0, 0xa) => "Fx0A",
+        (0xf, _, 1, 5) => "Fx15",
+        (0xf, _, 1, 8) => "Fx18",
+        (0xf, _, 1, 0xe) => "Fx1E",
+        (0xf, _, 2, 9) => "Fx29",
+        (0xf, _, 3, 3) => "Fx33",
+        (0xf, _, 5, 5) => "Fx55",
+        (0xf, _, 6, 5) => "Fx65",
+        _ => "data",
+    }
+}
+
+/// The assembly of an opcode, e.g. `ADD V1, V2` for 8124, with addresses and bytes in hex.
+/// Opcodes that are not instructions are shown as `DW 0x1234`.
+///
+/// ```
+/// use chip8_core::disassembler::disassemble;
+///
+/// assert_eq!(disassemble(0xA2F0), "LD I, 0x2F0");
+/// assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+/// ```
+pub fn disassemble(op_code: u16) -> String {
+    let (_, x, y, n) = Chip8Util::extract_nibbles(op_code);
+    let nnn = op_code & 0x0fff;
+    let kk = op_code & 0x00ff;
+    match pattern(op_code) {
+        "00E0" => String::from("CLS"),
+        "00EE" => String::from("RET"),
+        "0nnn" => format!("SYS 0x{:03X}", nnn),
+        "1nnn" => format!("JP 0x{:03X}", nnn),
+        "2nnn" => format!("CALL 0x{:03X}", nnn),
+        "3xkk" => format!("SE V{:X}, 0x{:02X}", x, kk),
+        "4xkk" => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        "5xy0" => format!("SE V{:X}, V{:X}", x, y),
+        "6xkk" => format!("LD V{:X}, 0x{:02X}", x, kk),
+        "7xkk" => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        "8xy0" => format!("LD V{:X}, V{:X}", x, y),
+        "8xy1" => format!("OR V{:X}, V{:X}", x, y),
+        "8xy2" => format!("AND V{:X}, V{:X}", x, y),
+        "8xy3" => format!("XOR V{:X}, V{:X}", x, y),
+        "8xy4" => format!("ADD V{:X}, V{:X}", x, y),
+        "8xy5" => format!("SUB V{:X}, V{:X}", x, y),
+        "8xy6" => format!("SHR V{:X}", x),
+        "8xy7" => format!("SUBN V{:X}, V{:X}", x, y),
+        "8xyE" => format!("SHL V{:X}", x),
+        "9xy0" => format!("SNE V{:X}, V{:X}", x, y),
+        "Annn" => format!("LD I, 0x{:03X}", nnn),
+        "Bnnn" => format!("JP V0, 0x{:03X}", nnn),
+        "Cxkk" => format!("RND V{:X}, 0x{:02X}", x, kk),
+        "Dxyn" => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        "Ex9E" => format!("SKP V{:X}", x),
+        "ExA1" => format!("SKNP V{:X}", x),
+        "Fx07" => format!("LD V{:X}, DT", x),
+        "Fx0A" => format!("LD V{:X}, K", x),
+        "Fx15" => format!("LD DT, V{:X}", x),
+        "Fx18" => format!("LD ST, V{:X}", x),
+        "Fx1E" => format!("ADD I, V{:X}", x),
+        "Fx29" => format!("LD F, V{:X}", x),
+        "Fx33" => format!("LD B, V{:X}", x),
+        "Fx55" => format!("LD [I], V{:X}", x),
+        "Fx65" => format!("LD V{:X}, [I]", x),
+        _ => format!("DW 0x{:04X}", op_code),
+    }
+}
+
+/// A line of a [`listing`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line {
+    Instruction { address: u16, op_code: u16 },
+    Data { address: u16, byte: u8 },
+}
+
+/// Lists the bytes of `ram` from `start` to `end` (excluded) as instructions, except the bytes
+/// for which `is_data` is true, which are listed one at a time. A run, such as a coverage map,
+/// tells which bytes are data, including the byte before an instruction at an odd offset.
+///
+/// ```
+/// use chip8_core::disassembler::{Line, listing};
+///
+/// let ram = [0x12, 0x03, 0x3C, 0x12, 0x03];
+/// assert_eq!(
+///     listing(&ram, 0, 5, |address| address == 2),
+///     [
+///         Line::Instruction { address: 0, op_code: 0x1203 },
+///         Line::Data { address: 2, byte: 0x3C },
+///         Line::Instruction { address: 3, op_code: 0x1203 },
+///     ]
+/// );
+/// ```
+pub fn listing(ram: &[u8], start: u16, end: u16, is_data: impl Fn(u16) -> bool) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = start;
+    while address < end {
+        let index = address as usize % ram.len();
+        if is_data(address) {
+            lines.push(Line::Data {
+                address,
+                byte: ram[index],
+            });
+            address += 1;
+        } else {
+            let op_code = u16::from_be_bytes([ram[index], ram[(index + 1) % ram.len()]]);
+            lines.push(Line::Instruction { address, op_code });
+            address += 2;
+        }
+    }
+    lines
+}
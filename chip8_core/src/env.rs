@@ -0,0 +1,389 @@
+//! A reinforcement-learning environment in the style of Gym: an agent picks one of a game's
+//! actions, the machine runs a few frames with the matching keys held, and the agent gets the
+//! screen, a reward and whether the episode ended.
+//!
+//! ```
+//! use chip8_core::env::{Chip8Env, EnvOptions, Pong};
+//!
+//! let rom = std::fs::read("../files/roms/PONG").unwrap();
+//! let mut env = Chip8Env::new(rom, Box::new(Pong::default()), EnvOptions::default());
+//! let observation = env.reset(42);
+//! assert_eq!(env.action_count(), 3); // Nothing, up, down
+//!
+//! let (observation, reward, done) = env.step(1);
+//! assert_eq!((reward, done), (0.0, false));
+//! ```
+//!
+//! Rewards and episode ends are game specific: they come from a [`GameRules`], which reads
+//! them from the machine, usually from the score kept in RAM.
+
+use crate::{
+    chip8::Chip8,
+    constants::{KEYBOARD_KEYS_COUNT, SCREEN_HEIGHT, SCREEN_WIDTH},
+    frontend::{FrameOutcome, Frontend, InputSource, InputState, RunLoop},
+    headless::{NullAudio, NullVideo, VirtualClock},
+};
+
+/// The screen, row by row, true for a lit pixel.
+pub type Observation = [bool; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+/// Keys of an action, bit `k` set when key `k` is held.
+pub type KeyMask = u16;
+
+/// Reward and termination extractors of a game.
+pub trait GameRules {
+    /// The actions the agent chooses from, as held keys. The first one is usually "no key".
+    fn actions(&self) -> Vec<KeyMask> {
+        let each_key = (0..KEYBOARD_KEYS_COUNT).map(|key| 1 << key);
+        std::iter::once(0).chain(each_key).collect()
+    }
+
+    /// Called when an episode starts, before any frame ran.
+    fn reset(&mut self, _chip8: &Chip8) {}
+
+    /// Reward earned during the frame that just ran.
+    fn reward(&mut self, chip8: &Chip8) -> f64;
+
+    /// Whether the episode is over after the frame that just ran.
+    fn is_done(&self, chip8: &Chip8) -> bool;
+}
+
+/// Rules of a game without known score: no reward, and episodes end only with
+/// [`EnvOptions::max_steps`]. Every key, alone, is an action.
+#[derive(Debug, Default)]
+pub struct NoRules;
+
+impl GameRules for NoRules {
+    fn reward(&mut self, _chip8: &Chip8) -> f64 {
+        0.0
+    }
+
+    fn is_done(&self, _chip8: &Chip8) -> bool {
+        false
+    }
+}
+
+/// A score stored as one byte of RAM: the reward is its increase, and the episode ends once it
+/// reaches `target`, if any.
+#[derive(Debug)]
+pub struct RamScore {
+    address: usize,
+    target: Option<u8>,
+    actions: Vec<KeyMask>,
+    last_score: u8,
+}
+
+impl RamScore {
+    pub fn new(address: u16, target: Option<u8>, actions: Vec<KeyMask>) -> Self {
+        Self {
+            address: address as usize,
+            target,
+            actions,
+            last_score: 0,
+        }
+    }
+}
+
+impl GameRules for RamScore {
+    fn actions(&self) -> Vec<KeyMask> {
+        self.actions.clone()
+    }
+
+    fn reset(&mut self, chip8: &Chip8) {
+        self.last_score = chip8.ram[self.address];
+    }
+
+    fn reward(&mut self, chip8: &Chip8) -> f64 {
+        let score = chip8.ram[self.address];
+        let reward = f64::from(score) - f64::from(self.last_score);
+        self.last_score = score;
+        reward
+    }
+
+    fn is_done(&self, chip8: &Chip8) -> bool {
+        self.target
+            .is_some_and(|target| chip8.ram[self.address] >= target)
+    }
+}
+
+/// Address of the score of PONG, written in BCD by Fx33 after each point:
+/// 0x2F3 holds the left player's points and 0x2F4 the right player's.
+const PONG_SCORE_ADDRESS: usize = 0x2F2;
+
+/// PONG, played by the left paddle (keys 1 and 4) against the right one. A point won is
+/// rewarded with 1, a point lost with -1, and the episode ends when a player reaches
+/// `winning_score`, at most 9 since each score is a single digit.
+#[derive(Debug)]
+pub struct Pong {
+    winning_score: u8,
+    last_scores: (u8, u8),
+}
+
+impl Pong {
+    pub fn new(winning_score: u8) -> Self {
+        Self {
+            winning_score: winning_score.clamp(1, 9),
+            last_scores: (0, 0),
+        }
+    }
+
+    /// Points of the left and right players.
+    pub fn scores(chip8: &Chip8) -> (u8, u8) {
+        (
+            chip8.ram[PONG_SCORE_ADDRESS + 1],
+            chip8.ram[PONG_SCORE_ADDRESS + 2],
+        )
+    }
+}
+
+impl Default for Pong {
+    fn default() -> Self {
+        Self::new(9)
+    }
+}
+
+impl GameRules for Pong {
+    fn actions(&self) -> Vec<KeyMask> {
+        vec![0, 1 << 0x1, 1 << 0x4]
+    }
+
+    fn reset(&mut self, chip8: &Chip8) {
+        self.last_scores = Self::scores(chip8);
+    }
+
+    fn reward(&mut self, chip8: &Chip8) -> f64 {
+        let (left, right) = Self::scores(chip8);
+        let (last_left, last_right) = self.last_scores;
+        self.last_scores = (left, right);
+        f64::from(left.saturating_sub(last_left)) - f64::from(right.saturating_sub(last_right))
+    }
+
+    fn is_done(&self, chip8: &Chip8) -> bool {
+        let (left, right) = Self::scores(chip8);
+        left >= self.winning_score || right >= self.winning_score
+    }
+}
+
+/// The rules of a bundled ROM, by file name, or [`NoRules`] for the others.
+pub fn rules_for_rom(name: &str) -> Box<dyn GameRules> {
+    match name {
+        "PONG" => Box::new(Pong::default()),
+        _ => Box::new(NoRules),
+    }
+}
+
+/// How the environment runs the machine.
+#[derive(Debug, Clone, Copy)]
+pub struct EnvOptions {
+    pub instructions_per_frame: usize,
+    /// Frames run by each step, whose rewards are summed; the observation is the last frame.
+    pub frame_skip: usize,
+    /// Frames of each step during which the action's keys are held; the step's following frames
+    /// run with every key released, for games that wait for a release between two presses.
+    pub action_repeat: usize,
+    /// Steps after which an episode ends even if the game did not.
+    pub max_steps: Option<usize>,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        Self {
+            instructions_per_frame: 10,
+            frame_skip: 4,
+            action_repeat: 4,
+            max_steps: None,
+        }
+    }
+}
+
+/// Keys held by the agent, read by the run loop at each frame.
+#[derive(Debug, Default)]
+pub struct ActionInput {
+    keys: [bool; KEYBOARD_KEYS_COUNT],
+}
+
+impl ActionInput {
+    fn hold(&mut self, mask: KeyMask) {
+        for (key, pressed) in self.keys.iter_mut().enumerate() {
+            *pressed = mask & (1 << key) != 0;
+        }
+    }
+}
+
+impl InputSource for ActionInput {
+    fn poll_input(&mut self) -> InputState {
+        InputState {
+            keys: self.keys,
+            ..InputState::default()
+        }
+    }
+}
+
+/// The headless frontend of the environment, whose input is the agent's action.
+pub type EnvHost = Frontend<NullVideo, ActionInput, NullAudio, VirtualClock>;
+
+/// A CHIP-8 game as a reinforcement-learning environment.
+pub struct Chip8Env {
+    rom: Vec<u8>,
+    rules: Box<dyn GameRules>,
+    actions: Vec<KeyMask>,
+    options: EnvOptions,
+    run_loop: RunLoop<EnvHost>,
+    steps: usize,
+    done: bool,
+}
+
+impl Chip8Env {
+    /// Creates an environment playing `rom`. Call [`Chip8Env::reset`] to start an episode.
+    pub fn new(rom: Vec<u8>, rules: Box<dyn GameRules>, options: EnvOptions) -> Self {
+        let actions = rules.actions();
+        let host = Frontend {
+            video: NullVideo,
+            input: ActionInput::default(),
+            audio: NullAudio,
+            time: VirtualClock::default(),
+        };
+        let run_loop = RunLoop::new(Chip8::new(), host, options.instructions_per_frame);
+        Self {
+            rom,
+            rules,
+            actions,
+            options,
+            run_loop,
+            steps: 0,
+            done: true,
+        }
+    }
+
+    /// Number of actions, so valid actions are `0..action_count()`.
+    pub fn action_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// The keys held by each action.
+    pub fn actions(&self) -> &[KeyMask] {
+        &self.actions
+    }
+
+    /// The machine, e.g. to read RAM or save its state.
+    pub fn chip8(&self) -> &Chip8 {
+        &self.run_loop.chip8
+    }
+
+    /// Steps taken in the current episode.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Restarts the ROM with `seed` for its random numbers, so episodes with the same seed and
+    /// actions are identical, and returns the first observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let mut chip8 = Chip8::start(self.rom.clone());
+        chip8.seed_rng(seed);
+        self.rules.reset(&chip8);
+        self.run_loop.chip8 = chip8;
+        self.run_loop.host.input.hold(0);
+        self.steps = 0;
+        self.done = false;
+        self.run_loop.chip8.screen
+    }
+
+    /// Holds the keys of `action` and runs [`EnvOptions::frame_skip`] frames, or fewer if the
+    /// episode ends. Returns the screen, the reward summed over the frames and whether the
+    /// episode is over, after which [`Chip8Env::reset`] must be called.
+    ///
+    /// # Panics
+    /// When `action` is not below [`Chip8Env::action_count`], or the episode is over.
+    pub fn step(&mut self, action: usize) -> (Observation, f64, bool) {
+        assert!(!self.done, "the episode is over, reset the environment");
+        let keys = self.actions[action];
+
+        let mut reward = 0.0;
+        for frame in 0..self.options.frame_skip.max(1) {
+            let held = if frame < self.options.action_repeat {
+                keys
+            } else {
+                0
+            };
+            self.run_loop.host.input.hold(held);
+            if self.run_loop.step_frame() != FrameOutcome::Ran {
+                break;
+            }
+            reward += self.rules.reward(&self.run_loop.chip8);
+            if self.rules.is_done(&self.run_loop.chip8) {
+                self.done = true;
+                break;
+            }
+        }
+
+        self.steps += 1;
+        if self
+            .options
+            .max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+        {
+            self.done = true;
+        }
+        (self.run_loop.chip8.screen, reward, self.done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PONG: &[u8] = include_bytes!("../../files/roms/PONG");
+
+    /// Plays PONG with a fixed policy until the end of the episode, returning the rewards.
+    fn play_pong(seed: u64, policy: impl Fn(usize) -> usize) -> Vec<f64> {
+        let mut env = Chip8Env::new(PONG.to_vec(), Box::new(Pong::new(2)), EnvOptions::default());
+        env.reset(seed);
+        let mut rewards = Vec::new();
+        loop {
+            let (_, reward, done) = env.step(policy(env.steps()));
+            rewards.push(reward);
+            if done {
+                return rewards;
+            }
+        }
+    }
+
+    #[test]
+    fn pong_rewards_points_and_ends_at_the_winning_score() {
+        // Holding down misses the balls aimed at the middle of the paddle's side
+        let rewards = play_pong(1, |_| 2);
+        let won: f64 = rewards.iter().filter(|&&r| r > 0.0).sum();
+        let lost: f64 = rewards.iter().filter(|&&r| r < 0.0).sum();
+        assert!(won == 2.0 || lost == -2.0, "{} won, {} lost", won, lost);
+        assert!(rewards.len() < 5_000);
+    }
+
+    #[test]
+    fn episodes_with_the_same_seed_and_actions_are_identical() {
+        let policy = |step: usize| (step / 10) % 3;
+        assert_eq!(play_pong(7, policy), play_pong(7, policy));
+    }
+
+    #[test]
+    fn steps_hold_the_action_keys_for_action_repeat_frames() {
+        // 6005: LD V0, 5, E09E: SKP V0, 1202: JP 0x202, 6101: LD V1, 1, 1208: JP 0x208
+        let rom = vec![0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x61, 0x01, 0x12, 0x08];
+        let options = EnvOptions {
+            frame_skip: 2,
+            action_repeat: 1,
+            max_steps: Some(2),
+            ..EnvOptions::default()
+        };
+        let mut env = Chip8Env::new(rom, Box::new(NoRules), options);
+        env.reset(0);
+        assert_eq!(env.action_count(), 17);
+
+        assert!(!env.step(0).2);
+        assert_eq!(env.chip8().v_registers[0x1], 0);
+
+        let key_5 = 6;
+        assert!(env.step(key_5).2);
+        assert_eq!(env.chip8().v_registers[0x1], 1);
+        assert!(!env.chip8().keyboard[0x5]);
+    }
+}
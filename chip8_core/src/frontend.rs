@@ -0,0 +1,328 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    cheats::Cheats,
+    chip8::Chip8,
+    constants::{FRAMES_PER_SECOND, KEYBOARD_KEYS_COUNT},
+};
+
+/// What the host reported since the previous frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InputState {
+    pub keys: [bool; KEYBOARD_KEYS_COUNT], // Chip-8 keys held down, indexed by hex code
+    pub quit: bool,                        // The user closed the frontend
+    pub toggle_pause: bool,                // The user asked to pause or resume execution
+    pub toggle_cheats: bool,               // The user asked to enable or disable the cheats
+}
+
+/// Shows the Chip-8 screen.
+pub trait VideoSink {
+    /// Called once per frame, after the frame's instructions ran.
+    /// `chip8.needs_redraw` and `chip8.dirty_regions` tell what changed since the previous call.
+    fn present(&mut self, chip8: &Chip8);
+
+    /// Called before each `present` with the state of the [`RunLoop`], for frontends that show
+    /// whether it is paused and whether the cheats are enabled.
+    fn show_run_state(&mut self, _paused: bool, _cheats_enabled: bool) {}
+}
+
+/// Reads the Chip-8 keypad and the frontend controls.
+pub trait InputSource {
+    /// Called once at the start of every frame.
+    fn poll_input(&mut self) -> InputState;
+}
+
+/// Plays the single tone of the Chip-8 buzzer.
+pub trait AudioSink {
+    /// Called once per frame with whether the buzzer sounds during that frame.
+    fn set_buzzer(&mut self, on: bool);
+}
+
+/// Paces the run loop.
+pub trait TimeSource {
+    /// Time elapsed since an arbitrary origin that does not change while the loop runs.
+    fn now(&mut self) -> Duration;
+
+    /// Blocks until `now()` reaches `deadline`. Headless frontends return at once.
+    fn wait_until(&mut self, deadline: Duration);
+}
+
+/// Everything the run loop needs from the host.
+/// Implemented for any type providing the four parts, such as [`Frontend`].
+pub trait Host: VideoSink + InputSource + AudioSink + TimeSource {}
+
+impl<T: VideoSink + InputSource + AudioSink + TimeSource> Host for T {}
+
+/// Builds a [`Host`] from four independent parts, e.g. a terminal screen with a null audio sink.
+pub struct Frontend<V, I, A, T> {
+    pub video: V,
+    pub input: I,
+    pub audio: A,
+    pub time: T,
+}
+
+impl<V: VideoSink, I, A, T> VideoSink for Frontend<V, I, A, T> {
+    fn present(&mut self, chip8: &Chip8) {
+        self.video.present(chip8);
+    }
+
+    fn show_run_state(&mut self, paused: bool, cheats_enabled: bool) {
+        self.video.show_run_state(paused, cheats_enabled);
+    }
+}
+
+impl<V, I: InputSource, A, T> InputSource for Frontend<V, I, A, T> {
+    fn poll_input(&mut self) -> InputState {
+        self.input.poll_input()
+    }
+}
+
+impl<V, I, A: AudioSink, T> AudioSink for Frontend<V, I, A, T> {
+    fn set_buzzer(&mut self, on: bool) {
+        self.audio.set_buzzer(on);
+    }
+}
+
+impl<V, I, A, T: TimeSource> TimeSource for Frontend<V, I, A, T> {
+    fn now(&mut self) -> Duration {
+        self.time.now()
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        self.time.wait_until(deadline);
+    }
+}
+
+/// Result of one iteration of the [`RunLoop`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameOutcome {
+    /// The instructions of a frame ran.
+    Ran,
+    /// Execution is paused, the screen was presented again.
+    Paused,
+    /// The frontend asked to quit.
+    Quit,
+}
+
+/// Drives a [`Chip8`] at 60 frames per second on any [`Host`]:
+/// poll input, apply the cheats, run the frame's instructions and timers, play the buzzer,
+/// present the screen, then wait for the next frame.
+pub struct RunLoop<H> {
+    pub chip8: Chip8,
+    pub host: H,
+    pub cheats: Cheats,
+    instructions_per_frame: usize,
+    frames_run: usize,
+    paused: bool,
+    pacer: FramePacer,
+}
+
+impl<H: Host> RunLoop<H> {
+    pub fn new(chip8: Chip8, host: H, instructions_per_frame: usize) -> Self {
+        Self {
+            chip8,
+            host,
+            cheats: Cheats::default(),
+            instructions_per_frame,
+            frames_run: 0,
+            paused: false,
+            pacer: FramePacer::default(),
+        }
+    }
+
+    /// Number of frames whose instructions ran, paused frames excluded.
+    pub fn frames_run(&self) -> usize {
+        self.frames_run
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Runs one frame.
+    pub fn step_frame(&mut self) -> FrameOutcome {
+        let input = self.host.poll_input();
+        if input.quit {
+            self.host.set_buzzer(false);
+            return FrameOutcome::Quit;
+        }
+        if input.toggle_pause {
+            self.paused = !self.paused;
+        }
+        if input.toggle_cheats {
+            self.cheats.enabled = !self.cheats.enabled;
+        }
+
+        let outcome = if self.paused {
+            self.host.set_buzzer(false);
+            FrameOutcome::Paused
+        } else {
+            self.chip8.keyboard = input.keys;
+            self.cheats.apply(&mut self.chip8);
+            let buzzer_on = self.chip8.run_frame(self.instructions_per_frame);
+            self.host.set_buzzer(buzzer_on);
+            self.frames_run += 1;
+            FrameOutcome::Ran
+        };
+
+        self.host.show_run_state(self.paused, self.cheats.enabled);
+        self.host.present(&self.chip8);
+        self.chip8.clear_redraw_state();
+        self.pacer.wait_for_next_frame(&mut self.host);
+        outcome
+    }
+
+    /// Runs frames until the frontend asks to quit.
+    pub fn run(&mut self) {
+        while self.step_frame() != FrameOutcome::Quit {}
+    }
+
+    /// Runs frames until `frames` of them ran or the frontend asks to quit.
+    pub fn run_frames(&mut self, frames: usize) {
+        let target = self.frames_run + frames;
+        while self.frames_run < target && self.step_frame() != FrameOutcome::Quit {}
+    }
+}
+
+/// Keeps a loop at 60 frames per second with a [`TimeSource`]. Used by the [`RunLoop`], and by
+/// loops driving a [`Chip8`] themselves such as the debugger's.
+#[derive(Debug, Default)]
+pub struct FramePacer {
+    next_frame_at: Option<Duration>,
+}
+
+impl FramePacer {
+    /// Sleeps until the next 60 Hz deadline. When the host fell more than a frame behind,
+    /// e.g. after the window was moved, the schedule restarts from now instead of catching up.
+    pub fn wait_for_next_frame(&mut self, time: &mut impl TimeSource) {
+        let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND as u32;
+        let now = time.now();
+        let deadline = match self.next_frame_at {
+            Some(deadline) if deadline + frame_duration >= now => deadline + frame_duration,
+            _ => now + frame_duration,
+        };
+        time.wait_until(deadline);
+        self.next_frame_at = Some(deadline);
+    }
+}
+
+/// Real time, measured with [`Instant`] and waited for with [`std::thread::sleep`].
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for SystemClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        if let Some(remaining) = deadline.checked_sub(self.start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{NullAudio, NullVideo, VirtualClock};
+
+    /// Replays a list of inputs, then asks to quit.
+    struct ScriptedInput(Vec<InputState>);
+
+    impl InputSource for ScriptedInput {
+        fn poll_input(&mut self) -> InputState {
+            if self.0.is_empty() {
+                InputState {
+                    quit: true,
+                    ..InputState::default()
+                }
+            } else {
+                self.0.remove(0)
+            }
+        }
+    }
+
+    fn run_loop(
+        inputs: Vec<InputState>,
+    ) -> RunLoop<Frontend<NullVideo, ScriptedInput, NullAudio, VirtualClock>> {
+        // 7001: ADD V0, 1, then 1200: JP 0x200
+        let chip8 = Chip8::start(vec![0x70, 0x01, 0x12, 0x00]);
+        let host = Frontend {
+            video: NullVideo,
+            input: ScriptedInput(inputs),
+            audio: NullAudio,
+            time: VirtualClock::default(),
+        };
+        RunLoop::new(chip8, host, 2)
+    }
+
+    #[test]
+    fn runs_until_the_input_asks_to_quit() {
+        let mut run_loop = run_loop(vec![InputState::default(); 3]);
+        run_loop.run();
+
+        assert_eq!(run_loop.frames_run(), 3);
+        assert_eq!(run_loop.chip8.v_registers[0], 3);
+        assert_eq!(
+            run_loop.host.time.now(),
+            3 * (Duration::from_secs(1) / FRAMES_PER_SECOND as u32)
+        );
+    }
+
+    #[test]
+    fn paused_frames_do_not_run_instructions() {
+        let toggle = InputState {
+            toggle_pause: true,
+            ..InputState::default()
+        };
+        let mut run_loop = run_loop(vec![toggle, InputState::default(), toggle]);
+
+        assert_eq!(run_loop.step_frame(), FrameOutcome::Paused);
+        assert_eq!(run_loop.step_frame(), FrameOutcome::Paused);
+        assert_eq!(run_loop.step_frame(), FrameOutcome::Ran);
+        assert_eq!(run_loop.step_frame(), FrameOutcome::Quit);
+        assert_eq!(run_loop.frames_run(), 1);
+    }
+
+    #[test]
+    fn keys_are_passed_to_the_interpreter() {
+        let mut keys = InputState::default();
+        keys.keys[0xA] = true;
+        let mut run_loop = run_loop(vec![keys]);
+        run_loop.step_frame();
+
+        assert!(run_loop.chip8.keyboard[0xA]);
+    }
+
+    #[test]
+    fn cheats_are_applied_before_each_frame_until_toggled() {
+        let toggle = InputState {
+            toggle_cheats: true,
+            ..InputState::default()
+        };
+        let mut run_loop = run_loop(vec![InputState::default(), toggle]);
+        run_loop.cheats = Cheats::parse("300 2A").unwrap();
+
+        run_loop.step_frame();
+        assert_eq!(run_loop.chip8.ram[0x300], 0x2A);
+        run_loop.chip8.ram[0x300] = 0;
+        run_loop.step_frame();
+        assert_eq!(run_loop.chip8.ram[0x300], 0);
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use crate::{
+    chip8::Chip8,
+    frontend::{AudioSink, Frontend, InputSource, InputState, TimeSource, VideoSink},
+};
+
+/// A frontend without window, keyboard, sound or real time, for tests, tools and batch runs.
+/// Drive it with [`crate::frontend::RunLoop::run_frames`], since it never asks to quit.
+pub type Headless = Frontend<NullVideo, NullInput, NullAudio, VirtualClock>;
+
+/// Creates a [`Headless`] frontend.
+pub fn headless() -> Headless {
+    Frontend {
+        video: NullVideo,
+        input: NullInput,
+        audio: NullAudio,
+        time: VirtualClock::default(),
+    }
+}
+
+/// Discards every frame.
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _chip8: &Chip8) {}
+}
+
+/// No key is ever pressed.
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll_input(&mut self) -> InputState {
+        InputState::default()
+    }
+}
+
+/// The buzzer is silent.
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_buzzer(&mut self, _on: bool) {}
+}
+
+/// A clock that jumps to each deadline instead of sleeping, so frames run as fast as possible
+/// while the run loop still sees 60 Hz time.
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    now: Duration,
+}
+
+impl TimeSource for VirtualClock {
+    fn now(&mut self) -> Duration {
+        self.now
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        self.now = self.now.max(deadline);
+    }
+}
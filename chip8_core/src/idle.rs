@@ -0,0 +1,97 @@
+use crate::{chip8::Chip8, constants::CHIP8_RAM_MEMORY_SIZE};
+
+/// Whether the program only waits until the end of the frame, so the instructions left in the
+/// frame would just go round a wait loop without changing anything else. The timers and keys
+/// only change between frames, so this holds when the next instruction is:
+/// - `Fx0A` while no key is held;
+/// - the `Fx07` of a `LD Vx, DT`, `SE Vx, kk` (or `SNE Vx, kk`), `JP` loop that already read
+///   the current delay timer and goes round again with it;
+/// - a `JP` to itself, which programs use to stop.
+pub fn is_idle(chip8: &Chip8) -> bool {
+    let op_code = |offset: u16| {
+        let address = chip8.pc.wrapping_add(offset) as usize;
+        u16::from_be_bytes([
+            chip8.ram[address % CHIP8_RAM_MEMORY_SIZE],
+            chip8.ram[(address + 1) % CHIP8_RAM_MEMORY_SIZE],
+        ])
+    };
+    let jumps_to_pc = |op_code: u16| op_code >> 12 == 0x1 && op_code & 0x0fff == chip8.pc;
+
+    let first = op_code(0);
+    match first & 0xf0ff {
+        0xf00a => return !chip8.keyboard.contains(&true),
+        0xf007 => (),
+        _ => return jumps_to_pc(first),
+    }
+
+    let x = (first >> 8 & 0xf) as usize;
+    let (skip, jump) = (op_code(2), op_code(4));
+    let delay_timer = chip8.delay_timer;
+    let goes_round = match skip & 0xf000 {
+        0x3000 => delay_timer != skip as u8,
+        0x4000 => delay_timer == skip as u8,
+        _ => false,
+    };
+    skip >> 8 & 0xf == x as u16
+        && goes_round
+        && jumps_to_pc(jump)
+        && chip8.v_registers[x] == delay_timer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PONG's wait for the delay timer: LD V0, DT, SE V0, 0, JP 0x200.
+    const DELAY_WAIT: [u8; 6] = [0xF0, 0x07, 0x30, 0x00, 0x12, 0x00];
+
+    #[test]
+    fn delay_timer_loops_are_idle_once_they_read_the_timer() {
+        let mut chip8 = Chip8::start(DELAY_WAIT.to_vec());
+        chip8.delay_timer = 5;
+        assert!(!is_idle(&chip8), "V0 has not read the timer yet");
+
+        chip8.v_registers[0] = 5;
+        assert!(is_idle(&chip8));
+
+        chip8.delay_timer = 0;
+        chip8.v_registers[0] = 0;
+        assert!(!is_idle(&chip8), "the loop exits once the timer expired");
+
+        chip8.pc += 2;
+        assert!(!is_idle(&chip8), "only the start of the loop is recognised");
+    }
+
+    #[test]
+    fn key_waits_are_idle_until_a_key_is_held() {
+        let mut chip8 = Chip8::start(vec![0xF3, 0x0A]);
+        assert!(is_idle(&chip8));
+
+        chip8.keyboard[0x7] = true;
+        assert!(!is_idle(&chip8));
+    }
+
+    #[test]
+    fn jumps_to_themselves_are_idle() {
+        assert!(is_idle(&Chip8::start(vec![0x12, 0x00])));
+        assert!(!is_idle(&Chip8::start(vec![0x12, 0x02])));
+    }
+
+    #[test]
+    fn idle_frames_end_early_and_resume_when_the_timer_expires() {
+        // LD V0, 3, LD DT, V0, the wait loop at 0x204, then ADD V2, 1 forever
+        let mut rom = vec![0x60, 0x03, 0xF0, 0x15];
+        rom.extend([0xF1, 0x07, 0x31, 0x00, 0x12, 0x04]);
+        rom.extend([0x72, 0x01, 0x12, 0x0A]);
+        let mut chip8 = Chip8::start(rom);
+        chip8.set_idle_skip(true);
+
+        chip8.run_frame(10);
+        assert_eq!(chip8.pc, 0x204);
+        for _ in 0..3 {
+            chip8.run_frame(10);
+        }
+        // The 4th frame leaves the loop after 2 instructions and adds 4 times
+        assert_eq!(chip8.v_registers[2], 4);
+    }
+}
@@ -1,14 +1,33 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! The CHIP-8 interpreter, without any window, sound or keyboard dependency.
+//!
+//! Frontends implement the traits of [`frontend`] and hand them to [`frontend::RunLoop`],
+//! which drives a [`chip8::Chip8`] at 60 frames per second:
+//!
+//! ```
+//! use chip8_core::{chip8::Chip8, frontend::RunLoop, headless};
+//!
+//! // 6005: LD V0, 5, then 1202: JP 0x202 forever
+//! let chip8 = Chip8::start(vec![0x60, 0x05, 0x12, 0x02]);
+//! let mut run_loop = RunLoop::new(chip8, headless::headless(), 10);
+//! run_loop.run_frames(3);
+//!
+//! assert_eq!(run_loop.frames_run(), 3);
+//! assert_eq!(run_loop.chip8.v_registers[0], 5);
+//! ```
 
+pub mod cheats;
+pub mod chip8;
+pub mod chip8_util;
+pub mod constants;
+pub mod coverage;
+pub mod disassembler;
+pub mod env;
+pub mod frontend;
+pub mod headless;
+pub mod idle;
 #[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+mod opcode_tests;
+pub mod profiler;
+pub mod recompiler;
+pub mod state;
+pub mod symbols;
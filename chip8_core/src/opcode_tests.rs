@@ -0,0 +1,260 @@
+//! One test case per instruction behaviour, written as a line of a table.
+//!
+//! Each case gives the state before, the opcode, run by [`Chip8::tick`] from address 200, and
+//! the state after. The state after only lists what changes: everything else must be
+//! unchanged, and PC must be 202 unless given. States are space separated assignments, in hex
+//! unless noted:
+//!
+//! - `V3=FF`, `I=300`, `PC=204`, `DT=10`, `ST=10`: registers and timers
+//! - `SP=1` (decimal) and `stack0=204`: the stack pointer and the stack entries
+//! - `[300]=0102`: bytes of memory from an address
+//! - `key5`: key 5 held down
+//! - `px3,4=F0`: 8 pixels from column 3, row 4 (decimal, wrapping around), one per bit
+//!
+//! The interpreter has no quirk settings, so each instruction has a single expected behaviour:
+//! 8xy1 to 8xy3 leave VF alone, shifts read Vx, Fx55 and Fx65 advance I, Bnnn adds V0 and sprites
+//! wrap around the screen edges. Supporting quirks would add a quirk column to the cases.
+//! Cxkk cases run with the random number generator seeded with 0, whose first byte is 7F.
+
+use crate::{
+    chip8::Chip8,
+    constants::{SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// `(description, state before, opcode, state after)`
+#[rustfmt::skip]
+const CASES: &[(&str, &str, u16, &str)] = &[
+    // 0nnn
+    ("0000 does nothing", "", 0x0000, ""),
+    ("00E0 clears the screen", "px0,0=FF px56,31=01", 0x00E0, "px0,0=00 px56,31=00"),
+    ("00EE returns to the top of the stack", "SP=2 stack0=204 stack1=30A", 0x00EE, "SP=1 PC=30A"),
+    ("00EE on an empty stack pops its last entry", "stackF=246", 0x00EE, "SP=15 PC=246"),
+    ("0nnn machine code routines are ignored", "", 0x0123, ""),
+    // 1nnn, 2nnn
+    ("1nnn jumps", "", 0x1ABC, "PC=ABC"),
+    ("2nnn pushes the return address and jumps", "SP=1", 0x2ABC, "SP=2 stack1=202 PC=ABC"),
+    ("2nnn with a full stack overwrites its oldest entry", "SP=15 stack0=111", 0x2300, "SP=0 stackF=202 PC=300"),
+    // 3xkk, 4xkk, 5xy0, 9xy0
+    ("3xkk skips when equal", "V3=42", 0x3342, "PC=204"),
+    ("3xkk does not skip when different", "V3=41", 0x3342, ""),
+    ("4xkk skips when different", "V3=41", 0x4342, "PC=204"),
+    ("4xkk does not skip when equal", "V3=42", 0x4342, ""),
+    ("5xy0 skips when equal", "V1=07 V2=07", 0x5120, "PC=204"),
+    ("5xy0 does not skip when different", "V1=07 V2=08", 0x5120, ""),
+    ("5xy1 is not an instruction", "V1=07 V2=07", 0x5121, ""),
+    ("9xy0 skips when different", "V1=07 V2=08", 0x9120, "PC=204"),
+    ("9xy0 does not skip when equal", "V1=07 V2=07", 0x9120, ""),
+    ("9xy1 is not an instruction", "V1=07 V2=08", 0x9121, ""),
+    // 6xkk, 7xkk
+    ("6xkk loads a byte", "", 0x6A5C, "VA=5C"),
+    ("7xkk adds a byte", "VA=10", 0x7A05, "VA=15"),
+    ("7xkk wraps without touching VF", "VA=FF VF=07", 0x7A02, "VA=01"),
+    // 8xy0 to 8xy3
+    ("8xy0 copies Vy", "V1=00 V2=AB", 0x8120, "V1=AB"),
+    ("8xy1 ors", "V1=F0 V2=0F VF=05", 0x8121, "V1=FF"),
+    ("8xy2 ands", "V1=F3 V2=3F VF=05", 0x8122, "V1=33"),
+    ("8xy3 xors", "V1=F3 V2=3F VF=05", 0x8123, "V1=CC"),
+    // 8xy4
+    ("8xy4 adds without carry", "V1=10 V2=20 VF=01", 0x8124, "V1=30 VF=00"),
+    ("8xy4 adds with carry", "V1=F0 V2=20", 0x8124, "V1=10 VF=01"),
+    ("8xy4 with x F keeps the carry, not the sum", "VF=F0 V2=20", 0x8F24, "VF=01"),
+    ("8xy4 with y F adds VF before setting it", "V1=01 VF=FF", 0x81F4, "V1=00 VF=01"),
+    // 8xy5, 8xy7
+    ("8xy5 subtracts without borrow", "V1=30 V2=10", 0x8125, "V1=20 VF=01"),
+    ("8xy5 subtracts with borrow", "V1=10 V2=30 VF=01", 0x8125, "V1=E0 VF=00"),
+    ("8xy5 of equal values does not borrow", "V1=10 V2=10", 0x8125, "V1=00 VF=01"),
+    ("8xy5 with x F keeps the flag, not the difference", "VF=30 V2=10", 0x8F25, "VF=01"),
+    ("8xy7 subtracts Vx from Vy without borrow", "V1=10 V2=30", 0x8127, "V1=20 VF=01"),
+    ("8xy7 subtracts Vx from Vy with borrow", "V1=30 V2=10 VF=01", 0x8127, "V1=E0 VF=00"),
+    ("8xy7 with x F keeps the flag, not the difference", "VF=30 V2=10", 0x8F27, "VF=00"),
+    // 8xy6, 8xyE: shifts read Vx and ignore Vy
+    ("8xy6 shifts Vx right into VF", "V1=05 V2=FF", 0x8126, "V1=02 VF=01"),
+    ("8xy6 shifts out a clear bit", "V1=04 VF=01", 0x8126, "V1=02 VF=00"),
+    ("8xy6 with x F keeps the bit shifted out", "VF=03", 0x8F26, "VF=01"),
+    ("8xyE shifts Vx left into VF", "V1=81 V2=00", 0x812E, "V1=02 VF=01"),
+    ("8xyE shifts out a clear bit", "V1=41 VF=01", 0x812E, "V1=82 VF=00"),
+    ("8xyE with x F keeps the bit shifted out", "VF=80", 0x8F2E, "VF=01"),
+    ("8xy8 is not an instruction", "V1=01 V2=02", 0x8128, ""),
+    // Annn, Bnnn, Cxkk
+    ("Annn loads I", "", 0xA123, "I=123"),
+    ("Bnnn jumps to nnn plus V0", "V0=10 V1=20", 0xB300, "PC=310"),
+    ("Cxkk with a zero mask is zero", "V4=FF", 0xC400, "V4=00"),
+    ("Cxkk with a full mask is the random byte", "", 0xC4FF, "V4=7F"),
+    ("Cxkk clears the random bits outside the mask", "", 0xC4F0, "V4=70"),
+    // Dxyn
+    ("Dxyn draws a sprite from I", "I=300 [300]=F090 V1=02 V2=03", 0xD122, "px2,3=F0 px2,4=90 VF=00"),
+    ("Dxyn erasing a pixel sets VF", "I=300 [300]=C0 px0,0=80", 0xD001, "px0,0=40 VF=01"),
+    ("Dxyn wraps around the right edge", "I=300 [300]=FF V1=3C", 0xD101, "px60,0=FF"),
+    ("Dxyn wraps around the bottom edge", "I=300 [300]=8080 V2=1F", 0xD022, "px0,31=80 px0,0=80"),
+    ("Dxyn reads sprites across the end of memory", "I=FFF [FFF]=80 [000]=40", 0xD002, "px0,0=80 px0,1=40"),
+    ("Dxy0 draws nothing", "I=300 [300]=FF VF=01", 0xD000, "VF=00"),
+    ("Dxyn with x F reads the column before setting VF", "I=300 [300]=80 VF=05 px5,0=80", 0xDF01, "px5,0=00 VF=01"),
+    ("Dxyn with y F reads the row before setting VF", "I=300 [300]=80 VF=02", 0xD0F1, "px0,2=80 VF=00"),
+    // Ex9E, ExA1: only the low nibble of Vx selects the key
+    ("Ex9E skips when the key is held", "V5=0A keyA", 0xE59E, "PC=204"),
+    ("Ex9E does not skip when the key is up", "V5=0A key3", 0xE59E, ""),
+    ("Ex9E uses the low nibble of Vx", "V5=1A keyA", 0xE59E, "PC=204"),
+    ("ExA1 skips when the key is up", "V5=0A", 0xE5A1, "PC=204"),
+    ("ExA1 does not skip when the key is held", "V5=0A keyA", 0xE5A1, ""),
+    ("ExFF is not an instruction", "", 0xE5FF, ""),
+    // Fx07 to Fx29
+    ("Fx07 reads the delay timer", "DT=3C", 0xF707, "V7=3C"),
+    ("Fx0A waits while no key is held", "V7=01", 0xF70A, "PC=200"),
+    ("Fx0A stores the highest key held", "key2 keyB", 0xF70A, "V7=0B"),
+    ("Fx15 sets the delay timer", "V7=3C", 0xF715, "DT=3C"),
+    ("Fx18 sets the sound timer", "V7=3C", 0xF718, "ST=3C"),
+    ("Fx1E adds Vx to I", "I=100 V7=10", 0xF71E, "I=110"),
+    ("Fx1E wraps I without touching VF", "I=FFFF V7=02 VF=07", 0xF71E, "I=1"),
+    ("Fx29 points I at the font digit", "V7=0A", 0xF729, "I=32"),
+    ("Fx29 uses the low nibble of Vx", "V7=FA", 0xF729, "I=32"),
+    // Fx33, Fx55, Fx65: Fx55 and Fx65 advance I
+    ("Fx33 stores the decimal digits of Vx", "I=300 V7=FE", 0xF733, "[300]=020504"),
+    ("Fx33 stores leading zeros", "I=300 V7=07", 0xF733, "[300]=000007"),
+    ("Fx33 wraps around the end of memory", "I=FFF V7=FE", 0xF733, "[FFF]=02 [000]=0504"),
+    ("Fx55 stores V0 to Vx and advances I", "I=300 V0=01 V1=02 V2=03", 0xF155, "[300]=0102 I=302"),
+    ("Fx55 with x F stores VF", "I=300 VF=0F", 0xFF55, "[30F]=0F I=310"),
+    ("Fx55 wraps around the end of memory", "I=FFF V0=01 V1=02", 0xF155, "[FFF]=01 [000]=02 I=1001"),
+    ("Fx65 loads V0 to Vx and advances I", "I=300 [300]=010203", 0xF165, "V0=01 V1=02 I=302"),
+    ("Fx65 with x F loads VF", "I=300 [30F]=0F", 0xFF65, "VF=0F I=310"),
+    ("Fx99 is not an instruction", "V7=01", 0xF799, ""),
+];
+
+/// Applies the assignments of a state, as described in the module documentation.
+fn apply(chip8: &mut Chip8, state: &str) {
+    let hex = |value: &str| u16::from_str_radix(value, 16).unwrap();
+    for item in state.split_whitespace() {
+        if let Some(key) = item.strip_prefix("key") {
+            chip8.keyboard[hex(key) as usize] = true;
+            continue;
+        }
+        let (name, value) = item.split_once('=').unwrap();
+        match name {
+            "I" => chip8.i_register = hex(value),
+            "PC" => chip8.pc = hex(value),
+            "DT" => chip8.delay_timer = hex(value) as u8,
+            "ST" => chip8.sound_timer = hex(value) as u8,
+            "SP" => chip8.stack_pointer = value.parse().unwrap(),
+            _ if name.starts_with('[') => {
+                let address = hex(name.trim_matches(['[', ']'])) as usize;
+                for (offset, index) in (0..value.len()).step_by(2).enumerate() {
+                    chip8.ram[address + offset] = hex(&value[index..index + 2]) as u8;
+                }
+            }
+            _ if name.starts_with("px") => {
+                let (x, y) = name[2..].split_once(',').unwrap();
+                let (x, y): (usize, usize) = (x.parse().unwrap(), y.parse().unwrap());
+                let byte = hex(value);
+                for bit in 0..8 {
+                    let index = y * SCREEN_WIDTH + (x + bit) % SCREEN_WIDTH;
+                    chip8.screen[index] = byte & 0x80 >> bit != 0;
+                }
+            }
+            _ if name.starts_with("stack") => chip8.stack[hex(&name[5..]) as usize] = hex(value),
+            _ if name.starts_with('V') => {
+                chip8.v_registers[hex(&name[1..]) as usize] = hex(value) as u8
+            }
+            _ => panic!("unknown item '{}'", item),
+        }
+    }
+}
+
+/// The state compared by the cases, one line per part that differs from `expected`.
+fn differences(chip8: &Chip8, expected: &Chip8) -> Vec<String> {
+    let mut differences = Vec::new();
+    let mut compare = |name: &str, actual: String, expected: String| {
+        if actual != expected {
+            differences.push(format!("{name} is {actual}, expected {expected}"));
+        }
+    };
+    compare(
+        "PC",
+        format!("{:03X}", chip8.pc),
+        format!("{:03X}", expected.pc),
+    );
+    compare(
+        "I",
+        format!("{:03X}", chip8.i_register),
+        format!("{:03X}", expected.i_register),
+    );
+    compare(
+        "V",
+        format!("{:02X?}", chip8.v_registers),
+        format!("{:02X?}", expected.v_registers),
+    );
+    compare(
+        "SP",
+        chip8.stack_pointer.to_string(),
+        expected.stack_pointer.to_string(),
+    );
+    compare(
+        "stack",
+        format!("{:03X?}", chip8.stack),
+        format!("{:03X?}", expected.stack),
+    );
+    compare(
+        "DT",
+        chip8.delay_timer.to_string(),
+        expected.delay_timer.to_string(),
+    );
+    compare(
+        "ST",
+        chip8.sound_timer.to_string(),
+        expected.sound_timer.to_string(),
+    );
+    for address in (0..chip8.ram.len()).filter(|&a| chip8.ram[a] != expected.ram[a]) {
+        compare(
+            &format!("[{:03X}]", address),
+            format!("{:02X}", chip8.ram[address]),
+            format!("{:02X}", expected.ram[address]),
+        );
+    }
+    for pixel in
+        (0..SCREEN_WIDTH * SCREEN_HEIGHT).filter(|&p| chip8.screen[p] != expected.screen[p])
+    {
+        compare(
+            &format!("pixel {},{}", pixel % SCREEN_WIDTH, pixel / SCREEN_WIDTH),
+            chip8.screen[pixel].to_string(),
+            expected.screen[pixel].to_string(),
+        );
+    }
+    differences
+}
+
+#[test]
+fn every_case_reaches_its_expected_state() {
+    let mut failures = Vec::new();
+    for &(description, before, op_code, after) in CASES {
+        let mut chip8 = Chip8::start(op_code.to_be_bytes().to_vec());
+        chip8.seed_rng(0);
+        apply(&mut chip8, before);
+
+        let mut expected = chip8.clone();
+        expected.pc += 2;
+        apply(&mut expected, after);
+
+        chip8.tick();
+        let differences = differences(&chip8, &expected);
+        if !differences.is_empty() {
+            failures.push(format!(
+                "{:04X} {}: {}",
+                op_code,
+                description,
+                differences.join(", ")
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn every_instruction_family_has_cases() {
+    for family in 0..16 {
+        assert!(
+            CASES
+                .iter()
+                .any(|&(_, _, op_code, _)| op_code >> 12 == family),
+            "no case for {:X}nnn",
+            family
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    constants::{CHIP8_RAM_MEMORY_SIZE, CHIP8_STACK_MEMORY_SIZE, START_RAM_ADDRESS},
+    disassembler::{self, Line},
+};
+
+/// Number of addresses listed in the hot addresses of a report.
+const HOT_ADDRESSES: usize = 20;
+
+/// Longest loop, in bytes from its first instruction to its backward jump, counted as waiting
+/// on the delay timer.
+const MAX_WAIT_LOOP_BYTES: u16 = 16;
+
+/// Execution counts gathered while a program runs, once enabled with
+/// [`crate::chip8::Chip8::set_profiler`]: per address, per instruction pattern, instructions
+/// spent waiting for a key in Fx0A, and stack depths.
+#[derive(Clone)]
+pub struct Profiler {
+    executions: Box<[u64]>,
+    patterns: BTreeMap<&'static str, u64>,
+    key_waits: u64,
+    stack_depths: [u64; CHIP8_STACK_MEMORY_SIZE + 1],
+    instructions: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            executions: vec![0; CHIP8_RAM_MEMORY_SIZE].into_boxed_slice(),
+            patterns: BTreeMap::new(),
+            key_waits: 0,
+            stack_depths: [0; CHIP8_STACK_MEMORY_SIZE + 1],
+            instructions: 0,
+        }
+    }
+
+    /// Counts an instruction run at `address` with `stack_depth` return addresses on the stack,
+    /// after which the program counter is `next_pc`.
+    pub fn record(&mut self, address: u16, op_code: u16, stack_depth: usize, next_pc: u16) {
+        self.instructions += 1;
+        self.executions[address as usize % CHIP8_RAM_MEMORY_SIZE] += 1;
+        let pattern = disassembler::pattern(op_code);
+        *self.patterns.entry(pattern).or_default() += 1;
+        if pattern == "Fx0A" && next_pc == address {
+            self.key_waits += 1;
+        }
+        self.stack_depths[stack_depth.min(CHIP8_STACK_MEMORY_SIZE)] += 1;
+    }
+
+    /// The number of instructions recorded.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// The number of instructions run at `address`.
+    pub fn executions(&self, address: u16) -> u64 {
+        self.executions[address as usize % CHIP8_RAM_MEMORY_SIZE]
+    }
+
+    /// The first and last addresses of the small loops that read the delay timer with Fx07 and
+    /// jump back until it runs out, as found in `ram`.
+    pub fn delay_wait_loops(&self, ram: &[u8]) -> Vec<(u16, u16)> {
+        let op_code = |address: u16| {
+            let address = address as usize;
+            u16::from_be_bytes([ram[address], ram[(address + 1) % ram.len()]])
+        };
+        (0..CHIP8_RAM_MEMORY_SIZE as u16)
+            .filter(|&address| self.executions(address) > 0)
+            .filter_map(|jump| {
+                let target = op_code(jump) & 0x0fff;
+                let is_backward_jump = op_code(jump) >> 12 == 0x1 && target <= jump;
+                let reads_delay_timer = (target..jump)
+                    .step_by(2)
+                    .any(|address| disassembler::pattern(op_code(address)) == "Fx07");
+                (is_backward_jump && jump - target <= MAX_WAIT_LOOP_BYTES && reads_delay_timer)
+                    .then_some((target, jump))
+            })
+            .collect()
+    }
+
+    /// A report of the hot addresses, the instruction patterns ranked by executions, the time
+    /// spent waiting and the stack depths. `ram` is used to show the instructions.
+    pub fn report(&self, ram: &[u8]) -> String {
+        let total = self.instructions.max(1) as f64;
+        let percent = |count: u64| 100.0 * count as f64 / total;
+        let mut report = String::new();
+
+        writeln!(report, "Profile of {} instructions", self.instructions).unwrap();
+
+        writeln!(report, "\nHot addresses:").unwrap();
+        let mut addresses: Vec<u16> = (0..CHIP8_RAM_MEMORY_SIZE as u16)
+            .filter(|&address| self.executions(address) > 0)
+            .collect();
+        addresses.sort_by_key(|&address| std::cmp::Reverse(self.executions(address)));
+        for (rank, &address) in addresses.iter().take(HOT_ADDRESSES).enumerate() {
+            let count = self.executions(address);
+            writeln!(
+                report,
+                "{:>4}. {:03X} {:>6.2}% {:>12}  {}",
+                rank + 1,
+                address,
+                percent(count),
+                count,
+                disassembler::disassemble(op_code_at(ram, address))
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nInstructions:").unwrap();
+        let mut patterns: Vec<(&str, u64)> = self.patterns.iter().map(|(&p, &c)| (p, c)).collect();
+        patterns.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        for (pattern, count) in patterns {
+            writeln!(
+                report,
+                "  {} {:>6.2}% {:>12}",
+                pattern,
+                percent(count),
+                count
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nWaiting:").unwrap();
+        writeln!(
+            report,
+            "  for a key (Fx0A)     {:>6.2}% {:>12}",
+            percent(self.key_waits),
+            self.key_waits
+        )
+        .unwrap();
+        let loops = self.delay_wait_loops(ram);
+        let delay_waits: u64 = loops
+            .iter()
+            .flat_map(|&(start, end)| start..=end)
+            .map(|address| self.executions(address))
+            .sum();
+        let ranges: Vec<String> = loops
+            .iter()
+            .map(|(start, end)| format!("{:03X}-{:03X}", start, end))
+            .collect();
+        writeln!(
+            report,
+            "  on the delay timer   {:>6.2}% {:>12}{}",
+            percent(delay_waits),
+            delay_waits,
+            if ranges.is_empty() {
+                String::new()
+            } else {
+                format!("  in loops at {}", ranges.join(", "))
+            }
+        )
+        .unwrap();
+
+        writeln!(report, "\nStack depth:").unwrap();
+        for (depth, &count) in self.stack_depths.iter().enumerate() {
+            if count > 0 {
+                writeln!(
+                    report,
+                    "  {:>2}   {:>6.2}% {:>12}",
+                    depth,
+                    percent(count),
+                    count
+                )
+                .unwrap();
+            }
+        }
+        report
+    }
+
+    /// Every instruction from the start of programs to the last address run, with its
+    /// execution count. Bytes skipped over by code running at odd addresses are shown as data.
+    pub fn annotated_disassembly(&self, ram: &[u8]) -> String {
+        let Some(last) = (0..CHIP8_RAM_MEMORY_SIZE as u16)
+            .rev()
+            .find(|&address| self.executions(address) > 0)
+        else {
+            return String::new();
+        };
+
+        let mut listing = String::new();
+        let start = START_RAM_ADDRESS.min(last);
+        let is_data = |address: u16| {
+            self.executions(address) == 0 && self.executions(address.wrapping_add(1)) > 0
+        };
+        for line in disassembler::listing(ram, start, last + 1, is_data) {
+            let address = match line {
+                Line::Instruction { address, .. } | Line::Data { address, .. } => address,
+            };
+            let count = match self.executions(address) {
+                0 => String::new(),
+                count => count.to_string(),
+            };
+            match line {
+                Line::Data { byte, .. } => writeln!(
+                    listing,
+                    "{:>12}  {:03X}  {:02X}    DB 0x{:02X}",
+                    count, address, byte, byte
+                ),
+                Line::Instruction { op_code, .. } => writeln!(
+                    listing,
+                    "{:>12}  {:03X}  {:04X}  {}",
+                    count,
+                    address,
+                    op_code,
+                    disassembler::disassemble(op_code)
+                ),
+            }
+            .unwrap();
+        }
+        listing
+    }
+}
+
+fn op_code_at(ram: &[u8], address: u16) -> u16 {
+    let address = address as usize;
+    u16::from_be_bytes([ram[address], ram[(address + 1) % ram.len()]])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip8::Chip8;
+
+    #[test]
+    fn delay_timer_loops_and_stack_depths_are_reported() {
+        // 2206: CALL 0x206, 1202: JP 0x202, 00E0: CLS,
+        // 6003: LD V0, 3, F015: LD DT, V0, F007: LD V0, DT, 3000: SE V0, 0, 120A: JP 0x20A, 00EE: RET
+        let mut chip8 = Chip8::start(vec![
+            0x22, 0x06, 0x12, 0x02, 0x00, 0xE0, 0x60, 0x03, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00,
+            0x12, 0x0A, 0x00, 0xEE,
+        ]);
+        chip8.set_profiler(true);
+        for _ in 0..6 {
+            chip8.run_frame(10);
+        }
+
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.instructions(), 60);
+        assert_eq!(profiler.delay_wait_loops(&chip8.ram), vec![(0x20A, 0x20E)]);
+        let report = profiler.report(&chip8.ram);
+        assert!(report.contains("   2. 20A  16.67%"), "{}", report);
+        assert!(report.contains("LD V0, DT"), "{}", report);
+        assert!(report.contains("in loops at 20A-20E"), "{}", report);
+        assert!(report.contains("\n   1 "), "{}", report);
+
+        let listing = profiler.annotated_disassembly(&chip8.ram);
+        assert!(listing.contains("  204  00E0  CLS\n"), "{}", listing);
+    }
+
+    #[test]
+    fn key_waits_are_counted() {
+        // F10A: LD V1, K
+        let mut chip8 = Chip8::start(vec![0xF1, 0x0A]);
+        chip8.set_profiler(true);
+        chip8.run_frame(5);
+        chip8.keyboard[7] = true;
+        chip8.tick();
+
+        let report = chip8.profiler().unwrap().report(&chip8.ram);
+        assert!(
+            report.contains("for a key (Fx0A)      83.33%            5"),
+            "{}",
+            report
+        );
+    }
+
+    #[test]
+    fn stack_depth_stays_at_16_once_the_stack_wraps() {
+        // 2200: CALL 0x200 forever
+        let mut chip8 = Chip8::start(vec![0x22, 0x00]);
+        chip8.set_profiler(true);
+        chip8.run_frame(20);
+
+        assert_eq!(chip8.stack_depth, 16);
+        let report = chip8.profiler().unwrap().report(&chip8.ram);
+        assert!(
+            report.contains("\n  16    20.00%            4"),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("\n   4     5.00%            1"),
+            "{}",
+            report
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    chip8::{Chip8, Handler},
+    chip8_util::Chip8Util,
+    constants::CHIP8_RAM_MEMORY_SIZE,
+    idle,
+};
+
+/// Longest block compiled, so a long run of straight-line code does not overshoot much when the
+/// instruction budget of a frame ends in its middle.
+const MAX_BLOCK_INSTRUCTIONS: usize = 32;
+
+/// A basic block: instructions stored in sequence in memory, run until one of them changes the
+/// program counter.
+#[derive(Clone)]
+struct Block {
+    instructions: Box<[(u16, Handler)]>,
+    /// The bytes of the instructions, as found in memory when compiling them.
+    code: Box<[u8]>,
+    /// Whether the last instruction is Fx33 or Fx55.
+    writes_memory: bool,
+    /// The value of [`Recompiler::epoch`] when the instructions were last found in memory.
+    verified: u32,
+    /// Set when the block is a loop waiting for the delay timer.
+    delay_wait: Option<DelayWait>,
+}
+
+impl Block {
+    /// Whether memory at `start` still holds the instructions of the block.
+    fn matches(&self, ram: &[u8], start: usize) -> bool {
+        ram[start..].starts_with(&self.code)
+    }
+}
+
+/// A block looping until the delay timer reaches a value: `LD Vx, DT`, then `SE Vx, kk` or
+/// `SNE Vx, kk`, then a jump back to the `LD Vx, DT`. The timer only changes between frames, so
+/// once the loop goes round it does so until the end of the frame, and the rest of the frame is
+/// run at once instead of three instructions at a time. Games spend most of their frames there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DelayWait {
+    x: usize,
+    kk: u8,
+    exits_when_equal: bool, // SE Vx, kk leaves the loop when the timer equals kk, SNE when it does not
+}
+
+impl DelayWait {
+    /// Recognizes the loop in the opcodes of a block compiled at `start`.
+    fn find(op_codes: &[u16], start: usize) -> Option<Self> {
+        let &[load, skip, jump] = op_codes else {
+            return None;
+        };
+        let (0xf, x, 0, 7) = Chip8Util::extract_nibbles(load) else {
+            return None;
+        };
+        let (kind, skip_x, _, _) = Chip8Util::extract_nibbles(skip);
+        if !matches!(kind, 0x3 | 0x4) || skip_x != x || jump != 0x1000 | start as u16 {
+            return None;
+        }
+        Some(Self {
+            x: x as usize,
+            kk: skip as u8,
+            exits_when_equal: kind == 0x3,
+        })
+    }
+
+    /// Whether the loop goes round with the delay timer at `delay_timer`.
+    fn goes_round(&self, delay_timer: u8) -> bool {
+        (delay_timer == self.kk) != self.exits_when_equal
+    }
+}
+
+/// Whether the instruction ends a block when compiling it: jumps, calls and returns, which
+/// always leave the block, and Fx33 and Fx55, which may overwrite the following instructions.
+/// Skips and Fx0A stay in blocks: a skip goes on with the instruction after the next one, and
+/// Fx0A leaves the block when it waits for a key.
+fn ends_block(op_code: u16) -> bool {
+    match Chip8Util::extract_nibbles(op_code) {
+        (0, 0, 0xe, 0xe) | (0x1, _, _, _) | (0x2, _, _, _) | (0xb, _, _, _) => true,
+        _ => writes_memory(op_code),
+    }
+}
+
+fn writes_memory(op_code: u16) -> bool {
+    matches!(
+        Chip8Util::extract_nibbles(op_code),
+        (0xf, _, 3, 3) | (0xf, _, 5, 5)
+    )
+}
+
+/// Executes programs a basic block at a time: each block is decoded once into a list of
+/// handlers, then run without fetching or decoding its instructions again, skips included.
+/// Blocks waiting for the delay timer are run to the end of the frame at once, see [`DelayWait`].
+///
+/// Self-modifying code is detected by checking that memory still holds the instructions of a
+/// block when it is first entered in a frame, and again after each Fx33 or Fx55, so blocks are
+/// compiled again after any write to their code, from the program, cheats, a debugger or a
+/// loaded state. Instructions are run by the interpreter, through [`Chip8::tick`], when debug
+/// mode traces them, when the profiler counts them or when the program counter is at the end of
+/// memory.
+#[derive(Clone)]
+pub struct Recompiler {
+    blocks: Box<[Option<Block>]>,
+    /// Incremented whenever memory may have changed since blocks were verified.
+    epoch: u32,
+}
+
+impl Default for Recompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recompiler {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![None; CHIP8_RAM_MEMORY_SIZE].into_boxed_slice(),
+            epoch: 0,
+        }
+    }
+
+    /// Executes `instructions` instructions, as many calls to [`Chip8::tick`] would. With idle
+    /// skipping enabled, stops after the block that leaves the program idle.
+    pub fn run(&mut self, chip8: &mut Chip8, instructions: usize) {
+        // Memory may have been written to since the previous frame
+        self.epoch = self.epoch.wrapping_add(1);
+
+        let mut remaining = instructions;
+        while remaining > 0 {
+            let Some(block) = self.block_at(chip8) else {
+                chip8.tick();
+                remaining -= 1;
+                if chip8.skips_idle() && idle::is_idle(chip8) {
+                    break;
+                }
+                continue;
+            };
+
+            // With idle skipping, the frame ends after the first turn instead, as when interpreted
+            if let Some(wait) = block.delay_wait
+                && !chip8.skips_idle()
+                && wait.goes_round(chip8.delay_timer)
+            {
+                // Where `remaining` instructions of the loop leave it: whole turns end on the
+                // LD Vx, DT, and every turn loads the same timer value
+                chip8.v_registers[wait.x] = chip8.delay_timer;
+                chip8.pc += 2 * (remaining % 3) as u16;
+                break;
+            }
+
+            // A skip taken inside the block goes on with the instruction after the next one;
+            // any other change of the program counter leaves the block
+            let instructions = &block.instructions;
+            let start = chip8.pc;
+            let mut index = 0;
+            let mut executed = 0;
+            let mut ran_last = false;
+            while index < instructions.len() && executed < remaining {
+                let (op_code, handler) = instructions[index];
+                let next = start + 2 * (index as u16 + 1);
+                ran_last = index + 1 == instructions.len();
+                chip8.pc = next;
+                handler(chip8, op_code);
+                executed += 1;
+                index += match chip8.pc.wrapping_sub(next) {
+                    0 => 1,
+                    2 if index + 2 < instructions.len() => 2,
+                    _ => break,
+                };
+            }
+            remaining -= executed;
+            if block.writes_memory && ran_last {
+                // Its last instruction wrote to memory, possibly over compiled code
+                self.epoch = self.epoch.wrapping_add(1);
+            }
+            if chip8.skips_idle() && idle::is_idle(chip8) {
+                break;
+            }
+        }
+    }
+
+    /// The block starting at the program counter, compiled if it is not cached or its code
+    /// changed. None when the interpreter has to run the next instruction.
+    fn block_at(&mut self, chip8: &Chip8) -> Option<&Block> {
+        let start = chip8.pc as usize;
+        if chip8.debug_mode
+            || chip8.profiler().is_some()
+            || chip8.coverage().is_some()
+            || start + 1 >= CHIP8_RAM_MEMORY_SIZE
+        {
+            return None;
+        }
+
+        let epoch = self.epoch;
+        let entry = &mut self.blocks[start];
+        match entry {
+            Some(block) if block.verified == epoch => {}
+            Some(block) if block.matches(&chip8.ram, start) => block.verified = epoch,
+            _ => *entry = Some(Self::compile(&chip8.ram, start, epoch)),
+        }
+        entry.as_ref()
+    }
+
+    fn compile(ram: &[u8], start: usize, epoch: u32) -> Block {
+        let mut instructions = Vec::new();
+        let mut address = start;
+        while address + 1 < ram.len() && instructions.len() < MAX_BLOCK_INSTRUCTIONS {
+            let op_code = u16::from_be_bytes([ram[address], ram[address + 1]]);
+            instructions.push((op_code, Chip8::decode(op_code)));
+            address += 2;
+            if ends_block(op_code) {
+                break;
+            }
+        }
+        let op_codes: Vec<u16> = instructions.iter().map(|&(op_code, _)| op_code).collect();
+        Block {
+            writes_memory: instructions
+                .last()
+                .is_some_and(|&(op_code, _)| writes_memory(op_code)),
+            code: ram[start..address].into(),
+            instructions: instructions.into_boxed_slice(),
+            verified: epoch,
+            delay_wait: DelayWait::find(&op_codes, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Runs `rom` with and without the recompiler, pressing keys in a fixed pattern, and checks
+    /// the machines stay identical frame after frame.
+    fn assert_same_as_interpreter(name: &str, rom: Vec<u8>, frames: usize) {
+        let mut interpreted = Chip8::start(rom);
+        interpreted.seed_rng(42);
+        let mut recompiled = interpreted.clone();
+        recompiled.set_recompiler(true);
+
+        for frame in 0..frames {
+            // Holds a different key every 8 frames, with gaps so games see presses and releases
+            let key = (frame / 8) % 20;
+            for chip8 in [&mut interpreted, &mut recompiled] {
+                chip8.reset_keyboard();
+                if let Some(pressed) = chip8.keyboard.get_mut(key) {
+                    *pressed = true;
+                }
+            }
+
+            let interpreted_buzzer = interpreted.run_frame(10);
+            let recompiled_buzzer = recompiled.run_frame(10);
+            assert!(
+                interpreted.save_state() == recompiled.save_state()
+                    && interpreted_buzzer == recompiled_buzzer,
+                "{} differs at frame {}",
+                name,
+                frame
+            );
+        }
+    }
+
+    #[test]
+    fn bundled_roms_run_the_same_as_with_the_interpreter() {
+        let mut roms = 0;
+        for directory in ["../files/roms", "../files/roms/tests"] {
+            for entry in fs::read_dir(directory).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_file() {
+                    let rom = fs::read(&path).unwrap();
+                    assert_same_as_interpreter(&path.display().to_string(), rom, 1200);
+                    roms += 1;
+                }
+            }
+        }
+        assert!(roms > 20);
+    }
+
+    #[test]
+    fn blocks_overwritten_by_their_program_are_compiled_again() {
+        // 6072: LD V0, 0x72, 6110: LD V1, 0x10, A20E: LD I, 0x20E, 220E: CALL 0x20E,
+        // F155: LD [I], V1, 220E: CALL 0x20E, 120C: JP 0x20C, 7201: ADD V2, 1, 00EE: RET,
+        // where the second call runs 7210: ADD V2, 0x10 written over the subroutine by the first
+        let rom = vec![
+            0x60, 0x72, 0x61, 0x10, 0xA2, 0x0E, 0x22, 0x0E, 0xF1, 0x55, 0x22, 0x0E, 0x12, 0x0C,
+            0x72, 0x01, 0x00, 0xEE,
+        ];
+        assert_same_as_interpreter("self-modifying", rom.clone(), 4);
+
+        let mut chip8 = Chip8::start(rom);
+        chip8.set_recompiler(true);
+        chip8.run_frame(12);
+        assert_eq!(chip8.v_registers[2], 0x11);
+    }
+
+    #[test]
+    fn delay_timer_waits_run_the_rest_of_the_frame_at_once() {
+        // 6005: LD V0, 5, F015: LD DT, V0, F107: LD V1, DT, 3100: SE V1, 0, 1204: JP 0x204,
+        // 7201: ADD V2, 1, 1202: JP 0x202, adding 1 to V2 every 5 frames
+        let mut rom = vec![
+            0x60, 0x05, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x72, 0x01, 0x12, 0x02,
+        ];
+        let block = Recompiler::compile(&Chip8::start(rom.clone()).ram, 0x204, 0);
+        assert_eq!(
+            block.delay_wait,
+            Some(DelayWait {
+                x: 1,
+                kk: 0,
+                exits_when_equal: true
+            })
+        );
+        assert_same_as_interpreter("SE wait", rom.clone(), 60);
+
+        // 4105: SNE V1, 5, waiting while the timer is still 5
+        rom[6] = 0x41;
+        rom[7] = 0x05;
+        assert_same_as_interpreter("SNE wait", rom.clone(), 60);
+
+        // 1206: JP 0x206 does not go back to the LD V1, DT
+        rom[9] = 0x06;
+        let block = Recompiler::compile(&Chip8::start(rom).ram, 0x204, 0);
+        assert_eq!(block.delay_wait, None);
+    }
+
+    #[test]
+    fn skips_go_on_inside_blocks() {
+        // 6001: LD V0, 1, 3001: SE V0, 1, 6102: LD V1, 2, 6203: LD V2, 3, A300: LD I, 0x300,
+        // 3001: SE V0, 1, F255: LD [I], V2, 1200: JP 0x200, where both skips are taken and the
+        // second one jumps over the memory write ending the block
+        let rom = vec![
+            0x60, 0x01, 0x30, 0x01, 0x61, 0x02, 0x62, 0x03, 0xA3, 0x00, 0x30, 0x01, 0xF2, 0x55,
+            0x12, 0x00,
+        ];
+        assert_eq!(
+            Recompiler::compile(&Chip8::start(rom.clone()).ram, 0x200, 0)
+                .instructions
+                .len(),
+            7
+        );
+        assert_same_as_interpreter("skips", rom.clone(), 20);
+
+        let mut chip8 = Chip8::start(rom);
+        chip8.set_recompiler(true);
+        chip8.run_frame(5);
+        assert_eq!(chip8.pc, 0x20E);
+        assert_eq!(chip8.v_registers[..3], [1, 0, 3]);
+        chip8.run_frame(1);
+        assert_eq!(chip8.pc, 0x200);
+        assert_eq!(chip8.ram[0x300], 0);
+    }
+
+    #[test]
+    fn blocks_stop_at_jumps_and_memory_writes() {
+        // 6001: LD V0, 1, 3002: SE V0, 2, 2300: CALL 0x300, 00E0: CLS
+        let block = Recompiler::compile(&[0x60, 0x01, 0x30, 0x02, 0x23, 0x00, 0x00, 0xE0], 0, 0);
+        assert_eq!(block.instructions.len(), 3);
+        // A20A: LD I, 0x20A, F055: LD [I], V0, 6001: LD V0, 1
+        let block = Recompiler::compile(&[0xA2, 0x0A, 0xF0, 0x55, 0x60, 0x01], 0, 0);
+        assert_eq!(block.instructions.len(), 2);
+    }
+}
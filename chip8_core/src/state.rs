@@ -0,0 +1,338 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    chip8::Chip8,
+    constants::{
+        CHIP8_RAM_MEMORY_SIZE, CHIP8_REGISTER_COUNT, CHIP8_STACK_MEMORY_SIZE, KEYBOARD_KEYS_COUNT,
+        SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+};
+
+const STATE_MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u8 = 1;
+
+/// Size in bytes of a snapshot written by [`Chip8::save_state`].
+pub const STATE_SIZE: usize = STATE_MAGIC.len()
+    + 1 // Version
+    + CHIP8_RAM_MEMORY_SIZE
+    + 2 // PC
+    + 2 // I
+    + 2 * CHIP8_STACK_MEMORY_SIZE
+    + 1 // Stack pointer
+    + 1 // Stack depth
+    + 1 // Delay timer
+    + 1 // Sound timer
+    + CHIP8_REGISTER_COUNT
+    + SCREEN_WIDTH * SCREEN_HEIGHT / 8
+    + 2 // Keyboard
+    + 8 // Random seed
+    + 16; // Position of the random generator in its stream
+
+/// Why a snapshot could not be restored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateError {
+    /// The data does not start with the snapshot signature.
+    NotAState,
+    /// The snapshot was written by a different version of the emulator.
+    UnsupportedVersion(u8),
+    /// The snapshot does not have the expected size.
+    WrongSize(usize),
+    /// A register holds a value the interpreter cannot run from.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a CHIP-8 save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::WrongSize(size) => write!(
+                f,
+                "save state is {} bytes, expected {} bytes",
+                size, STATE_SIZE
+            ),
+            StateError::Invalid(reason) => write!(f, "invalid save state: {}", reason),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Checks restored registers hold values the machine can reach, shared by snapshots and the
+/// register setters of the bindings. The stack is circular, so its pointer stays below 16 and at
+/// most 16 return addresses are kept. Any program counter is accepted: `Bnnn` and skips at the
+/// end of memory take it past 0xFFF, where [`Chip8::fetch`] reads `0000` and the machine stays put.
+pub fn check_registers(stack_pointer: usize, stack_depth: usize) -> Result<(), StateError> {
+    if stack_pointer >= CHIP8_STACK_MEMORY_SIZE {
+        return Err(StateError::Invalid("stack pointer out of range"));
+    }
+    if stack_depth > CHIP8_STACK_MEMORY_SIZE {
+        return Err(StateError::Invalid("stack depth out of range"));
+    }
+    Ok(())
+}
+
+impl Chip8 {
+    /// Serializes the machine: memory, registers, timers, screen, keys and the random generator.
+    /// Restoring it with [`Chip8::load_state`] continues exactly where the machine was, Cxkk included.
+    ///
+    /// ```
+    /// # use chip8_core::chip8::Chip8;
+    /// let mut chip8 = Chip8::start(vec![0x60, 0x2A]); // LD V0, 0x2A
+    /// let snapshot = chip8.save_state();
+    /// chip8.tick();
+    /// assert_eq!(chip8.v_registers[0], 0x2A);
+    ///
+    /// chip8.load_state(&snapshot).unwrap();
+    /// assert_eq!(chip8.v_registers[0], 0);
+    /// ```
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+        state.extend_from_slice(&self.ram);
+        state.extend_from_slice(&self.pc.to_le_bytes());
+        state.extend_from_slice(&self.i_register.to_le_bytes());
+        for address in self.stack {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+        state.push(self.stack_pointer as u8);
+        state.push(self.stack_depth as u8);
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.extend_from_slice(&self.v_registers);
+        for pixels in self.screen.chunks(8) {
+            state.push(
+                pixels
+                    .iter()
+                    .fold(0, |byte, &pixel_on| (byte << 1) | u8::from(pixel_on)),
+            );
+        }
+        let keys = (0..KEYBOARD_KEYS_COUNT)
+            .filter(|&key| self.keyboard[key])
+            .fold(0u16, |keys, key| keys | (1 << key));
+        state.extend_from_slice(&keys.to_le_bytes());
+        state.extend_from_slice(&self.rng_seed.to_le_bytes());
+        state.extend_from_slice(&self.rng.get_word_pos().to_le_bytes());
+        state
+    }
+
+    /// Restores a snapshot written by [`Chip8::save_state`]. The machine is left untouched
+    /// if the snapshot is rejected. The whole screen is marked for redraw.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if !state.starts_with(STATE_MAGIC) {
+            return Err(StateError::NotAState);
+        }
+        let version = state.get(STATE_MAGIC.len()).copied().unwrap_or(0);
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if state.len() != STATE_SIZE {
+            return Err(StateError::WrongSize(state.len()));
+        }
+
+        let mut reader = StateReader {
+            bytes: &state[STATE_MAGIC.len() + 1..],
+        };
+        let mut restored = self.clone();
+        restored
+            .ram
+            .copy_from_slice(reader.take(CHIP8_RAM_MEMORY_SIZE));
+        restored.pc = reader.u16();
+        restored.i_register = reader.u16();
+        for address in restored.stack.iter_mut() {
+            *address = reader.u16();
+        }
+        restored.stack_pointer = reader.u8() as usize;
+        restored.stack_depth = reader.u8() as usize;
+        restored.delay_timer = reader.u8();
+        restored.sound_timer = reader.u8();
+        restored
+            .v_registers
+            .copy_from_slice(reader.take(CHIP8_REGISTER_COUNT));
+        for (index, byte) in reader
+            .take(SCREEN_WIDTH * SCREEN_HEIGHT / 8)
+            .iter()
+            .enumerate()
+        {
+            for bit in 0..8 {
+                restored.screen[index * 8 + bit] = byte & (0x80 >> bit) != 0;
+            }
+        }
+        let keys = reader.u16();
+        for (key, pressed) in restored.keyboard.iter_mut().enumerate() {
+            *pressed = keys & (1 << key) != 0;
+        }
+        let seed = reader.u64();
+        let word_pos = reader.u128();
+
+        check_registers(restored.stack_pointer, restored.stack_depth)?;
+
+        // The stream of the generator is given by its seed, it jumps straight to the saved position
+        restored.seed_rng(seed);
+        restored.rng.set_word_pos(word_pos);
+        restored.last_sprite = None;
+
+        *self = restored;
+        self.request_full_redraw();
+        Ok(())
+    }
+}
+
+/// Reads the fields of a snapshot in order. The length was checked beforehand.
+struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, count: usize) -> &'a [u8] {
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        taken
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+
+    fn u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8));
+        u64::from_le_bytes(bytes)
+    }
+
+    fn u128(&mut self) -> u128 {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(self.take(16));
+        u128::from_le_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_has_the_documented_size() {
+        assert_eq!(Chip8::new().save_state().len(), STATE_SIZE);
+    }
+
+    #[test]
+    fn restored_machine_replays_identically() {
+        // C0FF: RND V0, 0xFF, D005: DRW V0, V0, 5, 1200: JP 0x200
+        let rom = vec![0xC0, 0xFF, 0xD0, 0x05, 0x12, 0x00];
+        let mut chip8 = Chip8::start(rom);
+        chip8.keyboard[0x5] = true;
+        chip8.delay_timer = 9;
+        chip8.run_frame(7);
+        let snapshot = chip8.save_state();
+
+        let mut restored = Chip8::new();
+        restored.load_state(&snapshot).unwrap();
+        assert_eq!(restored.save_state(), snapshot);
+
+        chip8.run_frame(30);
+        restored.run_frame(30);
+        assert_eq!(restored.v_registers, chip8.v_registers);
+        assert_eq!(restored.screen, chip8.screen);
+        assert_eq!(restored.save_state(), chip8.save_state());
+    }
+
+    #[test]
+    fn generator_position_is_restored_at_once() {
+        // C0FF: RND V0, 0xFF, 1200: JP 0x200
+        let mut chip8 = Chip8::start(vec![0xC0, 0xFF, 0x12, 0x00]);
+        let mut snapshot = chip8.save_state();
+        let position = STATE_SIZE - 16;
+        snapshot[position..].copy_from_slice(&u128::MAX.to_le_bytes());
+
+        let started = std::time::Instant::now();
+        chip8.load_state(&snapshot).unwrap();
+        chip8.run_frame(10);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        let long_session = chip8.save_state();
+        let mut restored = Chip8::new();
+        restored.load_state(&long_session).unwrap();
+        chip8.run_frame(10);
+        restored.run_frame(10);
+        assert_eq!(restored.save_state(), chip8.save_state());
+    }
+
+    #[test]
+    fn rejected_snapshot_leaves_the_machine_untouched() {
+        let mut chip8 = Chip8::start(vec![0x60, 0x01]);
+        let before = chip8.save_state();
+
+        assert_eq!(chip8.load_state(b"nope"), Err(StateError::NotAState));
+        assert_eq!(
+            chip8.load_state(&before[..100]),
+            Err(StateError::WrongSize(100))
+        );
+
+        let mut other_version = before.clone();
+        other_version[STATE_MAGIC.len()] = STATE_VERSION + 1;
+        assert_eq!(
+            chip8.load_state(&other_version),
+            Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+        );
+
+        let mut bad_sp = before.clone();
+        let sp_offset =
+            STATE_MAGIC.len() + 1 + CHIP8_RAM_MEMORY_SIZE + 4 + 2 * CHIP8_STACK_MEMORY_SIZE;
+        assert_eq!(bad_sp[sp_offset], 0);
+        bad_sp[sp_offset] = CHIP8_STACK_MEMORY_SIZE as u8;
+        assert!(matches!(
+            chip8.load_state(&bad_sp),
+            Err(StateError::Invalid(_))
+        ));
+
+        let mut bad_depth = before.clone();
+        bad_depth[sp_offset + 1] = CHIP8_STACK_MEMORY_SIZE as u8 + 1;
+        assert!(matches!(
+            chip8.load_state(&bad_depth),
+            Err(StateError::Invalid(_))
+        ));
+
+        assert_eq!(chip8.save_state(), before);
+    }
+
+    #[test]
+    fn program_counter_past_memory_round_trips() {
+        // 6010: LD V0, 0x10, BFFF: JP V0, 0xFFF, which jumps to 0x100F
+        let mut chip8 = Chip8::start(vec![0x60, 0x10, 0xBF, 0xFF]);
+        chip8.tick();
+        chip8.tick();
+        assert_eq!(chip8.pc, 0x100F);
+
+        let snapshot = chip8.save_state();
+        let mut restored = Chip8::new();
+        restored.load_state(&snapshot).unwrap();
+        assert_eq!(restored.pc, 0x100F);
+        assert_eq!(restored.save_state(), snapshot);
+    }
+
+    #[test]
+    fn wrapped_and_underflowed_stacks_round_trip() {
+        // 2200: CALL 0x200 forever, nesting 20 calls, and 00EE: RET with an empty stack
+        for (rom, ticks, depth) in [(vec![0x22, 0x00], 20, 16), (vec![0x00, 0xEE], 1, 0)] {
+            let mut chip8 = Chip8::start(rom);
+            for _ in 0..ticks {
+                chip8.tick();
+            }
+            assert_eq!(chip8.stack_depth, depth);
+
+            let mut restored = Chip8::new();
+            restored.load_state(&chip8.save_state()).unwrap();
+            assert_eq!(restored.stack_pointer, chip8.stack_pointer);
+            assert_eq!(restored.stack_depth, depth);
+            assert_eq!(restored.return_addresses(), chip8.return_addresses());
+        }
+    }
+}
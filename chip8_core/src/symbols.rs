@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::disassembler;
+
+/// The line of a source file an instruction was assembled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+}
+
+/// Labels and source lines of an assembled program, loaded from the symbol file of its
+/// assembler, so debuggers can show `draw_paddle+4` and `pong.8o:12` instead of raw addresses.
+///
+/// The text format has one symbol per line, `<address> <label>` or `<address> <file>:<line>`
+/// with the address in hex. Lines starting with `#` are comments:
+///
+/// ```text
+/// # Labels
+/// 200 main
+/// 2D4 draw_paddle
+/// # Source lines
+/// 200 pong.8o:12
+/// 202 pong.8o:13
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    labels: BTreeMap<u16, String>,
+    addresses: HashMap<String, u16>,
+    lines: BTreeMap<u16, SourceLine>,
+    sources: HashMap<String, Vec<String>>,
+}
+
+impl Symbols {
+    /// Parses the text format described on [`Symbols`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Self::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            symbols
+                .add(line)
+                .map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+        }
+        Ok(symbols)
+    }
+
+    /// Adds the symbol of a line of the text format.
+    fn add(&mut self, line: &str) -> Result<(), String> {
+        let (address, symbol) = line
+            .split_once(char::is_whitespace)
+            .ok_or("expected <address> <label> or <address> <file>:<line>")?;
+        let address = u16::from_str_radix(address, 16)
+            .ok()
+            .filter(|&address| address <= 0xfff)
+            .ok_or_else(|| format!("invalid address '{}'", address))?;
+        let symbol = symbol.trim();
+
+        if let Some((file, line)) = symbol.rsplit_once(':')
+            && let Ok(line) = line.parse()
+        {
+            let file = file.to_string();
+            self.lines.insert(address, SourceLine { file, line });
+        } else if symbol.contains(char::is_whitespace) {
+            return Err(format!("label '{}' contains spaces", symbol));
+        } else if let Some(previous) = self.addresses.get(symbol) {
+            return Err(format!("label '{}' already at {:03X}", symbol, previous));
+        } else {
+            self.addresses.insert(symbol.to_string(), address);
+            // The first label of an address names it
+            self.labels.entry(address).or_insert(symbol.to_string());
+        }
+        Ok(())
+    }
+
+    /// The source files the source lines refer to, to read them with [`Symbols::add_source`].
+    pub fn source_files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.lines.values().map(|line| line.file.as_str()).collect();
+        files.sort_unstable();
+        files.dedup();
+        files
+    }
+
+    /// Keeps the text of a source file, so [`Symbols::describe`] shows its lines.
+    pub fn add_source(&mut self, file: &str, text: &str) {
+        let lines = text.lines().map(str::to_string).collect();
+        self.sources.insert(file.to_string(), lines);
+    }
+
+    /// The label naming `address`, if any.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// The address of a label, or of a hex address when no label has that name.
+    pub fn resolve(&self, target: &str) -> Result<u16, String> {
+        match self.addresses.get(target) {
+            Some(&address) => Ok(address),
+            None => u16::from_str_radix(target, 16)
+                .ok()
+                .filter(|&address| address <= 0xfff)
+                .ok_or_else(|| format!("unknown label or address '{}'", target)),
+        }
+    }
+
+    /// `address` relative to the closest label before it, e.g. `draw_paddle+4`, or in hex when
+    /// there is none.
+    pub fn location(&self, address: u16) -> String {
+        match self.labels.range(..=address).next_back() {
+            Some((&start, label)) if start == address => label.clone(),
+            Some((&start, label)) => format!("{}+{:X}", label, address - start),
+            None => format!("{:03X}", address),
+        }
+    }
+
+    /// The source line of the instruction at `address`, if known.
+    pub fn source_line(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.get(&address)
+    }
+
+    /// Where the instruction at `address` comes from, e.g. `main+2 (pong.8o:13: ld vb, 12)`,
+    /// with the text of the line when its file was added.
+    pub fn describe(&self, address: u16) -> String {
+        let Some(source) = self.source_line(address) else {
+            return self.location(address);
+        };
+        let text = self
+            .sources
+            .get(&source.file)
+            .and_then(|lines| lines.get(source.line.checked_sub(1)?));
+        match text {
+            Some(text) => format!(
+                "{} ({}:{}: {})",
+                self.location(address),
+                source.file,
+                source.line,
+                text.trim()
+            ),
+            None => format!(
+                "{} ({}:{})",
+                self.location(address),
+                source.file,
+                source.line
+            ),
+        }
+    }
+
+    /// Like [`disassembler::disassemble`], with the labels of the addresses of jumps, calls
+    /// and LD I, e.g. `CALL draw_paddle`.
+    pub fn disassemble(&self, op_code: u16) -> String {
+        let assembly = disassembler::disassemble(op_code);
+        let address = op_code & 0x0fff;
+        match (disassembler::pattern(op_code), self.label(address)) {
+            ("0nnn" | "1nnn" | "2nnn" | "Annn" | "Bnnn", Some(label)) => {
+                assembly.replace(&format!("0x{:03X}", address), label)
+            }
+            _ => assembly,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PONG_SYMBOLS: &str = "\
+# Labels
+200 main
+2D4 draw_paddle
+# Source lines
+200 pong.8o:12
+202 pong.8o:13
+";
+
+    #[test]
+    fn labels_and_source_lines_are_parsed() {
+        let mut symbols = Symbols::parse(PONG_SYMBOLS).unwrap();
+        symbols.add_source("pong.8o", &format!("{}  ld vb, 12\n", "x\n".repeat(12)));
+
+        assert_eq!(symbols.resolve("draw_paddle"), Ok(0x2D4));
+        assert_eq!(symbols.resolve("2D8"), Ok(0x2D8));
+        assert!(symbols.resolve("paddle").is_err());
+        assert_eq!(symbols.location(0x1FE), "1FE");
+        assert_eq!(symbols.location(0x2D8), "draw_paddle+4");
+        assert_eq!(symbols.describe(0x200), "main (pong.8o:12: x)");
+        assert_eq!(symbols.describe(0x202), "main+2 (pong.8o:13: ld vb, 12)");
+        assert_eq!(symbols.source_files(), ["pong.8o"]);
+        assert_eq!(symbols.disassemble(0x22D4), "CALL draw_paddle");
+        assert_eq!(symbols.disassemble(0x62D4), "LD V2, 0xD4");
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        assert_eq!(
+            Symbols::parse("200 main\n\n300 main"),
+            Err(String::from("line 3: label 'main' already at 200"))
+        );
+        assert!(Symbols::parse("2000 main").is_err());
+        assert!(Symbols::parse("main").is_err());
+        assert!(Symbols::parse("200 two words").is_err());
+    }
+}
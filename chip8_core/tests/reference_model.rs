@@ -0,0 +1,303 @@
+//! Runs random instruction streams on [`Chip8`] and on a small reference model written
+//! straight from the instruction set, checking they agree after every instruction, and runs
+//! random ROM bytes on every execution path to check none of them panics.
+
+use chip8_core::{
+    chip8::Chip8,
+    constants::{CHIP8_RAM_MEMORY_SIZE, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+use proptest::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// The machine state the instructions act on, with the behaviours this emulator chose where
+/// the original interpreters differ: shifts read Vx, Fx55 and Fx65 advance I, sprites wrap
+/// around the screen, memory accesses from I wrap around 4 KiB and the stack is circular.
+struct Reference {
+    ram: [u8; CHIP8_RAM_MEMORY_SIZE],
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: [u16; 16],
+    sp: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    keys: [bool; 16],
+    rng: StdRng,
+}
+
+impl Reference {
+    /// A copy of `chip8`, whose random numbers come from `seed`.
+    fn from(chip8: &Chip8, seed: u64) -> Self {
+        Self {
+            ram: chip8.ram,
+            v: chip8.v_registers,
+            i: chip8.i_register,
+            pc: chip8.pc,
+            stack: chip8.stack,
+            sp: chip8.stack_pointer,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            screen: chip8.screen,
+            keys: chip8.keyboard,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn byte_at_i(&self, offset: usize) -> usize {
+        (self.i as usize + offset) % CHIP8_RAM_MEMORY_SIZE
+    }
+
+    fn step(&mut self) {
+        let pc = self.pc as usize;
+        if pc + 1 >= CHIP8_RAM_MEMORY_SIZE {
+            return; // Nothing to fetch, the machine stays there
+        }
+        let op = (self.ram[pc] as u16) << 8 | self.ram[pc + 1] as u16;
+        self.pc += 2;
+
+        let x = (op >> 8 & 0xF) as usize;
+        let y = (op >> 4 & 0xF) as usize;
+        let n = op & 0xF;
+        let kk = op as u8;
+        let nnn = op & 0xFFF;
+
+        match op >> 12 {
+            0x0 if op == 0x00E0 => self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            0x0 if op == 0x00EE => {
+                self.sp = (self.sp + 15) % 16;
+                self.pc = self.stack[self.sp];
+            }
+            0x1 => self.pc = nnn,
+            0x2 => {
+                self.stack[self.sp] = self.pc;
+                self.sp = (self.sp + 1) % 16;
+                self.pc = nnn;
+            }
+            0x3 if self.v[x] == kk => self.pc += 2,
+            0x4 if self.v[x] != kk => self.pc += 2,
+            0x5 if n == 0 && self.v[x] == self.v[y] => self.pc += 2,
+            0x6 => self.v[x] = kk,
+            0x7 => self.v[x] = self.v[x].wrapping_add(kk),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some((vx as u16 + vy as u16 > 0xFF) as u8),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (vx >> 1, Some(vx & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0xE => (vx << 1, Some(vx >> 7)),
+                    _ => return,
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 && self.v[x] != self.v[y] => self.pc += 2,
+            0xA => self.i = nnn,
+            0xB => self.pc = nnn + self.v[0] as u16,
+            0xC => self.v[x] = self.rng.gen_range(0..=255u8) & kk,
+            0xD => {
+                let mut erased = false;
+                for row in 0..n as usize {
+                    let sprite = self.ram[self.byte_at_i(row)];
+                    for column in 0..8 {
+                        if sprite & 0x80 >> column == 0 {
+                            continue;
+                        }
+                        let px = (self.v[x] as usize + column) % SCREEN_WIDTH;
+                        let py = (self.v[y] as usize + row) % SCREEN_HEIGHT;
+                        let pixel = &mut self.screen[py * SCREEN_WIDTH + px];
+                        erased |= *pixel;
+                        *pixel = !*pixel;
+                    }
+                }
+                self.v[0xF] = erased as u8;
+            }
+            0xE if kk == 0x9E && self.keys[(self.v[x] & 0xF) as usize] => self.pc += 2,
+            0xE if kk == 0xA1 && !self.keys[(self.v[x] & 0xF) as usize] => self.pc += 2,
+            0xF => match kk {
+                0x07 => self.v[x] = self.delay_timer,
+                0x0A => match (0..16).rev().find(|&key| self.keys[key]) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc -= 2,
+                },
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => self.sound_timer = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = (self.v[x] & 0xF) as u16 * 5,
+                0x33 => {
+                    let vx = self.v[x];
+                    for (offset, digit) in [vx / 100, vx / 10 % 10, vx % 10].into_iter().enumerate()
+                    {
+                        let address = self.byte_at_i(offset);
+                        self.ram[address] = digit;
+                    }
+                }
+                0x55 => {
+                    for register in 0..=x {
+                        let address = self.byte_at_i(register);
+                        self.ram[address] = self.v[register];
+                    }
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+                0x65 => {
+                    for register in 0..=x {
+                        self.v[register] = self.ram[self.byte_at_i(register)];
+                    }
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn update_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// The differences with `chip8`, empty when both agree.
+    fn differences(&self, chip8: &Chip8) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut compare = |name: &str, reference: String, emulator: String| {
+            if reference != emulator {
+                differences.push(format!("{name}: expected {reference}, got {emulator}"));
+            }
+        };
+        compare(
+            "PC",
+            format!("{:03X}", self.pc),
+            format!("{:03X}", chip8.pc),
+        );
+        compare(
+            "I",
+            format!("{:03X}", self.i),
+            format!("{:03X}", chip8.i_register),
+        );
+        compare(
+            "V",
+            format!("{:02X?}", self.v),
+            format!("{:02X?}", chip8.v_registers),
+        );
+        compare("SP", self.sp.to_string(), chip8.stack_pointer.to_string());
+        compare(
+            "stack",
+            format!("{:03X?}", self.stack),
+            format!("{:03X?}", chip8.stack),
+        );
+        compare(
+            "timers",
+            format!("{} {}", self.delay_timer, self.sound_timer),
+            format!("{} {}", chip8.delay_timer, chip8.sound_timer),
+        );
+        if let Some(address) = (0..CHIP8_RAM_MEMORY_SIZE).find(|&a| self.ram[a] != chip8.ram[a]) {
+            compare(
+                "memory",
+                format!("{:02X} at {:03X}", self.ram[address], address),
+                format!("{:02X}", chip8.ram[address]),
+            );
+        }
+        if let Some(pixel) = (0..self.screen.len()).find(|&p| self.screen[p] != chip8.screen[p]) {
+            compare(
+                "screen",
+                format!("{} at pixel {}", self.screen[pixel], pixel),
+                chip8.screen[pixel].to_string(),
+            );
+        }
+        differences
+    }
+}
+
+/// Opcodes spread over every instruction family, with the Fx and 8xy kinds that exist made as
+/// likely as the rest.
+fn opcode() -> impl Strategy<Value = u16> {
+    let nibble = || 0u16..16;
+    prop_oneof![
+        any::<u16>(),
+        Just(0x00E0),
+        Just(0x00EE),
+        (
+            nibble(),
+            nibble(),
+            prop::sample::select(vec![0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE])
+        )
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        (nibble(), prop::sample::select(vec![0x9E, 0xA1])).prop_map(|(x, kk)| 0xE000 | x << 8 | kk),
+        (
+            nibble(),
+            prop::sample::select(vec![0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65])
+        )
+            .prop_map(|(x, kk)| 0xF000 | x << 8 | kk),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn instructions_match_the_reference_model(
+        opcodes in prop::collection::vec(opcode(), 1..64),
+        registers in any::<[u8; 16]>(),
+        i_register in 0u16..0x1000,
+        keyboard in any::<[bool; 16]>(),
+        seed in any::<u64>(),
+    ) {
+        let rom = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+        let mut chip8 = Chip8::start(rom);
+        chip8.v_registers = registers;
+        chip8.i_register = i_register;
+        chip8.keyboard = keyboard;
+        chip8.seed_rng(seed);
+        let mut reference = Reference::from(&chip8, seed);
+
+        // Jumps and skips can leave the stream, so run as long again past its end
+        for step in 0..opcodes.len() * 2 {
+            let op = u16::from_be_bytes([
+                chip8.ram[chip8.pc as usize % CHIP8_RAM_MEMORY_SIZE],
+                chip8.ram[(chip8.pc as usize + 1) % CHIP8_RAM_MEMORY_SIZE],
+            ]);
+            chip8.tick();
+            reference.step();
+            if step % 8 == 7 {
+                chip8.update_timers();
+                reference.update_timers();
+            }
+            let differences = reference.differences(&chip8);
+            prop_assert!(
+                differences.is_empty(),
+                "after {:04X} at step {}: {}",
+                op,
+                step,
+                differences.join(", ")
+            );
+        }
+    }
+
+    #[test]
+    fn arbitrary_roms_never_panic(
+        rom in prop::collection::vec(any::<u8>(), 0..MAX_ROM_SIZE + 64),
+        keys in prop::collection::vec(any::<u16>(), 30),
+    ) {
+        let interpreted = Chip8::start(rom);
+        let mut recompiled = interpreted.clone();
+        recompiled.set_recompiler(true);
+
+        for mut chip8 in [interpreted, recompiled] {
+            chip8.seed_rng(0);
+            for &held in &keys {
+                for (key, pressed) in chip8.keyboard.iter_mut().enumerate() {
+                    *pressed = held & 1 << key != 0;
+                }
+                chip8.run_frame(10);
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * Runs a ROM for a number of frames, then prints the screen and the registers, and checks
+ * that a snapshot taken halfway replays to the same state.
+ *
+ *   cargo build -p chip8_ffi
+ *   cc chip8_ffi/examples/run_rom.c -Ichip8_ffi/include -Ltarget/debug -lchip8_ffi -o run_rom
+ *   LD_LIBRARY_PATH=target/debug ./run_rom files/roms/PONG 120
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define INSTRUCTIONS_PER_FRAME 10
+
+#define CHECK(call)                                                               \
+    do {                                                                          \
+        int status = (call);                                                      \
+        if (status != CHIP8_OK) {                                                 \
+            fprintf(stderr, "%s failed: %s\n", #call, chip8_status_message(status)); \
+            exit(1);                                                              \
+        }                                                                         \
+    } while (0)
+
+static void run_frames(Chip8Machine *machine, int frames) {
+    for (int frame = 0; frame < frames; frame++) {
+        CHECK(chip8_step(machine, INSTRUCTIONS_PER_FRAME));
+        CHECK(chip8_tick_timers(machine, NULL));
+    }
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s ROM [FRAMES]\n", argv[0]);
+        return 2;
+    }
+    int frames = argc > 2 ? atoi(argv[2]) : 60;
+
+    if (chip8_api_version() != CHIP8_API_VERSION) {
+        fprintf(stderr, "chip8.h does not match the library\n");
+        return 1;
+    }
+
+    FILE *file = fopen(argv[1], "rb");
+    if (!file) {
+        perror(argv[1]);
+        return 1;
+    }
+    uint8_t rom[CHIP8_MAX_ROM_SIZE + 1];
+    size_t rom_length = fread(rom, 1, sizeof rom, file);
+    fclose(file);
+
+    Chip8Machine *machine = chip8_create();
+    CHECK(chip8_load_rom(machine, rom, rom_length));
+    CHECK(chip8_seed(machine, 42));
+
+    run_frames(machine, frames / 2);
+    size_t snapshot_size = chip8_snapshot_size();
+    uint8_t *snapshot = malloc(snapshot_size);
+    CHECK(chip8_snapshot(machine, snapshot, snapshot_size));
+    run_frames(machine, frames - frames / 2);
+
+    uint8_t pixels[CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT];
+    CHECK(chip8_framebuffer(machine, pixels, sizeof pixels));
+    for (int y = 0; y < CHIP8_SCREEN_HEIGHT; y++) {
+        for (int x = 0; x < CHIP8_SCREEN_WIDTH; x++) {
+            putchar(pixels[y * CHIP8_SCREEN_WIDTH + x] ? '#' : '.');
+        }
+        putchar('\n');
+    }
+
+    Chip8Registers registers;
+    CHECK(chip8_get_registers(machine, &registers));
+    printf("PC=%04X I=%04X SP=%u DT=%u ST=%u\n", registers.pc, registers.i, registers.sp,
+           registers.delay_timer, registers.sound_timer);
+    for (int index = 0; index < 16; index++) {
+        printf("V%X=%02X%c", index, registers.v[index], index == 15 ? '\n' : ' ');
+    }
+
+    /* Replaying the second half from the snapshot must give the same screen */
+    uint8_t replayed[sizeof pixels];
+    CHECK(chip8_restore(machine, snapshot, snapshot_size));
+    run_frames(machine, frames - frames / 2);
+    CHECK(chip8_framebuffer(machine, replayed, sizeof replayed));
+    if (memcmp(pixels, replayed, sizeof pixels) != 0) {
+        fprintf(stderr, "the replay from the snapshot differs\n");
+        return 1;
+    }
+    printf("snapshot replay matches\n");
+
+    free(snapshot);
+    chip8_destroy(machine);
+    return 0;
+}
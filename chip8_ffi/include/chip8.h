@@ -0,0 +1,94 @@
+/*
+ * C API of the CHIP-8 interpreter.
+ *
+ * Link with libchip8_ffi (cargo build -p chip8_ffi --release). All functions taking a
+ * machine return a CHIP8_* status code; chip8_status_message() describes it.
+ * A machine is not thread-safe: use it from one thread at a time.
+ */
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CHIP8_API_VERSION 1
+
+#define CHIP8_SCREEN_WIDTH 64
+#define CHIP8_SCREEN_HEIGHT 32
+#define CHIP8_MEMORY_SIZE 4096
+#define CHIP8_ROM_ADDRESS 0x200
+#define CHIP8_MAX_ROM_SIZE (CHIP8_MEMORY_SIZE - CHIP8_ROM_ADDRESS)
+
+#define CHIP8_OK 0
+#define CHIP8_ERROR_NULL_POINTER 1
+#define CHIP8_ERROR_ROM_EMPTY 2
+#define CHIP8_ERROR_ROM_TOO_LARGE 3
+#define CHIP8_ERROR_OUT_OF_RANGE 4
+#define CHIP8_ERROR_BUFFER_TOO_SMALL 5
+#define CHIP8_ERROR_INVALID_STATE 6
+#define CHIP8_ERROR_CRASHED 7 /* An instruction failed; load a ROM or a snapshot to continue */
+
+typedef struct Chip8Machine Chip8Machine;
+
+typedef struct Chip8Registers {
+    uint8_t v[16];
+    uint16_t i;
+    uint16_t pc;
+    uint16_t stack[16];
+    uint8_t sp;
+    uint8_t stack_depth; /* Return addresses on the circular stack, at most 16 */
+    uint8_t delay_timer;
+    uint8_t sound_timer;
+} Chip8Registers;
+
+/* Returns CHIP8_API_VERSION of the library, to compare with the header's. */
+uint32_t chip8_api_version(void);
+
+/* Static description of a status code. */
+const char *chip8_status_message(int status);
+
+/* Creates a machine without a program. Free it with chip8_destroy. */
+Chip8Machine *chip8_create(void);
+void chip8_destroy(Chip8Machine *machine);
+
+/* Resets the machine and loads the ROM at 0x200. */
+int chip8_load_rom(Chip8Machine *machine, const uint8_t *rom, size_t length);
+
+/* Seeds the random numbers of Cxkk so runs are reproducible. */
+int chip8_seed(Chip8Machine *machine, uint64_t seed);
+
+/* Executes `cycles` instructions. Timers are not updated. */
+int chip8_step(Chip8Machine *machine, uint32_t cycles);
+
+/* Decrements the timers once (call at 60 Hz). `buzzer_on` may be NULL. */
+int chip8_tick_timers(Chip8Machine *machine, bool *buzzer_on);
+
+/* Copies the screen row by row, one byte per pixel (1 = lit). `length` >= 64 * 32. */
+int chip8_framebuffer(const Chip8Machine *machine, uint8_t *pixels, size_t length);
+
+/* Presses or releases key 0x0 to 0xF. */
+int chip8_set_key(Chip8Machine *machine, uint8_t key, bool pressed);
+
+/* Registers, timers and stack. Setting them fails with CHIP8_ERROR_OUT_OF_RANGE when sp is
+   16 or more or stack_depth above 16; any pc is accepted, as Bnnn can take it past 0xFFF. */
+int chip8_get_registers(const Chip8Machine *machine, Chip8Registers *registers);
+int chip8_set_registers(Chip8Machine *machine, const Chip8Registers *registers);
+
+int chip8_read_memory(const Chip8Machine *machine, uint16_t address, uint8_t *data, size_t length);
+int chip8_write_memory(Chip8Machine *machine, uint16_t address, const uint8_t *data, size_t length);
+
+/* Snapshots of the whole machine, chip8_snapshot_size() bytes long. */
+size_t chip8_snapshot_size(void);
+int chip8_snapshot(const Chip8Machine *machine, uint8_t *data, size_t length);
+int chip8_restore(Chip8Machine *machine, const uint8_t *data, size_t length);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CHIP8_H */
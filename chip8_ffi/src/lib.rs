@@ -0,0 +1,469 @@
+//! C ABI of the CHIP-8 interpreter, declared in `include/chip8.h`.
+//!
+//! Build with `cargo build -p chip8_ffi --release`, which produces `libchip8_ffi.so`
+//! (or `.dylib`/`.dll`) and `libchip8_ffi.a` in `target/release/`. Every function returning
+//! an `int` returns one of the `CHIP8_*` status codes; a null machine pointer gives
+//! `CHIP8_ERROR_NULL_POINTER` instead of crashing.
+
+use std::{
+    ffi::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use chip8_core::{
+    chip8::Chip8,
+    constants::{
+        CHIP8_RAM_MEMORY_SIZE, CHIP8_REGISTER_COUNT, CHIP8_STACK_MEMORY_SIZE, KEYBOARD_KEYS_COUNT,
+        MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    state::{self, STATE_SIZE},
+};
+
+/// Version of the C API, incremented when `chip8.h` changes incompatibly.
+pub const CHIP8_API_VERSION: u32 = 1;
+
+pub const CHIP8_OK: c_int = 0;
+pub const CHIP8_ERROR_NULL_POINTER: c_int = 1;
+pub const CHIP8_ERROR_ROM_EMPTY: c_int = 2;
+pub const CHIP8_ERROR_ROM_TOO_LARGE: c_int = 3;
+pub const CHIP8_ERROR_OUT_OF_RANGE: c_int = 4;
+pub const CHIP8_ERROR_BUFFER_TOO_SMALL: c_int = 5;
+pub const CHIP8_ERROR_INVALID_STATE: c_int = 6;
+pub const CHIP8_ERROR_CRASHED: c_int = 7;
+
+/// An interpreter owned by C code through `chip8_create` and `chip8_destroy`.
+pub struct Chip8Machine {
+    chip8: Chip8,
+    crashed: bool, // An instruction panicked, the machine must be reloaded or restored
+}
+
+/// Registers exchanged with `chip8_get_registers` and `chip8_set_registers`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chip8Registers {
+    pub v: [u8; CHIP8_REGISTER_COUNT],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: [u16; CHIP8_STACK_MEMORY_SIZE],
+    pub sp: u8,
+    pub stack_depth: u8, // Return addresses on the circular stack, at most 16
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// Returns `CHIP8_API_VERSION`, to check that the header matches the library.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_api_version() -> u32 {
+    CHIP8_API_VERSION
+}
+
+/// Returns a static, NUL-terminated description of a status code.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_status_message(status: c_int) -> *const c_char {
+    let message: &'static [u8] = match status {
+        CHIP8_OK => b"ok\0",
+        CHIP8_ERROR_NULL_POINTER => b"null pointer\0",
+        CHIP8_ERROR_ROM_EMPTY => b"the ROM is empty\0",
+        CHIP8_ERROR_ROM_TOO_LARGE => b"the ROM does not fit in memory\0",
+        CHIP8_ERROR_OUT_OF_RANGE => b"argument out of range\0",
+        CHIP8_ERROR_BUFFER_TOO_SMALL => b"buffer too small\0",
+        CHIP8_ERROR_INVALID_STATE => b"invalid snapshot\0",
+        CHIP8_ERROR_CRASHED => b"the program crashed the interpreter\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Creates a machine without a program. Free it with `chip8_destroy`.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_create() -> *mut Chip8Machine {
+    Box::into_raw(Box::new(Chip8Machine {
+        chip8: Chip8::new(),
+        crashed: false,
+    }))
+}
+
+/// Frees a machine. Does nothing when `machine` is null.
+///
+/// # Safety
+/// `machine` must come from `chip8_create` and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_destroy(machine: *mut Chip8Machine) {
+    if !machine.is_null() {
+        drop(unsafe { Box::from_raw(machine) });
+    }
+}
+
+/// Resets the machine and loads `length` bytes of ROM at 0x200.
+///
+/// # Safety
+/// `machine` must be a live machine and `rom` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_rom(
+    machine: *mut Chip8Machine,
+    rom: *const u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if rom.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+    if length == 0 {
+        return CHIP8_ERROR_ROM_EMPTY;
+    }
+    if length > MAX_ROM_SIZE {
+        return CHIP8_ERROR_ROM_TOO_LARGE;
+    }
+
+    let rom = unsafe { slice::from_raw_parts(rom, length) };
+    machine.chip8 = Chip8::start(rom.to_vec());
+    machine.crashed = false;
+    CHIP8_OK
+}
+
+/// Seeds the random numbers of Cxkk, so runs with the same seed and keys are identical.
+///
+/// # Safety
+/// `machine` must be a live machine.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_seed(machine: *mut Chip8Machine, seed: u64) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    machine.chip8.seed_rng(seed);
+    CHIP8_OK
+}
+
+/// Executes `cycles` instructions without touching the timers.
+/// Returns `CHIP8_ERROR_CRASHED` if the interpreter failed, which no program is expected to
+/// cause; the machine then refuses to run until a ROM or snapshot is loaded.
+///
+/// # Safety
+/// `machine` must be a live machine.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_step(machine: *mut Chip8Machine, cycles: u32) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    run_unless_crashed(machine, |chip8| {
+        for _ in 0..cycles {
+            chip8.tick();
+        }
+    })
+}
+
+/// Runs `run` on the machine's interpreter unless an earlier run crashed it. A panic marks the
+/// machine as crashed, since unwinding into C is undefined behaviour.
+fn run_unless_crashed(machine: &mut Chip8Machine, run: impl FnOnce(&mut Chip8)) -> c_int {
+    if machine.crashed {
+        return CHIP8_ERROR_CRASHED;
+    }
+
+    let chip8 = &mut machine.chip8;
+    if panic::catch_unwind(AssertUnwindSafe(|| run(chip8))).is_err() {
+        machine.crashed = true;
+        return CHIP8_ERROR_CRASHED;
+    }
+    CHIP8_OK
+}
+
+/// Decrements the delay and sound timers once, as happens 60 times per second.
+/// `buzzer_on`, when not null, receives whether the buzzer sounded before the update.
+///
+/// # Safety
+/// `machine` must be a live machine and `buzzer_on` null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_tick_timers(
+    machine: *mut Chip8Machine,
+    buzzer_on: *mut bool,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if let Some(buzzer_on) = unsafe { buzzer_on.as_mut() } {
+        *buzzer_on = machine.chip8.sound_timer > 0;
+    }
+    machine.chip8.update_timers();
+    CHIP8_OK
+}
+
+/// Copies the 64x32 screen, row by row, into `pixels`: 1 for a lit pixel, 0 otherwise.
+/// `length` must be at least `CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT`.
+///
+/// # Safety
+/// `machine` must be a live machine and `pixels` must point to `length` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_framebuffer(
+    machine: *const Chip8Machine,
+    pixels: *mut u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_ref() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if pixels.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+    if length < SCREEN_WIDTH * SCREEN_HEIGHT {
+        return CHIP8_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    let pixels = unsafe { slice::from_raw_parts_mut(pixels, SCREEN_WIDTH * SCREEN_HEIGHT) };
+    for (pixel, &pixel_on) in pixels.iter_mut().zip(machine.chip8.screen.iter()) {
+        *pixel = u8::from(pixel_on);
+    }
+    CHIP8_OK
+}
+
+/// Presses or releases a key of the hex keypad (0x0 to 0xF).
+///
+/// # Safety
+/// `machine` must be a live machine.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_key(
+    machine: *mut Chip8Machine,
+    key: u8,
+    pressed: bool,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if key as usize >= KEYBOARD_KEYS_COUNT {
+        return CHIP8_ERROR_OUT_OF_RANGE;
+    }
+    machine.chip8.keyboard[key as usize] = pressed;
+    CHIP8_OK
+}
+
+/// Copies the registers, timers and stack into `registers`.
+///
+/// # Safety
+/// `machine` must be a live machine and `registers` writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_get_registers(
+    machine: *const Chip8Machine,
+    registers: *mut Chip8Registers,
+) -> c_int {
+    let (Some(machine), Some(registers)) =
+        (unsafe { machine.as_ref() }, unsafe { registers.as_mut() })
+    else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    let chip8 = &machine.chip8;
+    *registers = Chip8Registers {
+        v: chip8.v_registers,
+        i: chip8.i_register,
+        pc: chip8.pc,
+        stack: chip8.stack,
+        sp: chip8.stack_pointer as u8,
+        stack_depth: chip8.stack_depth as u8,
+        delay_timer: chip8.delay_timer,
+        sound_timer: chip8.sound_timer,
+    };
+    CHIP8_OK
+}
+
+/// Replaces the registers, timers and stack. Fails without changing anything when the
+/// stack pointer is 16 or more or the stack depth above 16. Any program counter is accepted, as `Bnnn` can take it past
+/// 0xFFF, so whatever `chip8_get_registers` returned can be written back.
+///
+/// # Safety
+/// `machine` must be a live machine and `registers` readable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_registers(
+    machine: *mut Chip8Machine,
+    registers: *const Chip8Registers,
+) -> c_int {
+    let (Some(machine), Some(registers)) =
+        (unsafe { machine.as_mut() }, unsafe { registers.as_ref() })
+    else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if state::check_registers(registers.sp as usize, registers.stack_depth as usize).is_err() {
+        return CHIP8_ERROR_OUT_OF_RANGE;
+    }
+
+    let chip8 = &mut machine.chip8;
+    chip8.v_registers = registers.v;
+    chip8.i_register = registers.i;
+    chip8.pc = registers.pc;
+    chip8.stack = registers.stack;
+    chip8.stack_pointer = registers.sp as usize;
+    chip8.stack_depth = registers.stack_depth as usize;
+    chip8.delay_timer = registers.delay_timer;
+    chip8.sound_timer = registers.sound_timer;
+    CHIP8_OK
+}
+
+/// Copies `length` bytes of memory starting at `address` into `data`.
+///
+/// # Safety
+/// `machine` must be a live machine and `data` must point to `length` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_read_memory(
+    machine: *const Chip8Machine,
+    address: u16,
+    data: *mut u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_ref() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    let Some(range) = memory_range(address, length) else {
+        return CHIP8_ERROR_OUT_OF_RANGE;
+    };
+    if length == 0 {
+        return CHIP8_OK;
+    }
+    if data.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+
+    unsafe { ptr::copy_nonoverlapping(machine.chip8.ram[range].as_ptr(), data, length) };
+    CHIP8_OK
+}
+
+/// Writes `length` bytes from `data` to memory starting at `address`.
+/// The screen is not redrawn by itself; the written bytes are seen by the next instructions.
+///
+/// # Safety
+/// `machine` must be a live machine and `data` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_write_memory(
+    machine: *mut Chip8Machine,
+    address: u16,
+    data: *const u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    let Some(range) = memory_range(address, length) else {
+        return CHIP8_ERROR_OUT_OF_RANGE;
+    };
+    if length == 0 {
+        return CHIP8_OK;
+    }
+    if data.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+
+    let data = unsafe { slice::from_raw_parts(data, length) };
+    machine.chip8.ram[range].copy_from_slice(data);
+    CHIP8_OK
+}
+
+/// Size in bytes of a snapshot, to allocate the buffer of `chip8_snapshot`.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_snapshot_size() -> usize {
+    STATE_SIZE
+}
+
+/// Writes a snapshot of the whole machine into `data`, which must hold `chip8_snapshot_size()` bytes.
+///
+/// # Safety
+/// `machine` must be a live machine and `data` must point to `length` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_snapshot(
+    machine: *const Chip8Machine,
+    data: *mut u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_ref() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if data.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+    if length < STATE_SIZE {
+        return CHIP8_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    let state = machine.chip8.save_state();
+    unsafe { ptr::copy_nonoverlapping(state.as_ptr(), data, state.len()) };
+    CHIP8_OK
+}
+
+/// Restores a snapshot written by `chip8_snapshot`. The machine is unchanged when it is rejected.
+///
+/// # Safety
+/// `machine` must be a live machine and `data` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_restore(
+    machine: *mut Chip8Machine,
+    data: *const u8,
+    length: usize,
+) -> c_int {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return CHIP8_ERROR_NULL_POINTER;
+    };
+    if data.is_null() {
+        return CHIP8_ERROR_NULL_POINTER;
+    }
+
+    let state = unsafe { slice::from_raw_parts(data, length) };
+    match machine.chip8.load_state(state) {
+        Ok(()) => {
+            machine.crashed = false;
+            CHIP8_OK
+        }
+        Err(_) => CHIP8_ERROR_INVALID_STATE,
+    }
+}
+
+/// The memory addresses `address..address + length`, if they are all inside the 4 KiB of RAM.
+fn memory_range(address: u16, length: usize) -> Option<std::ops::Range<usize>> {
+    let start = address as usize;
+    let end = start.checked_add(length)?;
+    (end <= CHIP8_RAM_MEMORY_SIZE).then_some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Crashes the machine as a bug in the interpreter would, through the same guard as `chip8_step`.
+    unsafe fn crash(machine: *mut Chip8Machine) -> c_int {
+        let machine = unsafe { machine.as_mut() }.unwrap();
+        run_unless_crashed(machine, |_| panic!("interpreter bug simulated by a test"))
+    }
+
+    #[test]
+    fn crashed_machines_refuse_to_run_until_restored() {
+        // 1200: JP 0x200
+        let rom = [0x12, 0x00];
+        let machine = chip8_create();
+        unsafe {
+            assert_eq!(chip8_load_rom(machine, rom.as_ptr(), rom.len()), CHIP8_OK);
+            let mut snapshot = vec![0u8; chip8_snapshot_size()];
+            assert_eq!(
+                chip8_snapshot(machine, snapshot.as_mut_ptr(), snapshot.len()),
+                CHIP8_OK
+            );
+
+            assert_eq!(crash(machine), CHIP8_ERROR_CRASHED);
+            assert_eq!(chip8_step(machine, 1), CHIP8_ERROR_CRASHED);
+            assert_eq!(chip8_step(machine, 0), CHIP8_ERROR_CRASHED);
+
+            assert_eq!(
+                chip8_restore(machine, snapshot.as_ptr(), 10),
+                CHIP8_ERROR_INVALID_STATE
+            );
+            assert_eq!(chip8_step(machine, 1), CHIP8_ERROR_CRASHED);
+            assert_eq!(
+                chip8_restore(machine, snapshot.as_ptr(), snapshot.len()),
+                CHIP8_OK
+            );
+            assert_eq!(chip8_step(machine, 1), CHIP8_OK);
+
+            assert_eq!(crash(machine), CHIP8_ERROR_CRASHED);
+            assert_eq!(chip8_load_rom(machine, rom.as_ptr(), rom.len()), CHIP8_OK);
+            assert_eq!(chip8_step(machine, 1), CHIP8_OK);
+
+            chip8_destroy(machine);
+        }
+    }
+}
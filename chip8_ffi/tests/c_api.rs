@@ -0,0 +1,245 @@
+//! Exercises the C API through its Rust declarations, then compiles and runs the example
+//! C program against the built library and `include/chip8.h`.
+
+use std::{mem, path::PathBuf, process::Command, ptr};
+
+use chip8_ffi::*;
+
+/// 6005: LD V0, 5, F029: LD F, V0, D005: DRW V0, V0, 5, 1206: JP 0x206
+const DRAW_FIVE: [u8; 8] = [0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+
+/// Creates a machine running `rom` and destroys it after `test`.
+fn with_machine(rom: &[u8], test: impl FnOnce(*mut Chip8Machine)) {
+    let machine = chip8_create();
+    assert_eq!(
+        unsafe { chip8_load_rom(machine, rom.as_ptr(), rom.len()) },
+        CHIP8_OK
+    );
+    test(machine);
+    unsafe { chip8_destroy(machine) };
+}
+
+#[test]
+fn registers_match_the_c_layout() {
+    // 16 + 2 + 2 + 32 + 4 bytes
+    assert_eq!(mem::size_of::<Chip8Registers>(), 56);
+}
+
+#[test]
+fn null_machines_are_rejected() {
+    let mut pixels = [0u8; 64 * 32];
+    unsafe {
+        assert_eq!(chip8_step(ptr::null_mut(), 1), CHIP8_ERROR_NULL_POINTER);
+        assert_eq!(
+            chip8_framebuffer(ptr::null(), pixels.as_mut_ptr(), pixels.len()),
+            CHIP8_ERROR_NULL_POINTER
+        );
+        chip8_destroy(ptr::null_mut());
+    }
+}
+
+#[test]
+fn roms_are_validated() {
+    let machine = chip8_create();
+    let too_large = vec![0u8; 4096];
+    unsafe {
+        assert_eq!(
+            chip8_load_rom(machine, DRAW_FIVE.as_ptr(), 0),
+            CHIP8_ERROR_ROM_EMPTY
+        );
+        assert_eq!(
+            chip8_load_rom(machine, too_large.as_ptr(), too_large.len()),
+            CHIP8_ERROR_ROM_TOO_LARGE
+        );
+        chip8_destroy(machine);
+    }
+}
+
+#[test]
+fn steps_draw_to_the_framebuffer() {
+    with_machine(&DRAW_FIVE, |machine| unsafe {
+        assert_eq!(chip8_step(machine, 3), CHIP8_OK);
+
+        let mut pixels = [0u8; 64 * 32];
+        assert_eq!(
+            chip8_framebuffer(machine, pixels.as_mut_ptr(), pixels.len() - 1),
+            CHIP8_ERROR_BUFFER_TOO_SMALL
+        );
+        assert_eq!(
+            chip8_framebuffer(machine, pixels.as_mut_ptr(), pixels.len()),
+            CHIP8_OK
+        );
+        assert_eq!(&pixels[5 * 64 + 5..5 * 64 + 9], &[1, 1, 1, 1]);
+    });
+}
+
+#[test]
+fn registers_and_timers_round_trip() {
+    with_machine(&DRAW_FIVE, |machine| unsafe {
+        let mut registers = Chip8Registers::default();
+        assert_eq!(chip8_get_registers(machine, &mut registers), CHIP8_OK);
+        assert_eq!(registers.pc, 0x200);
+
+        registers.v[0xA] = 0x42;
+        registers.sound_timer = 1;
+        assert_eq!(chip8_set_registers(machine, &registers), CHIP8_OK);
+
+        let mut buzzer_on = false;
+        assert_eq!(chip8_tick_timers(machine, &mut buzzer_on), CHIP8_OK);
+        assert!(buzzer_on);
+
+        let mut read_back = Chip8Registers::default();
+        chip8_get_registers(machine, &mut read_back);
+        assert_eq!(read_back.v[0xA], 0x42);
+        assert_eq!(read_back.sound_timer, 0);
+
+        registers.sp = 16;
+        assert_eq!(
+            chip8_set_registers(machine, &registers),
+            CHIP8_ERROR_OUT_OF_RANGE
+        );
+        registers.sp = 0;
+        registers.stack_depth = 17;
+        assert_eq!(
+            chip8_set_registers(machine, &registers),
+            CHIP8_ERROR_OUT_OF_RANGE
+        );
+    });
+}
+
+#[test]
+fn registers_past_memory_are_written_back() {
+    // 6010: LD V0, 0x10, BFFF: JP V0, 0xFFF, which jumps to 0x100F
+    with_machine(&[0x60, 0x10, 0xBF, 0xFF], |machine| unsafe {
+        assert_eq!(chip8_step(machine, 2), CHIP8_OK);
+
+        let mut registers = Chip8Registers::default();
+        assert_eq!(chip8_get_registers(machine, &mut registers), CHIP8_OK);
+        assert_eq!(registers.pc, 0x100F);
+        assert_eq!(chip8_set_registers(machine, &registers), CHIP8_OK);
+
+        let mut read_back = Chip8Registers::default();
+        chip8_get_registers(machine, &mut read_back);
+        assert_eq!(read_back.pc, 0x100F);
+    });
+}
+
+#[test]
+fn wrapped_stacks_are_written_back_with_their_depth() {
+    // 2200: CALL 0x200 forever
+    with_machine(&[0x22, 0x00], |machine| unsafe {
+        assert_eq!(chip8_step(machine, 20), CHIP8_OK);
+
+        let mut registers = Chip8Registers::default();
+        assert_eq!(chip8_get_registers(machine, &mut registers), CHIP8_OK);
+        assert_eq!((registers.sp, registers.stack_depth), (4, 16));
+        registers.stack_depth = 2;
+        assert_eq!(chip8_set_registers(machine, &registers), CHIP8_OK);
+
+        let mut read_back = Chip8Registers::default();
+        chip8_get_registers(machine, &mut read_back);
+        assert_eq!(read_back, registers);
+    });
+}
+
+#[test]
+fn memory_reads_and_writes_are_bounds_checked() {
+    with_machine(&DRAW_FIVE, |machine| unsafe {
+        let patch = [0xAB, 0xCD];
+        assert_eq!(
+            chip8_write_memory(machine, 0x300, patch.as_ptr(), patch.len()),
+            CHIP8_OK
+        );
+
+        let mut read = [0u8; 2];
+        assert_eq!(
+            chip8_read_memory(machine, 0x300, read.as_mut_ptr(), read.len()),
+            CHIP8_OK
+        );
+        assert_eq!(read, patch);
+        assert_eq!(
+            chip8_read_memory(machine, 0xFFF, read.as_mut_ptr(), read.len()),
+            CHIP8_ERROR_OUT_OF_RANGE
+        );
+    });
+}
+
+#[test]
+fn keys_outside_the_keypad_are_rejected() {
+    with_machine(&DRAW_FIVE, |machine| unsafe {
+        assert_eq!(chip8_set_key(machine, 0xF, true), CHIP8_OK);
+        assert_eq!(chip8_set_key(machine, 0x10, true), CHIP8_ERROR_OUT_OF_RANGE);
+    });
+}
+
+#[test]
+fn stack_underflows_keep_running_and_snapshots_restore() {
+    // 00EE: RET with an empty stack, which pops the last entry of the circular stack
+    with_machine(&[0x00, 0xEE], |machine| unsafe {
+        let mut snapshot = vec![0u8; chip8_snapshot_size()];
+        assert_eq!(
+            chip8_snapshot(machine, snapshot.as_mut_ptr(), snapshot.len()),
+            CHIP8_OK
+        );
+
+        assert_eq!(chip8_step(machine, 1), CHIP8_OK);
+        assert_eq!(chip8_step(machine, 1), CHIP8_OK);
+
+        assert_eq!(
+            chip8_restore(machine, snapshot.as_ptr(), 10),
+            CHIP8_ERROR_INVALID_STATE
+        );
+        assert_eq!(
+            chip8_restore(machine, snapshot.as_ptr(), snapshot.len()),
+            CHIP8_OK
+        );
+        assert_eq!(chip8_step(machine, 0), CHIP8_OK);
+    });
+}
+
+#[test]
+fn c_example_runs_against_the_header_and_library() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The test binary is in target/<profile>/deps, next to which cargo puts the libraries
+    let library_dir = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .to_path_buf();
+    let executable = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("run_rom");
+
+    let compiled = Command::new("cc")
+        .arg(manifest_dir.join("examples/run_rom.c"))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg(format!("-I{}", manifest_dir.join("include").display()))
+        .arg(format!("-L{}", library_dir.display()))
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-lchip8_ffi")
+        .arg("-o")
+        .arg(&executable)
+        .status();
+    match compiled {
+        Ok(status) => assert!(status.success(), "the example does not compile"),
+        Err(e) => {
+            eprintln!("skipping the C example, no C compiler: {}", e);
+            return;
+        }
+    }
+
+    let output = Command::new(&executable)
+        .arg(manifest_dir.join("../files/roms/PONG"))
+        .arg("120")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains('#'), "nothing drawn:\n{}", stdout);
+    assert!(stdout.contains("snapshot replay matches"));
+}
@@ -0,0 +1,331 @@
+//! Python bindings of the CHIP-8 interpreter, built as the `chip8` module with maturin:
+//!
+//! ```python
+//! import numpy as np
+//! from chip8 import Chip8
+//!
+//! machine = Chip8(open("files/roms/PONG", "rb").read(), seed=42)
+//! machine.set_key(0x1, True)
+//! buzzer = machine.run_frame()
+//! screen = np.asarray(machine.screen)  # shape (32, 64), dtype uint8, 1 for a lit pixel
+//! checkpoint = machine.clone()
+//! ```
+
+use std::{
+    ffi::{c_int, c_void},
+    ptr,
+};
+
+use chip8_core::{
+    chip8::Chip8 as Machine,
+    constants::{
+        CHIP8_RAM_MEMORY_SIZE, KEYBOARD_KEYS_COUNT, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+};
+use pyo3::{
+    exceptions::{PyBufferError, PyValueError},
+    ffi,
+    prelude::*,
+    types::PyBytes,
+};
+
+/// Instructions per frame when none is given, like `--speed` on desktop.
+const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// A CHIP-8 machine.
+#[pyclass(module = "chip8")]
+struct Chip8 {
+    machine: Machine,
+    #[pyo3(get, set)]
+    speed: usize, // Instructions per frame of run_frame
+}
+
+#[pymethods]
+impl Chip8 {
+    /// Chip8(rom=None, seed=None, speed=10): a machine running `rom` (bytes), whose Cxkk
+    /// random numbers come from `seed` when it is given.
+    #[new]
+    #[pyo3(signature = (rom = None, seed = None, speed = DEFAULT_INSTRUCTIONS_PER_FRAME))]
+    fn new(rom: Option<&[u8]>, seed: Option<u64>, speed: usize) -> PyResult<Self> {
+        let mut chip8 = Chip8 {
+            machine: Machine::new(),
+            speed,
+        };
+        if let Some(rom) = rom {
+            chip8.load_rom(rom)?;
+        }
+        // After the ROM, which reseeds the machine
+        if let Some(seed) = seed {
+            chip8.machine.seed_rng(seed);
+        }
+        Ok(chip8)
+    }
+
+    /// Resets the machine and loads a ROM at 0x200, with a new random seed.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        if rom.is_empty() {
+            return Err(PyValueError::new_err("the ROM is empty"));
+        }
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(PyValueError::new_err(format!(
+                "the ROM is {} bytes, the maximum is {} bytes",
+                rom.len(),
+                MAX_ROM_SIZE
+            )));
+        }
+
+        self.machine = Machine::start(rom.to_vec());
+        Ok(())
+    }
+
+    /// Seeds the random numbers of Cxkk, so runs with the same seed and keys are identical.
+    fn seed(&mut self, seed: u64) {
+        self.machine.seed_rng(seed);
+    }
+
+    /// Executes `cycles` instructions without updating the timers.
+    #[pyo3(signature = (cycles = 1))]
+    fn step(&mut self, cycles: usize) {
+        for _ in 0..cycles {
+            self.machine.tick();
+        }
+    }
+
+    /// Runs one 60 Hz frame of `speed` instructions and a timer update.
+    /// Returns True when the buzzer sounds.
+    fn run_frame(&mut self) -> bool {
+        self.machine.run_frame(self.speed)
+    }
+
+    /// Presses or releases a key of the hex keypad (0x0 to 0xF).
+    fn set_key(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= KEYBOARD_KEYS_COUNT {
+            return Err(PyValueError::new_err(format!(
+                "key {:#x} is not on the keypad",
+                key
+            )));
+        }
+        self.machine.keyboard[key] = pressed;
+        Ok(())
+    }
+
+    /// Releases every key.
+    fn release_keys(&mut self) {
+        self.machine.reset_keyboard();
+    }
+
+    /// A read-only copy of the screen supporting the buffer protocol: `numpy.asarray(screen)`
+    /// is a (32, 64) array of uint8, 1 for a lit pixel.
+    #[getter]
+    fn screen(&self) -> Screen {
+        Screen {
+            pixels: self
+                .machine
+                .screen
+                .iter()
+                .map(|&pixel_on| u8::from(pixel_on))
+                .collect(),
+            shape: [
+                SCREEN_HEIGHT as ffi::Py_ssize_t,
+                SCREEN_WIDTH as ffi::Py_ssize_t,
+            ],
+            strides: [SCREEN_WIDTH as ffi::Py_ssize_t, 1],
+        }
+    }
+
+    /// The 16 V registers, as bytes.
+    #[getter]
+    fn v<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.machine.v_registers)
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.machine.pc
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.machine.i_register
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.machine.delay_timer
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.machine.sound_timer
+    }
+
+    /// Reads `length` bytes of memory from `address`.
+    fn read_memory<'py>(
+        &self,
+        py: Python<'py>,
+        address: usize,
+        length: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let end = memory_end(address, length)?;
+        Ok(PyBytes::new(py, &self.machine.ram[address..end]))
+    }
+
+    /// Writes bytes to memory from `address`, e.g. to set a score in RAM.
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        let end = memory_end(address, data.len())?;
+        self.machine.ram[address..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// An independent copy of the machine, random generator included.
+    fn clone(&self) -> Chip8 {
+        Chip8 {
+            machine: self.machine.clone(),
+            speed: self.speed,
+        }
+    }
+
+    fn __copy__(&self) -> Chip8 {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Chip8 {
+        self.clone()
+    }
+
+    /// The whole machine as bytes, to restore later with `load_state`.
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.machine.save_state())
+    }
+
+    /// Restores bytes from `save_state`. Raises ValueError, leaving the machine unchanged,
+    /// when they are not a valid snapshot.
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.machine
+            .load_state(state)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Chip8(pc={:#05x}, i={:#05x}, speed={})",
+            self.machine.pc, self.machine.i_register, self.speed
+        )
+    }
+}
+
+/// End of the memory range `address..address + length`, if it is inside the 4 KiB of RAM.
+fn memory_end(address: usize, length: usize) -> PyResult<usize> {
+    address
+        .checked_add(length)
+        .filter(|&end| end <= CHIP8_RAM_MEMORY_SIZE)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{} bytes at {:#x} are outside the {} bytes of memory",
+                length, address, CHIP8_RAM_MEMORY_SIZE
+            ))
+        })
+}
+
+/// A frozen copy of the screen exposed through the buffer protocol as a 2D array of bytes.
+#[pyclass(module = "chip8", frozen)]
+struct Screen {
+    pixels: Vec<u8>,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+#[pymethods]
+impl Screen {
+    #[getter]
+    fn width(&self) -> usize {
+        SCREEN_WIDTH
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        SCREEN_HEIGHT
+    }
+
+    /// Returns 1 when the pixel at (x, y) is lit.
+    fn pixel(&self, x: usize, y: usize) -> PyResult<u8> {
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return Err(PyValueError::new_err(format!(
+                "({}, {}) is outside the screen",
+                x, y
+            )));
+        }
+        Ok(self.pixels[y * SCREEN_WIDTH + x])
+    }
+
+    /// The pixels row by row, as bytes.
+    fn tobytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.pixels)
+    }
+
+    /// Fills a read-only, C-contiguous (32, 64) view of unsigned bytes. Consumers not asking for
+    /// the shape get a flat view of the 2048 bytes, as `PyBuffer_FillInfo` gives.
+    ///
+    /// # Safety
+    /// Called by Python with a valid `view` to fill.
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("view is null"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("the screen is read-only"));
+        }
+
+        let screen = slf.get();
+        unsafe {
+            (*view).buf = screen.pixels.as_ptr() as *mut c_void;
+            (*view).len = screen.pixels.len() as ffi::Py_ssize_t;
+            (*view).readonly = 1;
+            (*view).itemsize = 1;
+            (*view).format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+                c"B".as_ptr() as *mut _
+            } else {
+                ptr::null_mut()
+            };
+            if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+                (*view).ndim = 2;
+                (*view).shape = screen.shape.as_ptr() as *mut _;
+                (*view).strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES {
+                    screen.strides.as_ptr() as *mut _
+                } else {
+                    ptr::null_mut()
+                };
+            } else {
+                (*view).ndim = 1;
+                (*view).shape = ptr::null_mut();
+                (*view).strides = ptr::null_mut();
+            }
+            (*view).suboffsets = ptr::null_mut();
+            (*view).internal = ptr::null_mut();
+            // The view keeps the screen, and so the pixels, alive
+            (*view).obj = slf.into_any().into_ptr();
+        }
+        Ok(())
+    }
+
+    /// Nothing to free, the pixels belong to the screen object.
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+
+    fn __repr__(&self) -> String {
+        format!("Screen({}x{})", SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
+
+/// The `chip8` Python module.
+#[pymodule]
+fn chip8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Chip8>()?;
+    module.add_class::<Screen>()?;
+    module.add("SCREEN_WIDTH", SCREEN_WIDTH)?;
+    module.add("SCREEN_HEIGHT", SCREEN_HEIGHT)?;
+    Ok(())
+}
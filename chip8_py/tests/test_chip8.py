@@ -0,0 +1,121 @@
+"""Tests of the Python bindings. Run them after `pip install ./chip8_py` with `pytest chip8_py`."""
+
+import copy
+import hashlib
+from pathlib import Path
+
+import pytest
+
+from chip8 import SCREEN_HEIGHT, SCREEN_WIDTH, Chip8
+
+PONG = (Path(__file__).parents[2] / "files" / "roms" / "PONG").read_bytes()
+
+# 6005: LD V0, 5, F029: LD F, V0, D005: DRW V0, V0, 5, 1206: JP 0x206
+DRAW_FIVE = bytes([0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+
+# C0FF: RND V0, 0xFF, 1200: JP 0x200
+RANDOM_LOOP = bytes([0xC0, 0xFF, 0x12, 0x00])
+
+
+def test_steps_draw_to_the_screen():
+    machine = Chip8(DRAW_FIVE)
+    machine.step(3)
+
+    screen = memoryview(machine.screen)
+    assert screen.shape == (SCREEN_HEIGHT, SCREEN_WIDTH)
+    assert screen.format == "B"
+    assert screen.readonly
+    assert screen.tolist()[5][5:9] == [1, 1, 1, 1]
+    assert machine.screen.pixel(5, 5) == 1
+    assert machine.pc == 0x206
+
+
+def test_screen_is_a_numpy_array():
+    np = pytest.importorskip("numpy")
+    machine = Chip8(DRAW_FIVE)
+    machine.run_frame()
+
+    screen = np.asarray(machine.screen)
+    assert screen.shape == (32, 64)
+    assert screen.dtype == np.uint8
+    assert screen[5, 5:9].tolist() == [1, 1, 1, 1]
+    assert not screen.flags.writeable
+
+
+def test_simple_buffer_consumers_get_the_flat_pixels():
+    machine = Chip8(DRAW_FIVE)
+    machine.run_frame()
+
+    # hashlib asks for a plain buffer without its shape and rejects views of several dimensions
+    pixels = machine.screen.tobytes()
+    assert hashlib.sha256(machine.screen).digest() == hashlib.sha256(pixels).digest()
+    assert len(pixels) == SCREEN_WIDTH * SCREEN_HEIGHT
+
+
+def test_frames_run_speed_instructions_and_a_timer_update():
+    machine = Chip8(PONG, speed=5)
+    machine.run_frame()
+    assert machine.pc != 0x200
+    assert machine.speed == 5
+
+
+def test_invalid_roms_and_keys_are_rejected():
+    machine = Chip8()
+    with pytest.raises(ValueError):
+        machine.load_rom(b"")
+    with pytest.raises(ValueError):
+        machine.load_rom(bytes(4096))
+    with pytest.raises(ValueError):
+        machine.set_key(0x10, True)
+    machine.set_key(0xF, True)
+
+
+def test_memory_is_bounds_checked():
+    machine = Chip8(DRAW_FIVE)
+    machine.write_memory(0x300, b"\xab\xcd")
+    assert machine.read_memory(0x300, 2) == b"\xab\xcd"
+    with pytest.raises(ValueError):
+        machine.read_memory(0xFFF, 2)
+
+
+def test_same_seed_gives_the_same_random_numbers():
+    first = Chip8(RANDOM_LOOP, seed=7)
+    second = Chip8(RANDOM_LOOP, seed=7)
+    first.step()
+    second.step()
+    assert first.v == second.v
+
+
+def test_clones_are_independent_and_replay_identically():
+    machine = Chip8(PONG, seed=42)
+    for _ in range(30):
+        machine.run_frame()
+
+    clone = machine.clone()
+    deep_copy = copy.deepcopy(machine)
+    for candidate in (machine, clone, deep_copy):
+        candidate.set_key(0x1, True)
+        for _ in range(60):
+            candidate.run_frame()
+
+    assert clone.save_state() == machine.save_state()
+    assert deep_copy.save_state() == machine.save_state()
+
+    clone.step(100)
+    assert clone.save_state() != machine.save_state()
+
+
+def test_states_round_trip():
+    machine = Chip8(PONG, seed=1)
+    state = machine.save_state()
+    for _ in range(60):
+        machine.run_frame()
+    after = machine.save_state()
+
+    machine.load_state(state)
+    for _ in range(60):
+        machine.run_frame()
+    assert machine.save_state() == after
+
+    with pytest.raises(ValueError):
+        machine.load_state(b"not a state")
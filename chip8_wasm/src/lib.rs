@@ -0,0 +1,122 @@
+//! WebAssembly bindings of the CHIP-8 interpreter for the browser page in `www/`.
+//!
+//! Build with `wasm-pack build chip8_wasm --target web`, which writes the JavaScript
+//! module to `chip8_wasm/pkg/`. JavaScript drives the machine one 60 Hz frame at a time:
+//!
+//! ```js
+//! const chip8 = new Chip8();
+//! chip8.load_rom(new Uint8Array(await (await fetch("PONG")).arrayBuffer()));
+//! chip8.set_key(0x1, true);
+//! const buzzer = chip8.run_frame();
+//! const pixels = chip8.framebuffer(); // 64 * 32 bytes, 1 for a lit pixel
+//! ```
+
+use chip8_core::{
+    chip8::Chip8 as Machine,
+    constants::{KEYBOARD_KEYS_COUNT, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+use wasm_bindgen::prelude::*;
+
+/// Instructions per frame when the page does not call `set_speed`, like `--speed` on desktop.
+const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// A CHIP-8 machine exposed to JavaScript. Errors are thrown as strings.
+#[wasm_bindgen]
+pub struct Chip8 {
+    machine: Machine,
+    instructions_per_frame: usize,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    /// Creates a machine without a program.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8 {
+        Chip8 {
+            machine: Machine::new(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+        }
+    }
+
+    /// Screen width in pixels.
+    pub fn width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    /// Screen height in pixels.
+    pub fn height() -> usize {
+        SCREEN_HEIGHT
+    }
+
+    /// Resets the machine and loads a ROM at 0x200.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        if rom.is_empty() {
+            return Err(String::from("the ROM is empty"));
+        }
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(format!(
+                "the ROM is {} bytes, the maximum is {} bytes",
+                rom.len(),
+                MAX_ROM_SIZE
+            ));
+        }
+
+        self.machine = Machine::start(rom.to_vec());
+        self.machine.request_full_redraw();
+        Ok(())
+    }
+
+    /// Seeds the random numbers of Cxkk, to replay a session exactly.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.machine.seed_rng(seed);
+    }
+
+    /// Sets how many instructions run in each frame.
+    pub fn set_speed(&mut self, instructions_per_frame: usize) {
+        self.instructions_per_frame = instructions_per_frame;
+    }
+
+    /// Runs one 60 Hz frame and returns true when the buzzer sounds.
+    pub fn run_frame(&mut self) -> bool {
+        self.machine.run_frame(self.instructions_per_frame)
+    }
+
+    /// Presses or releases a key of the hex keypad. Keys above 0xF are ignored.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if (key as usize) < KEYBOARD_KEYS_COUNT {
+            self.machine.keyboard[key as usize] = pressed;
+        }
+    }
+
+    /// The screen, row by row, one byte per pixel: 1 when lit, 0 otherwise.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.machine
+            .screen
+            .iter()
+            .map(|&pixel_on| u8::from(pixel_on))
+            .collect()
+    }
+
+    /// Returns true when the screen changed since the last call, so the page can skip drawing.
+    pub fn take_redraw(&mut self) -> bool {
+        let needs_redraw = self.machine.needs_redraw;
+        self.machine.clear_redraw_state();
+        needs_redraw
+    }
+
+    /// Serializes the whole machine, see `Chip8::save_state` in `chip8_core`.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.machine.save_state()
+    }
+
+    /// Restores a snapshot from `save_state`. The machine is unchanged if it is rejected.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        self.machine.load_state(state).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,71 @@
+//! Tests of the JavaScript-facing API. They run as native tests with `cargo test`, and in
+//! Node.js with `wasm-pack test --node chip8_wasm` (or `cargo test --target wasm32-unknown-unknown`
+//! with `wasm-bindgen-test-runner` as the runner).
+
+use chip8_wasm::Chip8;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const SCREEN_PIXELS: usize = 64 * 32;
+
+/// 6005: LD V0, 5, F029: LD F, V0, D005: DRW V0, V0, 5, 1206: JP 0x206
+const DRAW_FIVE: [u8; 8] = [0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn framebuffer_shows_the_drawn_sprite() {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&DRAW_FIVE).unwrap();
+    chip8.run_frame();
+
+    let framebuffer = chip8.framebuffer();
+    assert_eq!(framebuffer.len(), SCREEN_PIXELS);
+    // The top row of the "5" glyph is 0xF0, drawn at (5, 5)
+    assert_eq!(&framebuffer[5 * 64 + 5..5 * 64 + 9], &[1, 1, 1, 1]);
+    assert_eq!(
+        framebuffer.iter().filter(|&&pixel| pixel == 1).count(),
+        4 + 1 + 4 + 1 + 4
+    );
+    assert!(chip8.take_redraw());
+    assert!(!chip8.take_redraw());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn invalid_roms_are_rejected() {
+    let mut chip8 = Chip8::new();
+    assert!(chip8.load_rom(&[]).is_err());
+    assert!(chip8.load_rom(&vec![0; 4096]).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn keys_are_read_by_the_program() {
+    // E09E: SKP V0, 1200: JP 0x200, D005: DRW V0, V0, 5, 1206: JP 0x206
+    // Waits for key 0, then draws the "0" glyph at (0, 0)
+    let rom = [0xE0, 0x9E, 0x12, 0x00, 0xD0, 0x05, 0x12, 0x06];
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+    chip8.set_key(0x42, true); // Ignored
+    chip8.run_frame();
+    assert_eq!(chip8.framebuffer(), vec![0; SCREEN_PIXELS]);
+
+    chip8.set_key(0x0, true);
+    chip8.run_frame();
+    assert_eq!(&chip8.framebuffer()[..4], &[1, 1, 1, 1]);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn state_round_trips() {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&DRAW_FIVE).unwrap();
+    let before = chip8.save_state();
+    chip8.run_frame();
+    assert_ne!(chip8.framebuffer(), vec![0; SCREEN_PIXELS]);
+
+    chip8.load_state(&before).unwrap();
+    assert_eq!(chip8.framebuffer(), vec![0; SCREEN_PIXELS]);
+    assert_eq!(chip8.save_state(), before);
+    assert!(chip8.load_state(b"garbage").is_err());
+}
@@ -0,0 +1,169 @@
+// Browser frontend of the CHIP-8 interpreter. Build the module first with
+// `wasm-pack build chip8_wasm --target web --out-dir www/pkg`.
+import init, { Chip8 } from "./pkg/chip8_wasm.js";
+
+// Same layout as the desktop emulator: 1234 / QWER / ASDF / ZXCV
+const KEYS = {
+  Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xC,
+  KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xD,
+  KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xE,
+  KeyZ: 0xA, KeyX: 0x0, KeyC: 0xB, KeyV: 0xF,
+};
+const FOREGROUND = [0xFF, 0xFF, 0xFF];
+const BACKGROUND = [0x00, 0x00, 0x00];
+const FRAME_MS = 1000 / 60;
+const STATE_KEY = "chip8-state";
+
+await init();
+const chip8 = new Chip8();
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const image = context.createImageData(Chip8.width(), Chip8.height());
+const status = document.getElementById("status");
+const pauseButton = document.getElementById("pause");
+let romLoaded = false;
+let paused = false;
+let beep = null;
+
+function loadRom(bytes, name) {
+  try {
+    chip8.load_rom(bytes);
+    romLoaded = true;
+    status.textContent = `Running ${name}`;
+  } catch (error) {
+    status.textContent = `Could not load ${name}: ${error}`;
+  }
+}
+
+function draw() {
+  const pixels = chip8.framebuffer();
+  for (let index = 0; index < pixels.length; index++) {
+    const color = pixels[index] ? FOREGROUND : BACKGROUND;
+    image.data.set([...color, 0xFF], index * 4);
+  }
+  context.putImageData(image, 0, 0);
+}
+
+// The buzzer is a square wave that is started on the first key press, as browsers
+// only allow audio after a user gesture
+function setBuzzer(on) {
+  if (!beep) {
+    return;
+  }
+  beep.gain.gain.value = on ? 0.1 : 0;
+}
+
+function startAudio() {
+  if (beep) {
+    return;
+  }
+  const audio = new AudioContext();
+  const oscillator = audio.createOscillator();
+  const gain = audio.createGain();
+  oscillator.type = "square";
+  oscillator.frequency.value = 440;
+  gain.gain.value = 0;
+  oscillator.connect(gain).connect(audio.destination);
+  oscillator.start();
+  beep = { oscillator, gain };
+}
+
+// Runs whole 60 Hz frames to catch up with the time elapsed since the last animation frame
+let lastTime = performance.now();
+let pending = 0;
+function loop(time) {
+  pending = Math.min(pending + (time - lastTime), 5 * FRAME_MS);
+  lastTime = time;
+  if (romLoaded && !paused) {
+    while (pending >= FRAME_MS) {
+      setBuzzer(chip8.run_frame());
+      pending -= FRAME_MS;
+    }
+    if (chip8.take_redraw()) {
+      draw();
+    }
+  } else {
+    setBuzzer(false);
+  }
+  requestAnimationFrame(loop);
+}
+
+document.addEventListener("keydown", (event) => {
+  startAudio();
+  if (event.code in KEYS) {
+    chip8.set_key(KEYS[event.code], true);
+    event.preventDefault();
+  }
+});
+document.addEventListener("keyup", (event) => {
+  if (event.code in KEYS) {
+    chip8.set_key(KEYS[event.code], false);
+  }
+});
+
+document.getElementById("rom-file").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (file) {
+    loadRom(new Uint8Array(await file.arrayBuffer()), file.name);
+  }
+});
+
+const drop = document.getElementById("drop");
+drop.addEventListener("dragover", (event) => {
+  event.preventDefault();
+  drop.classList.add("over");
+});
+drop.addEventListener("dragleave", () => drop.classList.remove("over"));
+drop.addEventListener("drop", async (event) => {
+  event.preventDefault();
+  drop.classList.remove("over");
+  const file = event.dataTransfer.files[0];
+  if (file) {
+    loadRom(new Uint8Array(await file.arrayBuffer()), file.name);
+  }
+});
+
+pauseButton.addEventListener("click", () => {
+  paused = !paused;
+  pauseButton.textContent = paused ? "Resume" : "Pause";
+});
+
+document.getElementById("speed").addEventListener("change", (event) => {
+  chip8.set_speed(Math.max(1, Number(event.target.value)));
+});
+
+// Save states are kept in the browser's local storage as base64
+document.getElementById("save").addEventListener("click", () => {
+  const state = chip8.save_state();
+  localStorage.setItem(STATE_KEY, btoa(String.fromCharCode(...state)));
+  status.textContent = "State saved";
+});
+document.getElementById("load").addEventListener("click", () => {
+  const saved = localStorage.getItem(STATE_KEY);
+  if (!saved) {
+    status.textContent = "No saved state";
+    return;
+  }
+  try {
+    chip8.load_state(Uint8Array.from(atob(saved), (character) => character.charCodeAt(0)));
+    romLoaded = true;
+    draw();
+    status.textContent = "State loaded";
+  } catch (error) {
+    status.textContent = `Could not load the state: ${error}`;
+  }
+});
+
+// A ROM can be shared as a link: index.html?rom=<url of the ROM>
+const romUrl = new URLSearchParams(location.search).get("rom");
+if (romUrl) {
+  const response = await fetch(romUrl);
+  if (response.ok) {
+    loadRom(new Uint8Array(await response.arrayBuffer()), romUrl);
+  } else {
+    status.textContent = `Could not download ${romUrl}: ${response.status}`;
+  }
+}
+
+draw();
+requestAnimationFrame(loop);
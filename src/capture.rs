@@ -0,0 +1,343 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    chip8::Chip8,
+    constants::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    screen::DisplayOptions,
+};
+
+/// GIF delays are in hundredths of a second and most viewers ignore delays below 2,
+/// so every other 60 Hz frame is recorded and shown for 3/100 s (30 fps).
+const GIF_FRAME_INTERVAL: usize = 2;
+const GIF_FRAME_DELAY: u16 = 3;
+
+/// Saves screenshots and animated GIFs of the Chip-8 screen to a directory.
+/// Images use the current palette, at the native 64x32 resolution or at the window scale.
+pub struct Capture {
+    directory: PathBuf,
+    native_size: bool,
+    recording: Option<GifRecording>,
+}
+
+/// An animated GIF being written, one frame at a time.
+struct GifRecording {
+    path: PathBuf,
+    encoder: gif::Encoder<BufWriter<File>>,
+    frames_seen: usize,
+}
+
+impl Capture {
+    /// Creates a capture helper writing to `directory`, which is created on the first capture.
+    pub fn new(directory: PathBuf, native_size: bool) -> Self {
+        Self {
+            directory,
+            native_size,
+            recording: None,
+        }
+    }
+
+    /// Returns true while a GIF is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Saves the current screen as a PNG and returns its path.
+    pub fn save_screenshot(&self, chip8: &Chip8, options: &DisplayOptions) -> io::Result<PathBuf> {
+        let path = self.timestamped_path("screenshot", "png")?;
+        let scale = self.image_scale(options);
+        let (width, height) = (SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale);
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        for index in scaled_pixel_indexes(scale) {
+            let color = options.palette.color(chip8.screen[index]);
+            rgb.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(&path)?),
+            width as u32,
+            height as u32,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgb))
+            .map_err(io::Error::other)?;
+
+        Ok(path)
+    }
+
+    /// Starts recording a GIF, or stops and finishes the current one.
+    /// Returns the path of the file that was started or finished.
+    pub fn toggle_recording(&mut self, options: &DisplayOptions) -> io::Result<PathBuf> {
+        if let Some(recording) = self.recording.take() {
+            // Dropping the encoder writes the GIF trailer and flushes the file
+            drop(recording.encoder);
+            return Ok(recording.path);
+        }
+
+        let path = self.timestamped_path("recording", "gif")?;
+        let scale = self.image_scale(options);
+        let palette = options.palette;
+        let global_palette: Vec<u8> = [palette.background, palette.foreground]
+            .iter()
+            .flat_map(|color| [(color >> 16) as u8, (color >> 8) as u8, *color as u8])
+            .collect();
+
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(File::create(&path)?),
+            (SCREEN_WIDTH * scale) as u16,
+            (SCREEN_HEIGHT * scale) as u16,
+            &global_palette,
+        )
+        .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        self.recording = Some(GifRecording {
+            path: path.clone(),
+            encoder,
+            frames_seen: 0,
+        });
+        Ok(path)
+    }
+
+    /// Abandons the current recording, used after a write error.
+    pub fn stop_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// Adds the current screen to the GIF being recorded, if any. Call once per frame.
+    pub fn record_frame(&mut self, chip8: &Chip8, options: &DisplayOptions) -> io::Result<()> {
+        let scale = self.image_scale(options);
+        let Some(recording) = self.recording.as_mut() else {
+            return Ok(());
+        };
+
+        recording.frames_seen += 1;
+        if recording.frames_seen % GIF_FRAME_INTERVAL != 0 {
+            return Ok(());
+        }
+
+        // Index 0 is the background colour and 1 the foreground colour of the global palette
+        let pixels: Vec<u8> = scaled_pixel_indexes(scale)
+            .map(|index| u8::from(chip8.screen[index]))
+            .collect();
+        let mut frame = gif::Frame::from_indexed_pixels(
+            (SCREEN_WIDTH * scale) as u16,
+            (SCREEN_HEIGHT * scale) as u16,
+            pixels,
+            None,
+        );
+        frame.delay = GIF_FRAME_DELAY;
+        recording
+            .encoder
+            .write_frame(&frame)
+            .map_err(io::Error::other)
+    }
+
+    fn image_scale(&self, options: &DisplayOptions) -> usize {
+        if self.native_size { 1 } else { options.scale }
+    }
+
+    /// Builds a path such as `captures/screenshot-20250101-120000-123.png`.
+    fn timestamped_path(&self, prefix: &str, extension: &str) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+        Ok(
+            Path::new(&self.directory).join(format!(
+                "{}-{}.{}",
+                prefix,
+                utc_timestamp(),
+                extension
+            )),
+        )
+    }
+}
+
+/// Index in `chip8.screen` of every pixel of an image scaled by `scale`, row by row.
+fn scaled_pixel_indexes(scale: usize) -> impl Iterator<Item = usize> {
+    (0..SCREEN_HEIGHT * scale).flat_map(move |y| {
+        (0..SCREEN_WIDTH * scale).map(move |x| (y / scale) * SCREEN_WIDTH + x / scale)
+    })
+}
+
+/// Current UTC time formatted as `YYYYMMDD-HHMMSS-mmm`.
+fn utc_timestamp() -> String {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format_utc(elapsed)
+}
+
+/// Formats a time since the Unix epoch as `YYYYMMDD-HHMMSS-mmm`.
+fn format_utc(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Converts days since 1970-01-01 to a civil date (Howard Hinnant's civil_from_days)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::palette::Palette;
+
+    fn temp_capture_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()))
+    }
+
+    /// A screen with only the top-left and the bottom-right pixels on.
+    fn corner_pixels() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.screen[0] = true;
+        chip8.screen[SCREEN_WIDTH * SCREEN_HEIGHT - 1] = true;
+        chip8
+    }
+
+    fn options(scale: usize) -> DisplayOptions {
+        DisplayOptions {
+            scale,
+            palette: Palette {
+                foreground: 0x112233,
+                background: 0x445566,
+            },
+            ..DisplayOptions::default()
+        }
+    }
+
+    #[test]
+    fn timestamps_are_civil_utc_dates() {
+        let cases = [
+            (Duration::ZERO, "19700101-000000-000"),
+            (Duration::from_secs(951_782_400), "20000229-000000-000"),
+            (
+                Duration::from_millis(1_709_251_199_999),
+                "20240229-235959-999",
+            ),
+            (
+                Duration::from_millis(1_735_732_800_123),
+                "20250101-120000-123",
+            ),
+            (Duration::from_secs(4_107_542_399), "21000228-235959-000"),
+        ];
+
+        for (elapsed, expected) in cases {
+            assert_eq!(format_utc(elapsed), expected);
+        }
+        assert_eq!(utc_timestamp().len(), "YYYYMMDD-HHMMSS-mmm".len());
+    }
+
+    #[test]
+    fn scaled_pixels_repeat_each_screen_pixel() {
+        let native: Vec<usize> = scaled_pixel_indexes(1).collect();
+        assert_eq!(
+            native,
+            (0..SCREEN_WIDTH * SCREEN_HEIGHT).collect::<Vec<_>>()
+        );
+
+        let scaled: Vec<usize> = scaled_pixel_indexes(2).collect();
+        assert_eq!(scaled.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(&scaled[..4], &[0, 0, 1, 1]);
+        assert_eq!(
+            &scaled[SCREEN_WIDTH * 2..SCREEN_WIDTH * 2 + 4],
+            &[0, 0, 1, 1]
+        );
+        assert_eq!(
+            &scaled[SCREEN_WIDTH * 4..SCREEN_WIDTH * 4 + 2],
+            &[SCREEN_WIDTH, SCREEN_WIDTH]
+        );
+        assert_eq!(scaled.last(), Some(&(SCREEN_WIDTH * SCREEN_HEIGHT - 1)));
+    }
+
+    #[test]
+    fn screenshots_are_pngs_in_the_palette_colours() {
+        let directory = temp_capture_dir("screenshot");
+        let capture = Capture::new(directory.clone(), false);
+        let path = capture
+            .save_screenshot(&corner_pixels(), &options(2))
+            .unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgb = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgb).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!((info.width, info.height), (128, 64));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        let pixel = |x: usize, y: usize| &rgb[(y * 128 + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), &[0x11, 0x22, 0x33]);
+        assert_eq!(pixel(1, 1), &[0x11, 0x22, 0x33]);
+        assert_eq!(pixel(2, 0), &[0x44, 0x55, 0x66]);
+        assert_eq!(pixel(127, 63), &[0x11, 0x22, 0x33]);
+        assert_eq!(pixel(125, 63), &[0x44, 0x55, 0x66]);
+    }
+
+    #[test]
+    fn recordings_keep_every_other_frame() {
+        let directory = temp_capture_dir("recording");
+        let mut capture = Capture::new(directory.clone(), true);
+        let options = options(4);
+        let chip8 = corner_pixels();
+
+        capture.toggle_recording(&options).unwrap();
+        for _ in 0..5 {
+            capture.record_frame(&chip8, &options).unwrap();
+        }
+        let path = capture.toggle_recording(&options).unwrap();
+        assert!(!capture.is_recording());
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = decoder.read_info(File::open(&path).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (64, 32));
+        assert_eq!(
+            decoder.global_palette(),
+            Some(&[0x44, 0x55, 0x66, 0x11, 0x22, 0x33][..])
+        );
+
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, GIF_FRAME_DELAY);
+            assert_eq!(frame.buffer[0], 1);
+            assert_eq!(frame.buffer[1], 0);
+            assert_eq!(frame.buffer[SCREEN_WIDTH * SCREEN_HEIGHT - 1], 1);
+            frames += 1;
+        }
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(frames, 2);
+    }
+}
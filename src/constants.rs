@@ -1,38 +1,10 @@
 use minifb::Key;
 
-pub const CHIP8_RAM_MEMORY_SIZE: usize = 4096;
-pub const CHIP8_STACK_MEMORY_SIZE: usize = 16;
-pub const CHIP8_REGISTER_COUNT: usize = 16;
+pub use chip8_core::constants::*;
 
-pub const SCREEN_WIDTH: usize = 64;
-pub const SCREEN_HEIGHT: usize = 32;
-pub const SCREEN_SCALE_FACTOR: usize = 10;
-pub const START_RAM_ADDRESS: u16 = 0x200;
-pub const CLEANED_SCREEN: [bool; SCREEN_WIDTH * SCREEN_HEIGHT] =
-    [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-
-pub const FONT_SPRITES_SIZE: usize = 80;
-
-pub const FONT_SPRITES: [u8; FONT_SPRITES_SIZE] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-
-pub const KEYBOARD_KEYS_COUNT: usize = 16;
+pub const DEFAULT_SCREEN_SCALE_FACTOR: usize = 10;
+pub const DEFAULT_PHOSPHOR_DECAY: f32 = 0.6;
+pub const ROMS_DIRECTORY: &str = "files/roms";
 
 /// Mapping of physical keyboard keys to Chip-8 hexadecimal keypad codes.
 /// Keyboard     ->     Chip-8 Keyboard
@@ -40,6 +12,7 @@ pub const KEYBOARD_KEYS_COUNT: usize = 16;
 /// Q W E R      ->     4 5 6 D
 /// A S D F      ->     7 8 9 E
 /// Z X C V      ->     A 0 B F
+#[rustfmt::skip] // One group of rows per keypad row
 pub const KEYBOARD_CODES: [(Key, u8); KEYBOARD_KEYS_COUNT] = [
     (Key::Key1, 0x1),
     (Key::Key2, 0x2),
@@ -60,4 +33,13 @@ pub const KEYBOARD_CODES: [(Key, u8); KEYBOARD_KEYS_COUNT] = [
     (Key::X, 0x0),
     (Key::C, 0xB),
     (Key::V, 0xF)
-];
\ No newline at end of file
+];
+
+/// The same layout as `KEYBOARD_CODES` for the terminal frontend, which receives characters.
+#[rustfmt::skip] // One line per keypad row
+pub const TERMINAL_KEYBOARD_CODES: [(char, u8); KEYBOARD_KEYS_COUNT] = [
+    ('1', 0x1), ('2', 0x2), ('3', 0x3), ('4', 0xC),
+    ('q', 0x4), ('w', 0x5), ('e', 0x6), ('r', 0xD),
+    ('a', 0x7), ('s', 0x8), ('d', 0x9), ('f', 0xE),
+    ('z', 0xA), ('x', 0x0), ('c', 0xB), ('v', 0xF),
+];
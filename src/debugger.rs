@@ -0,0 +1,688 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    io::{self, BufRead, IsTerminal},
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    cheats::{Cheat, CheatSearch, Cheats, Comparison},
+    chip8::Chip8,
+    chip8_util::Chip8Util,
+    constants::CHIP8_RAM_MEMORY_SIZE,
+    disassembler::{self, Line},
+    memory_view,
+    palette::Palette,
+    sprite_viewer::{self, SpriteLayout},
+    symbols::Symbols,
+};
+
+/// Number of instructions shown by `list` without a count.
+const LIST_INSTRUCTIONS: usize = 10;
+
+/// Number of addresses printed by `find`.
+const FIND_RESULTS: usize = 32;
+
+/// Help of the debugger commands, printed by `help`.
+pub const HELP: &str = "\
+Commands:
+  step [n]              Execute n instructions (1 by default)
+  frame [n]             Run n frames of instructions and timer updates (1 by default)
+  continue              Run frames until an instruction with a breakpoint, Space stops
+  break <target>        Set a breakpoint at a label or hex address
+  delete <target>       Remove the breakpoint at a label or hex address
+  breaks                List the breakpoints
+  list [target] [n]     Disassemble n instructions (10 by default) from a label, a hex
+                        address or the PC
+  mem [target] [n]      Show n rows of 16 bytes (8 by default) from a label or hex address,
+                        or all 4 KiB, highlighting the font, ROM, I pointer and stack
+  poke <target> <bytes> Write hex bytes at a label or hex address
+  fill <start> <end> <byte>
+                        Write a hex byte from start to end included
+  find <bytes>          Find a sequence of hex bytes in memory, ?? matching any byte
+  sprite                Show the last sprite drawn, with its I address and height
+  sprites <start> <end> [height]
+                        Show memory from start to end included as sprites of 1 to 15 rows
+                        or 16x16, by default as high as the last sprite drawn
+  sheet <file.png> <start> <end> [height]
+                        Save the same sprites as a PNG sprite sheet
+  search                Start a cheat search with every address as a candidate
+  search <comparison>   Keep the candidates whose byte is: equal <value>, unchanged,
+                        changed, increased or decreased since the previous search step
+  cheat <addr> <bytes> [name]
+                        Write the hex bytes at the hex address before every step and frame
+  uncheat <addr>        Remove the cheat at the hex address
+  cheats                List the cheats
+  help                  Show this help";
+
+/// A command typed in the debugger console.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugCommand {
+    Step(usize),
+    Frame(usize),
+    Continue,
+    Break(String),
+    Delete(String),
+    Breaks,
+    List(Option<String>, usize),
+    Memory(Option<String>, Option<usize>),
+    Poke(String, Vec<u8>),
+    Fill(String, String, u8),
+    Find(Vec<Option<u8>>),
+    Sprite,
+    Sprites(String, String, Option<SpriteLayout>),
+    Sheet(PathBuf, String, String, Option<SpriteLayout>),
+    Search(Option<Comparison>),
+    Cheat(Cheat),
+    Uncheat(u16),
+    Cheats,
+    Help,
+}
+
+impl FromStr for DebugCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, arguments) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, rest)| (name, rest.trim()));
+        let count = || -> Result<usize, String> {
+            if arguments.is_empty() {
+                Ok(1)
+            } else {
+                arguments
+                    .parse()
+                    .map_err(|_| format!("invalid count '{}'", arguments))
+            }
+        };
+
+        match name {
+            "step" | "s" => Ok(DebugCommand::Step(count()?)),
+            "frame" | "f" => Ok(DebugCommand::Frame(count()?)),
+            "continue" | "c" => Ok(DebugCommand::Continue),
+            "break" | "b" if !arguments.is_empty() => Ok(DebugCommand::Break(arguments.into())),
+            "delete" | "d" if !arguments.is_empty() => Ok(DebugCommand::Delete(arguments.into())),
+            "break" | "b" | "delete" | "d" => Err(String::from("missing label or address")),
+            "breaks" => Ok(DebugCommand::Breaks),
+            "list" | "l" => {
+                let mut words = arguments.split_whitespace();
+                let target = words.next().map(String::from);
+                let count = match words.next() {
+                    Some(count) => count
+                        .parse()
+                        .map_err(|_| format!("invalid count '{}'", count))?,
+                    None => LIST_INSTRUCTIONS,
+                };
+                Ok(DebugCommand::List(target, count))
+            }
+            "search" if arguments.is_empty() => Ok(DebugCommand::Search(None)),
+            "search" => Ok(DebugCommand::Search(Some(arguments.parse()?))),
+            "mem" | "m" => {
+                let mut words = arguments.split_whitespace();
+                let target = words.next().map(String::from);
+                let rows = words
+                    .next()
+                    .map(|rows| {
+                        rows.parse()
+                            .map_err(|_| format!("invalid row count '{}'", rows))
+                    })
+                    .transpose()?;
+                Ok(DebugCommand::Memory(target, rows))
+            }
+            "poke" => {
+                let (target, bytes) = arguments
+                    .split_once(char::is_whitespace)
+                    .ok_or("expected poke <target> <bytes>")?;
+                let bytes = Chip8Util::parse_hex_bytes(bytes, false)?;
+                Ok(DebugCommand::Poke(
+                    target.into(),
+                    bytes.into_iter().flatten().collect(),
+                ))
+            }
+            "fill" => match arguments.split_whitespace().collect::<Vec<_>>()[..] {
+                [start, end, byte] => u8::from_str_radix(byte, 16)
+                    .map(|byte| DebugCommand::Fill(start.into(), end.into(), byte))
+                    .map_err(|_| format!("'{}' is not a hex byte", byte)),
+                _ => Err(String::from("expected fill <start> <end> <byte>")),
+            },
+            "find" => Ok(DebugCommand::Find(Chip8Util::parse_hex_bytes(
+                arguments, true,
+            )?)),
+            "sprite" => Ok(DebugCommand::Sprite),
+            "sprites" | "sheet" => {
+                let mut words: Vec<&str> = arguments.split_whitespace().collect();
+                let path = match name {
+                    "sheet" if !words.is_empty() => Some(PathBuf::from(words.remove(0))),
+                    _ => None,
+                };
+                let (start, end, layout) = match words[..] {
+                    [start, end] => (start, end, None),
+                    [start, end, layout] => (start, end, Some(layout.parse()?)),
+                    _ => return Err(format!("expected {} <start> <end> [height]", name)),
+                };
+                match path {
+                    Some(path) => Ok(DebugCommand::Sheet(path, start.into(), end.into(), layout)),
+                    None if name == "sheet" => Err(String::from("missing PNG file")),
+                    None => Ok(DebugCommand::Sprites(start.into(), end.into(), layout)),
+                }
+            }
+            "cheat" => Ok(DebugCommand::Cheat(arguments.parse()?)),
+            "uncheat" => u16::from_str_radix(arguments, 16)
+                .map(DebugCommand::Uncheat)
+                .map_err(|_| format!("invalid address '{}'", arguments)),
+            "cheats" => Ok(DebugCommand::Cheats),
+            "help" | "?" => Ok(DebugCommand::Help),
+            _ => Err(format!("unknown command '{}', type help", name)),
+        }
+    }
+}
+
+/// State of a debugging session kept between commands.
+pub struct Debugger {
+    pub cheats: Cheats,
+    pub rom_length: usize, // Bytes of the ROM, highlighted by the memory view
+    pub palette: Palette,  // Colours of the sprite sheets
+    search: Option<CheatSearch>,
+    instructions_per_frame: usize,
+    breakpoints: BTreeSet<u16>,
+    running: bool,
+    resuming: bool, // The first instruction run by `continue` does not stop at its breakpoint
+}
+
+impl Debugger {
+    pub fn new(cheats: Cheats, instructions_per_frame: usize) -> Self {
+        Self {
+            cheats,
+            rom_length: 0,
+            palette: Palette::CLASSIC,
+            search: None,
+            instructions_per_frame,
+            breakpoints: BTreeSet::new(),
+            running: false,
+            resuming: false,
+        }
+    }
+
+    /// Whether `continue` is running frames, see [`Debugger::run_frame`].
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Stops running frames, returning where the program stopped.
+    pub fn stop(&mut self, chip8: &Chip8) -> String {
+        self.running = false;
+        format!("Stopped at {}", position(chip8))
+    }
+
+    /// Runs the next frame of `continue`, without tracing. Stops before an instruction with a
+    /// breakpoint, in which case the timers are not updated and where it stopped is returned.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Option<String> {
+        let debug_mode = std::mem::replace(&mut chip8.debug_mode, false);
+        self.cheats.apply(chip8);
+        let mut hit = false;
+        for _ in 0..self.instructions_per_frame {
+            if !std::mem::take(&mut self.resuming) && self.breakpoints.contains(&chip8.pc) {
+                hit = true;
+                break;
+            }
+            chip8.tick();
+            chip8.instructions_executed += 1;
+        }
+        chip8.debug_mode = debug_mode;
+
+        if hit {
+            self.running = false;
+            Some(format!("Breakpoint at {}", position(chip8)))
+        } else {
+            chip8.update_timers();
+            None
+        }
+    }
+
+    /// Executes one instruction, with the cheats applied first.
+    pub fn step(&self, chip8: &mut Chip8) {
+        self.cheats.apply(chip8);
+        chip8.tick();
+    }
+
+    /// Runs a command and returns what it prints.
+    pub fn execute(&mut self, chip8: &mut Chip8, command: DebugCommand) -> String {
+        match command {
+            DebugCommand::Step(count) => {
+                for _ in 0..count {
+                    self.step(chip8);
+                }
+                position(chip8)
+            }
+            DebugCommand::Frame(count) => {
+                // Tracing every instruction of whole frames would flood the console
+                let debug_mode = std::mem::replace(&mut chip8.debug_mode, false);
+                for _ in 0..count {
+                    self.cheats.apply(chip8);
+                    chip8.run_frame(self.instructions_per_frame);
+                }
+                chip8.debug_mode = debug_mode;
+                chip8.instructions_executed += count * self.instructions_per_frame;
+                format!("Ran {} frames, {}", count, position(chip8))
+            }
+            DebugCommand::Continue => {
+                self.running = true;
+                self.resuming = true;
+                String::from("Running until a breakpoint, Space stops")
+            }
+            DebugCommand::Break(target) => match resolve(chip8, &target) {
+                Ok(address) => {
+                    self.breakpoints.insert(address);
+                    format!("Breakpoint at {}", describe(chip8, address))
+                }
+                Err(e) => e,
+            },
+            DebugCommand::Delete(target) => match resolve(chip8, &target) {
+                Ok(address) if self.breakpoints.remove(&address) => {
+                    format!("Removed the breakpoint at {}", describe(chip8, address))
+                }
+                Ok(address) => format!("No breakpoint at {}", describe(chip8, address)),
+                Err(e) => e,
+            },
+            DebugCommand::Breaks if self.breakpoints.is_empty() => String::from("No breakpoints"),
+            DebugCommand::Breaks => self
+                .breakpoints
+                .iter()
+                .map(|&address| describe(chip8, address))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            DebugCommand::List(target, count) => {
+                let start = match target.map(|target| resolve(chip8, &target)) {
+                    Some(Ok(address)) => address,
+                    Some(Err(e)) => return e,
+                    None => chip8.pc,
+                };
+                self.listing(chip8, start, count)
+            }
+            DebugCommand::Memory(target, rows) => {
+                let (address, rows) = match target.map(|target| resolve(chip8, &target)) {
+                    Some(Ok(address)) => (address, rows.unwrap_or(8)),
+                    Some(Err(e)) => return e,
+                    None => (0, CHIP8_RAM_MEMORY_SIZE / memory_view::BYTES_PER_ROW),
+                };
+                let colour = io::stdout().is_terminal();
+                memory_view::render(chip8, self.rom_length, address, rows, colour)
+            }
+            DebugCommand::Poke(target, bytes) => match resolve(chip8, &target) {
+                Ok(address) if address as usize + bytes.len() <= CHIP8_RAM_MEMORY_SIZE => {
+                    let start = address as usize;
+                    chip8.ram[start..start + bytes.len()].copy_from_slice(&bytes);
+                    format!(
+                        "Wrote {} bytes at {}",
+                        bytes.len(),
+                        describe(chip8, address)
+                    )
+                }
+                Ok(address) => {
+                    format!("{} bytes at {:03X} do not fit in RAM", bytes.len(), address)
+                }
+                Err(e) => e,
+            },
+            DebugCommand::Fill(start, end, byte) => {
+                match (resolve(chip8, &start), resolve(chip8, &end)) {
+                    (Ok(start), Ok(end)) if start <= end => {
+                        chip8.ram[start as usize..=end as usize].fill(byte);
+                        format!("Filled {:03X}-{:03X} with {:02X}", start, end, byte)
+                    }
+                    (Ok(start), Ok(end)) => format!("{:03X} is after {:03X}", start, end),
+                    (Err(e), _) | (_, Err(e)) => e,
+                }
+            }
+            DebugCommand::Find(pattern) => {
+                let matches = memory_view::find(&chip8.ram, &pattern);
+                let shown: Vec<String> = matches
+                    .iter()
+                    .take(FIND_RESULTS)
+                    .map(|address| format!("{:03X}", address))
+                    .collect();
+                match matches.len() {
+                    0 => String::from("Not found"),
+                    1 => format!("1 match: {}", describe(chip8, matches[0])),
+                    count if count > FIND_RESULTS => {
+                        format!("{} matches: {} ...", count, shown.join(" "))
+                    }
+                    count => format!("{} matches: {}", count, shown.join(" ")),
+                }
+            }
+            DebugCommand::Search(None) => {
+                let search = CheatSearch::start(chip8);
+                let summary = search.to_string();
+                self.search = Some(search);
+                summary
+            }
+            DebugCommand::Search(Some(comparison)) => match self.search.as_mut() {
+                Some(search) => {
+                    search.narrow(chip8, comparison);
+                    search.to_string()
+                }
+                None => String::from("No search in progress, start one with search"),
+            },
+            DebugCommand::Sprite => match chip8.last_sprite {
+                Some(sprite) => {
+                    let layout = SpriteLayout::Chip8 {
+                        height: (sprite.height as usize).max(1),
+                    };
+                    // Past 0xFFF when Dxyn wrapped around to the start of memory
+                    let end = sprite.address + layout.bytes() as u16 - 1;
+                    let sprites = sprite_viewer::decode(&chip8.ram, sprite.address, end, layout);
+                    format!(
+                        "I={} with {} rows, drawn at ({}, {})\n{}",
+                        describe(chip8, sprite.address),
+                        sprite.height,
+                        sprite.x,
+                        sprite.y,
+                        sprite_viewer::render_text(&sprites, layout, None)
+                    )
+                }
+                None => String::from("No sprite drawn yet"),
+            },
+            DebugCommand::Sprites(start, end, layout) => {
+                match sprites_between(chip8, &start, &end, layout) {
+                    Ok((sprites, layout)) => {
+                        let highlighted = chip8.last_sprite.map(|sprite| sprite.address);
+                        sprite_viewer::render_text(&sprites, layout, highlighted)
+                    }
+                    Err(e) => e,
+                }
+            }
+            DebugCommand::Sheet(path, start, end, layout) => {
+                match sprites_between(chip8, &start, &end, layout) {
+                    Ok((sprites, layout)) => {
+                        let highlighted = chip8.last_sprite.map(|sprite| sprite.address);
+                        match sprite_viewer::save_sheet(
+                            &path,
+                            &sprites,
+                            layout,
+                            &self.palette,
+                            highlighted,
+                        ) {
+                            Ok(()) => {
+                                format!("Saved {} sprites to {}", sprites.len(), path.display())
+                            }
+                            Err(e) => format!("Could not save {}: {}", path.display(), e),
+                        }
+                    }
+                    Err(e) => e,
+                }
+            }
+            DebugCommand::Cheat(cheat) => {
+                let summary = format!("Cheat {}", cheat);
+                self.cheats.add(cheat);
+                self.cheats.apply(chip8);
+                summary
+            }
+            DebugCommand::Uncheat(address) => {
+                if self.cheats.remove(address) {
+                    format!("Removed the cheat at {:03X}", address)
+                } else {
+                    format!("No cheat at {:03X}", address)
+                }
+            }
+            DebugCommand::Cheats if self.cheats.cheats.is_empty() => String::from("No cheats"),
+            DebugCommand::Cheats => self
+                .cheats
+                .cheats
+                .iter()
+                .map(Cheat::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            DebugCommand::Help => String::from(HELP),
+        }
+    }
+
+    /// `count` instructions from `start`, with the labels, source lines and breakpoints.
+    /// `>` marks the program counter and `*` the breakpoints.
+    fn listing(&self, chip8: &Chip8, start: u16, count: usize) -> String {
+        let end = (start as usize + 2 * count).min(CHIP8_RAM_MEMORY_SIZE) as u16;
+        let mut listing = String::new();
+        for line in disassembler::listing(&chip8.ram, start, end, |_| false) {
+            let Line::Instruction { address, op_code } = line else {
+                continue;
+            };
+            let symbols = chip8.symbols();
+            if let Some(label) = symbols.and_then(|symbols| symbols.label(address)) {
+                writeln!(listing, "{}:", label).unwrap();
+            }
+            let marker = match (address == chip8.pc, self.breakpoints.contains(&address)) {
+                (true, _) => '>',
+                (false, true) => '*',
+                (false, false) => ' ',
+            };
+            let assembly = match symbols {
+                Some(symbols) => symbols.disassemble(op_code),
+                None => disassembler::disassemble(op_code),
+            };
+            let mut line = format!(
+                "{} {:03X}  {:04X}  {:<20}",
+                marker, address, op_code, assembly
+            );
+            if let Some(source) = symbols.and_then(|symbols| symbols.source_line(address)) {
+                write!(line, "; {}:{}", source.file, source.line).unwrap();
+            }
+            writeln!(listing, "{}", line.trim_end()).unwrap();
+        }
+        listing.trim_end().to_string()
+    }
+}
+
+/// The program counter, with its label and source line when the program has symbols.
+fn position(chip8: &Chip8) -> String {
+    match chip8.symbols() {
+        Some(symbols) => format!("PC={:03X} {}", chip8.pc, symbols.describe(chip8.pc)),
+        None => format!("PC={:03X}", chip8.pc),
+    }
+}
+
+/// An address in hex, with its label and source line when the program has symbols.
+fn describe(chip8: &Chip8, address: u16) -> String {
+    match chip8.symbols() {
+        Some(symbols) => format!("{:03X} {}", address, symbols.describe(address)),
+        None => format!("{:03X}", address),
+    }
+}
+
+/// The sprites from the `start` target to the `end` one included, as high as the last sprite
+/// drawn when no layout is given, or 8 rows.
+fn sprites_between(
+    chip8: &Chip8,
+    start: &str,
+    end: &str,
+    layout: Option<SpriteLayout>,
+) -> Result<(Vec<sprite_viewer::Sprite>, SpriteLayout), String> {
+    let (start, end) = (resolve(chip8, start)?, resolve(chip8, end)?);
+    if start > end {
+        return Err(format!("{:03X} is after {:03X}", start, end));
+    }
+    let layout = layout.unwrap_or(SpriteLayout::Chip8 {
+        height: chip8
+            .last_sprite
+            .map_or(8, |sprite| (sprite.height as usize).max(1)),
+    });
+    Ok((
+        sprite_viewer::decode(&chip8.ram, start, end, layout),
+        layout,
+    ))
+}
+
+/// The address of a label of the program, or of a hex address.
+fn resolve(chip8: &Chip8, target: &str) -> Result<u16, String> {
+    match chip8.symbols() {
+        Some(symbols) => symbols.resolve(target),
+        None => Symbols::default().resolve(target),
+    }
+}
+
+/// Lines typed on stdin, read on a separate thread so the debugger window keeps running.
+pub struct DebugConsole {
+    lines: Receiver<String>,
+}
+
+impl Default for DebugConsole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugConsole {
+    pub fn new() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { lines }
+    }
+
+    /// The next line typed, if any, without waiting.
+    pub fn poll_line(&self) -> Option<String> {
+        self.lines.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!("step".parse(), Ok(DebugCommand::Step(1)));
+        assert_eq!("frame 30".parse(), Ok(DebugCommand::Frame(30)));
+        assert_eq!(
+            "search equal 3".parse(),
+            Ok(DebugCommand::Search(Some(Comparison::Equal(3))))
+        );
+        assert_eq!("uncheat 2F3".parse(), Ok(DebugCommand::Uncheat(0x2F3)));
+        assert_eq!(
+            "fill 300 30F ff".parse(),
+            Ok(DebugCommand::Fill("300".into(), "30F".into(), 0xFF))
+        );
+        assert_eq!(
+            "find A2 ??".parse(),
+            Ok(DebugCommand::Find(vec![Some(0xA2), None]))
+        );
+        assert!("poke 300".parse::<DebugCommand>().is_err());
+        assert!("step many".parse::<DebugCommand>().is_err());
+        assert!("jump".parse::<DebugCommand>().is_err());
+    }
+
+    #[test]
+    fn searches_find_a_counter_that_cheats_then_freeze() {
+        // 7001: ADD V0, 1, A300: LD I, 0x300, F055: LD [I], V0, 1200: JP 0x200
+        let mut chip8 = Chip8::start(vec![0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00]);
+        let mut debugger = Debugger::new(Cheats::default(), 4);
+        let run = |debugger: &mut Debugger, chip8: &mut Chip8, command: &str| {
+            debugger.execute(chip8, command.parse().unwrap())
+        };
+
+        run(&mut debugger, &mut chip8, "search");
+        run(&mut debugger, &mut chip8, "frame");
+        run(&mut debugger, &mut chip8, "search increased");
+        run(&mut debugger, &mut chip8, "frame");
+        assert_eq!(
+            run(&mut debugger, &mut chip8, "search equal 2"),
+            "1 candidate 300=02"
+        );
+
+        run(&mut debugger, &mut chip8, "cheat 300 63");
+        // The fourth step writes the cheat over the value stored by the third one
+        run(&mut debugger, &mut chip8, "step 4");
+        assert_eq!(chip8.ram[0x300], 0x63);
+        assert_eq!(run(&mut debugger, &mut chip8, "cheats"), "300 63");
+    }
+
+    #[test]
+    fn memory_is_edited_and_searched_by_label() {
+        let mut chip8 = Chip8::start(vec![0x12, 0x00]);
+        chip8.set_symbols(Some(Symbols::parse("300 score").unwrap()));
+        let mut debugger = Debugger::new(Cheats::default(), 10);
+        let mut run = |command: &str| debugger.execute(&mut chip8, command.parse().unwrap());
+
+        assert_eq!(run("poke score 12 34"), "Wrote 2 bytes at 300 score");
+        assert_eq!(run("fill 302 30F 12"), "Filled 302-30F with 12");
+        assert_eq!(run("find 34 12 12"), "1 match: 301 score+1");
+        assert_eq!(run("find 12 ??").split(':').next(), Some("16 matches"));
+        assert_eq!(run("poke FFF 12 34"), "2 bytes at FFF do not fit in RAM");
+        assert_eq!(run("fill 30F 300 00"), "30F is after 300");
+    }
+
+    #[test]
+    fn sprites_are_shown_from_the_last_one_drawn() {
+        // A20A: LD I, 0x20A, D012: DRW V0, V1, 2, 1204: JP 0x204, then 2 rows of sprite data
+        let mut chip8 = Chip8::start(vec![
+            0xA2, 0x0A, 0xD0, 0x12, 0x12, 0x04, 0x00, 0x00, 0x00, 0x00, 0x3C, 0x42,
+        ]);
+        let mut debugger = Debugger::new(Cheats::default(), 10);
+        let mut run = |command: &str| debugger.execute(&mut chip8, command.parse().unwrap());
+
+        assert_eq!(run("sprite"), "No sprite drawn yet");
+        run("step 2");
+        assert_eq!(
+            run("sprite"),
+            "I=20A with 2 rows, drawn at (0, 0)\n20A\n..####..\n.#....#."
+        );
+        assert_eq!(
+            run("sprites 208 20B"),
+            "208       20A*\n\
+             ........  ..####..\n\
+             ........  .#....#."
+        );
+        assert!("sheet out.png 200".parse::<DebugCommand>().is_err());
+        assert!("sprites 200 20F 16".parse::<DebugCommand>().is_err());
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoints_set_by_label() {
+        // main: 6005: LD V0, 5, 2206: CALL add, 1202: JP 0x202, add: 7001: ADD V0, 1, 00EE: RET
+        let mut chip8 = Chip8::start(vec![
+            0x60, 0x05, 0x22, 0x06, 0x12, 0x02, 0x70, 0x01, 0x00, 0xEE,
+        ]);
+        let mut symbols = Symbols::parse("200 main\n206 add\n206 game.8o:9").unwrap();
+        symbols.add_source("game.8o", &format!("{}  v0 += 1\n", "\n".repeat(8)));
+        chip8.set_symbols(Some(symbols));
+        let mut debugger = Debugger::new(Cheats::default(), 10);
+        let run = |debugger: &mut Debugger, chip8: &mut Chip8, command: &str| {
+            debugger.execute(chip8, command.parse().unwrap())
+        };
+
+        assert_eq!(
+            run(&mut debugger, &mut chip8, "break add"),
+            "Breakpoint at 206 add (game.8o:9: v0 += 1)"
+        );
+        run(&mut debugger, &mut chip8, "continue");
+        assert_eq!(
+            debugger.run_frame(&mut chip8),
+            Some(String::from(
+                "Breakpoint at PC=206 add (game.8o:9: v0 += 1)"
+            ))
+        );
+        assert!(!debugger.is_running());
+
+        // Continuing leaves the breakpoint, then comes back to it on the next call
+        run(&mut debugger, &mut chip8, "continue");
+        assert!(debugger.run_frame(&mut chip8).is_some());
+        assert_eq!(chip8.v_registers[0], 6);
+        assert_eq!(run(&mut debugger, &mut chip8, "step"), "PC=208 add+2");
+
+        assert_eq!(
+            run(&mut debugger, &mut chip8, "list main 4"),
+            "main:\n  \
+             200  6005  LD V0, 0x05\n  \
+             202  2206  CALL add\n  \
+             204  1202  JP 0x202\n\
+             add:\n* \
+             206  7001  ADD V0, 0x01        ; game.8o:9"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::str::FromStr;
+
+/// A post-processing effect applied in software to the scaled `0x00RRGGBB` framebuffer.
+pub trait Filter {
+    /// Applies the effect in place. `scale` is the size in host pixels of each Chip-8 pixel.
+    fn apply(&self, buffer: &mut [u32], width: usize, height: usize, scale: usize);
+}
+
+/// Filters that can be selected from the command line with `--filter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    Scanlines,
+    Grid,
+    Bloom,
+    Curvature,
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "scanlines" => Ok(FilterKind::Scanlines),
+            "grid" => Ok(FilterKind::Grid),
+            "bloom" => Ok(FilterKind::Bloom),
+            "curvature" => Ok(FilterKind::Curvature),
+            _ => Err(format!(
+                "unknown filter '{}', use scanlines, grid, bloom or curvature",
+                value
+            )),
+        }
+    }
+}
+
+impl FilterKind {
+    /// Creates the filter with its default settings.
+    fn build(self) -> Box<dyn Filter> {
+        match self {
+            FilterKind::Scanlines => Box::new(Scanlines { darkness: 0.5 }),
+            FilterKind::Grid => Box::new(PixelGrid { darkness: 0.35 }),
+            FilterKind::Bloom => Box::new(Bloom { strength: 0.6 }),
+            FilterKind::Curvature => Box::new(Curvature { amount: 0.08 }),
+        }
+    }
+}
+
+/// Runs a list of filters, in order, on a copy of the rendered screen.
+/// The source buffer is left untouched, so it can be filtered again without the effects stacking up.
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+    output: Vec<u32>,
+}
+
+impl FilterChain {
+    /// Builds the chain for the filters selected on the command line.
+    pub fn new(kinds: &[FilterKind]) -> Self {
+        Self {
+            filters: kinds.iter().map(|kind| kind.build()).collect(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Returns true when no filter was selected.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Copies `input` into the output buffer and runs every filter on it.
+    pub fn apply(&mut self, input: &[u32], width: usize, height: usize, scale: usize) {
+        self.output.clear();
+        self.output.extend_from_slice(input);
+        for filter in &self.filters {
+            filter.apply(&mut self.output, width, height, scale);
+        }
+    }
+
+    /// The last filtered frame.
+    pub fn output(&self) -> &[u32] {
+        &self.output
+    }
+}
+
+/// Darkens every other row of host pixels, imitating the gaps between the lines of a CRT.
+pub struct Scanlines {
+    pub darkness: f32, // 0.0 leaves the rows untouched, 1.0 turns them black
+}
+
+impl Filter for Scanlines {
+    fn apply(&self, buffer: &mut [u32], width: usize, height: usize, _scale: usize) {
+        for y in (1..height).step_by(2) {
+            for color in &mut buffer[y * width..(y + 1) * width] {
+                *color = scale_color(*color, 1.0 - self.darkness);
+            }
+        }
+    }
+}
+
+/// Darkens the right column and bottom row of every Chip-8 pixel, like the grid of an LCD.
+/// Does nothing when each Chip-8 pixel is a single host pixel.
+pub struct PixelGrid {
+    pub darkness: f32,
+}
+
+impl Filter for PixelGrid {
+    fn apply(&self, buffer: &mut [u32], width: usize, height: usize, scale: usize) {
+        if scale < 2 {
+            return;
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                if x % scale == scale - 1 || y % scale == scale - 1 {
+                    let index = y * width + x;
+                    buffer[index] = scale_color(buffer[index], 1.0 - self.darkness);
+                }
+            }
+        }
+    }
+}
+
+/// Adds a blurred copy of the image on top of itself so lit pixels glow onto their neighbours.
+/// The blur radius is half a Chip-8 pixel.
+pub struct Bloom {
+    pub strength: f32,
+}
+
+impl Filter for Bloom {
+    fn apply(&self, buffer: &mut [u32], width: usize, height: usize, scale: usize) {
+        let radius = (scale / 2).max(1);
+        let blurred = box_blur(buffer, width, height, radius);
+        for (color, glow) in buffer.iter_mut().zip(blurred) {
+            *color = add_colors(*color, scale_color(glow, self.strength));
+        }
+    }
+}
+
+/// Bends the image like the convex glass of a CRT. The corners that fall outside the
+/// source image become black.
+pub struct Curvature {
+    pub amount: f32, // How much the edges are pushed outwards, 0.0 keeps the image flat
+}
+
+impl Filter for Curvature {
+    fn apply(&self, buffer: &mut [u32], width: usize, height: usize, _scale: usize) {
+        let source = buffer.to_vec();
+        for y in 0..height {
+            for x in 0..width {
+                // Coordinates of the pixel centre from -1.0 to 1.0, with 0.0 in the middle of the screen
+                let nx = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+                let ny = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+                let distortion = 1.0 + self.amount * (nx * nx + ny * ny);
+                let (sx, sy) = (nx * distortion, ny * distortion);
+
+                buffer[y * width + x] = if sx.abs() > 1.0 || sy.abs() > 1.0 {
+                    0x000000
+                } else {
+                    let source_x = (((sx + 1.0) / 2.0 * width as f32) as usize).min(width - 1);
+                    let source_y = (((sy + 1.0) / 2.0 * height as f32) as usize).min(height - 1);
+                    source[source_y * width + source_x]
+                };
+            }
+        }
+    }
+}
+
+/// Multiplies each colour channel by `factor`.
+fn scale_color(color: u32, factor: f32) -> u32 {
+    let mut result = 0;
+    for shift in [16, 8, 0] {
+        let channel = ((color >> shift) & 0xFF) as f32 * factor;
+        result |= (channel.round().clamp(0.0, 255.0) as u32) << shift;
+    }
+    result
+}
+
+/// Adds two colours channel by channel, saturating at 0xFF.
+fn add_colors(first: u32, second: u32) -> u32 {
+    let mut result = 0;
+    for shift in [16, 8, 0] {
+        let channel = ((first >> shift) & 0xFF) + ((second >> shift) & 0xFF);
+        result |= channel.min(0xFF) << shift;
+    }
+    result
+}
+
+/// Averages every pixel with its neighbours in a square of side `2 * radius + 1`,
+/// one pass per axis using running sums.
+fn box_blur(buffer: &[u32], width: usize, height: usize, radius: usize) -> Vec<u32> {
+    let horizontal = blur_pass(buffer, width, height, radius, 1, width);
+    blur_pass(&horizontal, height, width, radius, width, 1)
+}
+
+/// Blurs `lines` lines of `length` pixels each. `step` is the distance between two pixels
+/// of a line and `line_step` the distance between the first pixels of two lines.
+fn blur_pass(
+    buffer: &[u32],
+    length: usize,
+    lines: usize,
+    radius: usize,
+    step: usize,
+    line_step: usize,
+) -> Vec<u32> {
+    let mut result = vec![0; buffer.len()];
+    let window_size = (2 * radius + 1) as u32;
+
+    for line in 0..lines {
+        let start = line * line_step;
+        let pixel = |position: isize| -> u32 {
+            let position = position.clamp(0, length as isize - 1) as usize;
+            buffer[start + position * step]
+        };
+
+        let mut sums = [0u32; 3];
+        for position in -(radius as isize)..=radius as isize {
+            let color = pixel(position);
+            for (channel, shift) in [16, 8, 0].iter().enumerate() {
+                sums[channel] += (color >> shift) & 0xFF;
+            }
+        }
+
+        for position in 0..length {
+            let mut color = 0;
+            for (channel, shift) in [16, 8, 0].iter().enumerate() {
+                color |= (sums[channel] / window_size) << shift;
+            }
+            result[start + position * step] = color;
+
+            let leaving = pixel(position as isize - radius as isize);
+            let entering = pixel(position as isize + radius as isize + 1);
+            for (channel, shift) in [16, 8, 0].iter().enumerate() {
+                sums[channel] += (entering >> shift) & 0xFF;
+                sums[channel] -= (leaving >> shift) & 0xFF;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u32 = 0xFFFFFF;
+    const BLACK: u32 = 0x000000;
+
+    #[test]
+    fn scanlines_darken_odd_rows_only() {
+        let mut buffer = vec![WHITE; 3 * 4];
+        Scanlines { darkness: 0.5 }.apply(&mut buffer, 3, 4, 1);
+
+        for y in 0..4 {
+            let expected = if y % 2 == 1 { 0x808080 } else { WHITE };
+            assert_eq!(&buffer[y * 3..(y + 1) * 3], &[expected; 3]);
+        }
+    }
+
+    #[test]
+    fn pixel_grid_darkens_the_edges_of_each_chip8_pixel() {
+        let mut buffer = vec![WHITE; 4 * 4];
+        PixelGrid { darkness: 1.0 }.apply(&mut buffer, 4, 4, 2);
+
+        #[rustfmt::skip]
+        let expected = vec![
+            WHITE, BLACK, WHITE, BLACK,
+            BLACK, BLACK, BLACK, BLACK,
+            WHITE, BLACK, WHITE, BLACK,
+            BLACK, BLACK, BLACK, BLACK,
+        ];
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn pixel_grid_does_nothing_without_scaling() {
+        let mut buffer = vec![WHITE; 4];
+        PixelGrid { darkness: 1.0 }.apply(&mut buffer, 2, 2, 1);
+        assert_eq!(buffer, vec![WHITE; 4]);
+    }
+
+    #[test]
+    fn bloom_spreads_light_to_neighbours() {
+        let mut buffer = vec![BLACK; 5 * 5];
+        buffer[2 * 5 + 2] = WHITE;
+        Bloom { strength: 1.0 }.apply(&mut buffer, 5, 5, 2);
+
+        assert_eq!(buffer[2 * 5 + 2], WHITE);
+        assert_ne!(buffer[2 * 5 + 1], BLACK);
+        assert_ne!(buffer[5 + 1], BLACK);
+        assert_eq!(buffer[0], BLACK);
+    }
+
+    #[test]
+    fn bloom_keeps_a_uniform_image_unchanged() {
+        let mut buffer = vec![0x202020; 4 * 3];
+        Bloom { strength: 0.0 }.apply(&mut buffer, 4, 3, 2);
+        assert_eq!(buffer, vec![0x202020; 4 * 3]);
+    }
+
+    #[test]
+    fn curvature_keeps_the_centre_and_blanks_the_corners() {
+        let mut buffer = vec![WHITE; 9 * 9];
+        buffer[4 * 9 + 4] = 0x123456;
+        Curvature { amount: 0.5 }.apply(&mut buffer, 9, 9, 1);
+
+        assert_eq!(buffer[4 * 9 + 4], 0x123456);
+        assert_eq!(buffer[0], BLACK);
+        assert_eq!(buffer[9 * 9 - 1], BLACK);
+    }
+
+    #[test]
+    fn curvature_of_zero_is_the_identity() {
+        let buffer: Vec<u32> = (0..6 * 4).collect();
+        let mut filtered = buffer.clone();
+        Curvature { amount: 0.0 }.apply(&mut filtered, 6, 4, 1);
+        assert_eq!(filtered, buffer);
+    }
+
+    #[test]
+    fn chain_leaves_the_input_untouched() {
+        let input = vec![WHITE; 2 * 2];
+        let mut chain = FilterChain::new(&[FilterKind::Scanlines]);
+        chain.apply(&input, 2, 2, 1);
+
+        assert_eq!(input, vec![WHITE; 4]);
+        assert_eq!(chain.output(), &[WHITE, WHITE, 0x808080, 0x808080]);
+    }
+
+    #[test]
+    fn empty_chain_copies_the_input() {
+        let input = vec![1, 2, 3, 4];
+        let mut chain = FilterChain::new(&[]);
+        chain.apply(&input, 2, 2, 1);
+        assert_eq!(chain.output(), input.as_slice());
+    }
+
+    #[test]
+    fn filter_names_are_parsed() {
+        assert_eq!("Bloom".parse::<FilterKind>(), Ok(FilterKind::Bloom));
+        assert!("sepia".parse::<FilterKind>().is_err());
+    }
+}
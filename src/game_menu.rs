@@ -54,4 +54,4 @@ pub fn check_key_input() -> Option<String> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,277 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{cheats::Cheat, constants::KEYBOARD_KEYS_COUNT};
+
+/// The keys pressed during a session, stored as the frames where the set of pressed keys changes,
+/// with the cheats of the session and the frames where they were enabled or disabled.
+///
+/// The text format has a `seed` line with the random seed of the session, a `speed` line with
+/// the instructions run per frame and an `idle_skip on|off` line telling whether frames ended
+/// early at waits, as that shifts when waits end. The `speed` and `idle_skip` lines are required.
+/// Then come a `cheat <address> <bytes> [name]` line per cheat and one `<frame> <keys>` line per
+/// change, where `<keys>` lists the pressed keys as hex digits or is `-` when no key is pressed.
+/// `<frame> cheats on|off` lines enable or disable the cheats from that frame on; they are
+/// enabled at the start. Lines starting with `#` are comments:
+///
+/// ```text
+/// # chip8 input log
+/// seed 42
+/// speed 10
+/// idle_skip on
+/// cheat 2F3 09 left score
+/// 0 -
+/// 120 5
+/// 130 56
+/// 140 -
+/// 200 cheats off
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputLog {
+    pub seed: u64,
+    pub speed: usize,
+    pub idle_skip: bool,
+    pub cheats: Vec<Cheat>,
+    pub events: Vec<(usize, [bool; KEYBOARD_KEYS_COUNT])>, // (frame, keys held from that frame on), sorted by frame
+    pub cheat_toggles: Vec<(usize, bool)>, // (frame, cheats enabled from that frame on), sorted by frame
+}
+
+impl InputLog {
+    /// Reads an input log from a file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Parses the text format described on [`InputLog`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut log = Self::default();
+        let mut speed = None;
+        let mut idle_skip = None;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: &str| format!("line {}: {}", line_number + 1, message);
+            let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            if first == "seed" {
+                log.seed = rest.parse().map_err(|_| error("invalid seed"))?;
+                continue;
+            }
+            if first == "speed" {
+                speed = Some(
+                    rest.parse()
+                        .ok()
+                        .filter(|&speed| speed > 0)
+                        .ok_or_else(|| error("invalid speed"))?,
+                );
+                continue;
+            }
+            if first == "idle_skip" {
+                idle_skip =
+                    Some(parse_on_off(rest).ok_or_else(|| error("idle_skip must be on or off"))?);
+                continue;
+            }
+            if first == "cheat" {
+                log.cheats
+                    .push(rest.parse().map_err(|e: String| error(&e))?);
+                continue;
+            }
+
+            let frame: usize = first.parse().map_err(|_| error("invalid frame number"))?;
+            if let Some(state) = rest.strip_prefix("cheats") {
+                let enabled =
+                    parse_on_off(state.trim()).ok_or_else(|| error("cheats must be on or off"))?;
+                if log
+                    .cheat_toggles
+                    .last()
+                    .is_some_and(|(last_frame, _)| *last_frame >= frame)
+                {
+                    return Err(error("frames must be in increasing order"));
+                }
+                log.cheat_toggles.push((frame, enabled));
+                continue;
+            }
+            if log
+                .events
+                .last()
+                .is_some_and(|(last_frame, _)| *last_frame >= frame)
+            {
+                return Err(error("frames must be in increasing order"));
+            }
+
+            let mut keys = [false; KEYBOARD_KEYS_COUNT];
+            if rest != "-" {
+                for digit in rest.chars() {
+                    let key = digit
+                        .to_digit(16)
+                        .ok_or_else(|| error("keys must be hex digits or -"))?;
+                    keys[key as usize] = true;
+                }
+            }
+            log.events.push((frame, keys));
+        }
+
+        log.speed = speed.ok_or("missing speed line")?;
+        log.idle_skip = idle_skip.ok_or("missing idle_skip line")?;
+        Ok(log)
+    }
+
+    /// Returns the keys held during the given frame.
+    pub fn keys_at(&self, frame: usize) -> [bool; KEYBOARD_KEYS_COUNT] {
+        let index = self
+            .events
+            .partition_point(|(event_frame, _)| *event_frame <= frame);
+        if index == 0 {
+            [false; KEYBOARD_KEYS_COUNT]
+        } else {
+            self.events[index - 1].1
+        }
+    }
+
+    /// Returns whether the cheats are enabled during the given frame.
+    pub fn cheats_enabled_at(&self, frame: usize) -> bool {
+        let index = self
+            .cheat_toggles
+            .partition_point(|(toggle_frame, _)| *toggle_frame <= frame);
+        index == 0 || self.cheat_toggles[index - 1].1
+    }
+
+    /// The frame of the last change of keys or cheats, or 0 for an empty log.
+    pub fn last_frame(&self) -> usize {
+        let last_keys = self.events.last().map_or(0, |(frame, _)| *frame);
+        let last_toggle = self.cheat_toggles.last().map_or(0, |(frame, _)| *frame);
+        last_keys.max(last_toggle)
+    }
+}
+
+/// Parses the `on` or `off` of a setting.
+fn parse_on_off(text: &str) -> Option<bool> {
+    match text {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns `on` or `off` for a setting.
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+/// Writes an [`InputLog`] while a session is being played.
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    last_keys: Option<[bool; KEYBOARD_KEYS_COUNT]>,
+    cheats_enabled: bool,
+}
+
+impl InputRecorder {
+    /// Creates the log file and writes its header with the random seed, the speed, the idle skip
+    /// setting and the cheats of the session.
+    pub fn create(
+        path: &Path,
+        seed: u64,
+        speed: usize,
+        idle_skip: bool,
+        cheats: &[Cheat],
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "# chip8 input log")?;
+        writeln!(writer, "seed {}", seed)?;
+        writeln!(writer, "speed {}", speed)?;
+        writeln!(writer, "idle_skip {}", on_off(idle_skip))?;
+        for cheat in cheats {
+            writeln!(writer, "cheat {}", cheat)?;
+        }
+        Ok(Self {
+            writer,
+            last_keys: None,
+            cheats_enabled: true,
+        })
+    }
+
+    /// Records the keys held during a frame and whether the cheats were enabled.
+    /// Only changes are written to the file.
+    pub fn record(
+        &mut self,
+        frame: usize,
+        keys: &[bool; KEYBOARD_KEYS_COUNT],
+        cheats_enabled: bool,
+    ) -> io::Result<()> {
+        if cheats_enabled != self.cheats_enabled {
+            self.cheats_enabled = cheats_enabled;
+            writeln!(self.writer, "{} cheats {}", frame, on_off(cheats_enabled))?;
+        }
+        if self.last_keys.as_ref() == Some(keys) {
+            return Ok(());
+        }
+        self.last_keys = Some(*keys);
+
+        let pressed: String = (0..KEYBOARD_KEYS_COUNT)
+            .filter(|&key| keys[key])
+            .map(|key| format!("{:X}", key))
+            .collect();
+        let pressed = if pressed.is_empty() {
+            String::from("-")
+        } else {
+            pressed
+        };
+        writeln!(self.writer, "{} {}", frame, pressed)
+    }
+
+    /// Flushes the log to disk.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_cheats_and_toggles_are_replayed() {
+        let path = std::env::temp_dir().join(format!("input-log-{}.txt", std::process::id()));
+        let cheat: Cheat = "2F3 09 left score".parse().unwrap();
+        let mut keys = [false; KEYBOARD_KEYS_COUNT];
+
+        let mut recorder =
+            InputRecorder::create(&path, 42, 12, false, std::slice::from_ref(&cheat)).unwrap();
+        recorder.record(0, &keys, true).unwrap();
+        keys[5] = true;
+        recorder.record(120, &keys, false).unwrap();
+        recorder.record(121, &keys, false).unwrap();
+        recorder.record(130, &keys, true).unwrap();
+        recorder.finish().unwrap();
+
+        let log = InputLog::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(log.seed, 42);
+        assert_eq!(log.speed, 12);
+        assert!(!log.idle_skip);
+        assert_eq!(log.cheats, [cheat]);
+        assert_eq!(log.keys_at(125), keys);
+        assert!(log.cheats_enabled_at(119));
+        assert!(!log.cheats_enabled_at(129));
+        assert!(log.cheats_enabled_at(130));
+        assert_eq!(log.last_frame(), 130);
+        assert!(InputLog::parse("speed 10\nidle_skip on\n10 cheats maybe").is_err());
+        assert!(InputLog::parse("seed 1\nidle_skip on\n0 -").is_err());
+        assert!(InputLog::parse("seed 1\nspeed 10\n0 -").is_err());
+        assert!(InputLog::parse("speed 0").is_err());
+        assert!(InputLog::parse("idle_skip yes").is_err());
+    }
+}
@@ -0,0 +1,23 @@
+//! The `minifb` frontend and tools used by the `chip8_emulator_rust` binary.
+//! The interpreter lives in the `chip8_core` crate and is re-exported here.
+//! The modules are exposed as a library so benchmarks and other tools can drive the emulator.
+
+pub mod capture;
+pub mod constants;
+pub mod debugger;
+pub mod filters;
+pub mod input_log;
+pub mod memory_view;
+pub mod minifb_frontend;
+pub mod palette;
+pub mod rom_loader;
+pub mod rom_watcher;
+pub mod screen;
+pub mod sprite_viewer;
+pub mod terminal_frontend;
+pub mod user_input;
+pub mod video_export;
+
+pub use chip8_core::{
+    cheats, chip8, chip8_util, coverage, disassembler, frontend, headless, symbols,
+};
@@ -1,15 +1,30 @@
-use std::time::{Duration, Instant};
-
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chip8_emulator_rust::{
+    capture::Capture,
+    cheats::Cheats,
+    chip8, constants,
+    coverage::Coverage,
+    debugger::{self, DebugCommand, DebugConsole, Debugger},
+    filters::{FilterChain, FilterKind},
+    frontend::{FrameOutcome, FramePacer, Host, InputSource, RunLoop, VideoSink},
+    input_log::{InputLog, InputRecorder},
+    minifb_frontend::MinifbFrontend,
+    palette::Palette,
+    rom_loader::RomSource,
+    rom_watcher::RomWatcher,
+    screen::{DisplayOptions, PersistenceMode},
+    symbols::Symbols,
+    terminal_frontend::{TerminalFrontend, TerminalGlyphs},
+    video_export::{self, ExportSettings},
+};
 use clap::Parser;
-use minifb::Key;
-
 
-mod chip8;
-mod chip8_util;
-mod constants;
 mod game_menu;
-mod user_input;
-mod screen;
 
 /// Command-line arguments for the Chip-8 Emulator
 #[derive(Parser, Debug)]
@@ -23,21 +38,260 @@ struct Args {
     #[arg(long = "instruction_count", default_value_t = 20)]
     instruction_count: usize,
 
-    #[arg(long = "rom", default_value_t = String::from("PONG"))]
-    rom: String,
+    /// ROM to run: a bundled ROM name (e.g. PONG), a path to a file, `-` for stdin,
+    /// or a zip archive optionally followed by `:<entry>` (e.g. games.zip:PONG).
+    /// In normal mode the game menu is shown when no ROM is given.
+    #[arg(long = "rom")]
+    rom: Option<String>,
+
+    /// Symbol file of the ROM written by its assembler, with one `<address> <label>` or
+    /// `<address> <file>:<line>` line per symbol, addresses in hex. Debug mode then shows labels
+    /// and source lines, read next to the symbol file, and accepts labels for breakpoints.
+    #[arg(long)]
+    symbols: Option<PathBuf>,
+
+    /// Reload the ROM whenever its file changes on disk, keeping the window open (not in --debug mode)
+    #[arg(long, conflicts_with = "debug")]
+    watch: bool,
+
+    /// Number of instructions executed per frame in normal mode (kept when the ROM is reloaded)
+    #[arg(long, default_value_t = 10)]
+    speed: usize,
+
+    /// Run every instruction of frames spent waiting for the delay timer or a key, for accuracy
+    /// testing. By default such frames end as soon as the wait is detected and the emulator sleeps
+    /// until the next frame. Recorded in --record_input logs, whose setting --export_video uses.
+    #[arg(long = "no_idle_skip")]
+    no_idle_skip: bool,
+
+    /// Colour theme: classic, green, amber, lcd, or custom FOREGROUND,BACKGROUND hex colours (e.g. "#FF00FF,#202020")
+    #[arg(long, default_value = "classic")]
+    palette: Palette,
+
+    /// Size in host pixels of each Chip-8 pixel
+    #[arg(
+        long,
+        default_value_t = constants::DEFAULT_SCREEN_SCALE_FACTOR,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=40)
+    )]
+    scale: usize,
+
+    /// Start in fullscreen mode (F11 toggles it while running)
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Anti-flicker rendering: off, phosphor (pixels fade out) or or (OR of the last two frames)
+    #[arg(long, default_value = "off")]
+    persistence: PersistenceMode,
+
+    /// Fraction of brightness a pixel keeps each frame after turning off, in phosphor mode
+    #[arg(long = "phosphor_decay", default_value_t = constants::DEFAULT_PHOSPHOR_DECAY)]
+    phosphor_decay: f32,
+
+    /// Comma separated post-processing filters applied in order: scanlines, grid, bloom, curvature
+    #[arg(long = "filter", value_delimiter = ',')]
+    filters: Vec<FilterKind>,
+
+    /// Directory where screenshots (F12) and GIF recordings (F10) are saved
+    #[arg(long = "capture_dir", default_value = "captures")]
+    capture_dir: PathBuf,
+
+    /// Save screenshots and recordings at the native 64x32 resolution instead of the window scale
+    #[arg(long = "capture_native")]
+    capture_native: bool,
+
+    /// Where normal mode runs: window, or terminal to play in a text terminal (e.g. over SSH)
+    #[arg(long, default_value = "window")]
+    frontend: FrontendKind,
+
+    /// Characters used to draw the screen in the terminal frontend: halfblock (colour) or braille (denser)
+    #[arg(long = "terminal_glyphs", default_value = "halfblock")]
+    terminal_glyphs: TerminalGlyphs,
+
+    /// Record the keys pressed in normal mode, with the random seed, to replay the session later
+    #[arg(long = "record_input")]
+    record_input: Option<PathBuf>,
+
+    /// Cheat file whose RAM writes are applied before every frame, one `<address> <bytes> [name]`
+    /// line per cheat in hex (e.g. `2F3 09 score`). F6 enables or disables them while playing.
+    #[arg(long)]
+    cheats: Option<PathBuf>,
+
+    /// Count the instructions run in normal mode and print a report on exit: hot addresses,
+    /// instruction patterns, time spent waiting for a key or the delay timer, and stack depths.
+    /// Counts start again when --watch reloads the ROM.
+    #[arg(long)]
+    profile: bool,
+
+    /// With --profile, also print every instruction of the ROM with its execution count
+    #[arg(long = "profile_disassembly")]
+    profile_disassembly: bool,
+
+    /// Record which bytes of memory are executed, drawn as sprites, or read or written by
+    /// Fx33/Fx55/Fx65 in normal mode, and on exit write a report with a disassembly split into
+    /// code and data to this file, and a colour-coded hex view next to it (.html).
+    /// The map starts again when --watch reloads the ROM.
+    #[arg(long)]
+    coverage: Option<PathBuf>,
+
+    /// Export a headless replay of --rom at 60 fps without opening a window: a .y4m file,
+    /// or a directory of numbered PPM frames. The buzzer is written to a WAV file next to it.
+    #[arg(long = "export_video")]
+    export_video: Option<PathBuf>,
+
+    /// Input log recorded with --record_input to replay during --export_video
+    #[arg(long)]
+    input: Option<PathBuf>,
+
+    /// Number of frames to export, by default until one second after the last input change (or 10 seconds)
+    #[arg(long = "export_frames")]
+    export_frames: Option<usize>,
+}
+
+/// Frontends that can run the normal mode.
+#[derive(Debug, Clone, Copy)]
+enum FrontendKind {
+    Window,
+    Terminal,
+}
+
+impl FromStr for FrontendKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "window" => Ok(FrontendKind::Window),
+            "terminal" => Ok(FrontendKind::Terminal),
+            _ => Err(format!(
+                "unknown frontend '{}', use window or terminal",
+                value
+            )),
+        }
+    }
+}
+
+/// Settings of the normal mode loop.
+struct RunOptions {
+    watch: bool,
+    speed: usize,
+    idle_skip: bool,
+    record_input: Option<PathBuf>,
+    cheats: Cheats,
+    profile: Profile,
+    coverage: Option<PathBuf>,
+}
+
+/// What normal mode prints about the instructions run, on exit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Profile {
+    Off,
+    Report,
+    ReportAndDisassembly,
 }
 
 /// Example usage:
 /// Normal mode: `cargo run`
+/// Normal mode with a ROM: `cargo run -- --rom ~/roms/game.ch8`
+/// Reload the ROM on every rebuild: `cargo run -- --rom game.ch8 --watch`
 /// Debug mode: `cargo run -- --debug --instruction_count 50`
-
+/// Debug with symbols: `cargo run -- --debug --rom game.ch8 --symbols game.sym`
+/// Record a session: `cargo run -- --rom PONG --record_input pong.txt`
+/// Export it: `cargo run -- --rom PONG --input pong.txt --export_video pong.y4m`
+/// Profile a ROM: `cargo run -- --rom PONG --profile`
+/// Map the bytes a ROM uses: `cargo run -- --rom PONG --coverage pong.txt`
 fn main() {
     let args = Args::parse();
     let debug_mode = args.debug;
     let instruction_count = args.instruction_count;
-
-    if debug_mode {
-        run_debug_mode(instruction_count, args.rom);
+    let display_options = DisplayOptions {
+        scale: args.scale,
+        palette: args.palette,
+        fullscreen: args.fullscreen,
+        persistence: args.persistence,
+        phosphor_decay: args.phosphor_decay,
+    };
+    let mut frontend_options = FrontendOptions {
+        display: display_options,
+        filters: FilterChain::new(&args.filters),
+        capture: Capture::new(args.capture_dir, args.capture_native),
+        terminal_glyphs: args.terminal_glyphs,
+    };
+    let cheats = match &args.cheats {
+        Some(path) => load_cheats_or_exit(path),
+        None => Cheats::default(),
+    };
+    let run_options = RunOptions {
+        watch: args.watch,
+        speed: args.speed,
+        idle_skip: !args.no_idle_skip,
+        record_input: args.record_input,
+        cheats,
+        profile: match (args.profile, args.profile_disassembly) {
+            (false, _) => Profile::Off,
+            (true, false) => Profile::Report,
+            (true, true) => Profile::ReportAndDisassembly,
+        },
+        coverage: args.coverage,
+    };
+
+    if let Some(output) = args.export_video {
+        let rom = args.rom.unwrap_or_else(|| String::from("PONG"));
+        let binary = load_rom_or_exit(&RomSource::parse(&rom));
+        let input_log = match args.input {
+            Some(path) => InputLog::load(&path).unwrap_or_else(|e| {
+                eprintln!("Error: could not read input log: {}", e);
+                std::process::exit(1);
+            }),
+            None => InputLog {
+                speed: args.speed,
+                idle_skip: !args.no_idle_skip,
+                ..InputLog::default()
+            },
+        };
+        let settings = ExportSettings {
+            frames: args
+                .export_frames
+                .unwrap_or((input_log.last_frame() + 60).max(600)),
+        };
+
+        match video_export::export_video(
+            binary,
+            &input_log,
+            &output,
+            &settings,
+            &frontend_options.display,
+            &mut frontend_options.filters,
+        ) {
+            Ok(wav_path) => println!(
+                "Exported {} frames to {} and the sound to {}",
+                settings.frames,
+                output.display(),
+                wav_path.display()
+            ),
+            Err(e) => {
+                eprintln!("Error: export failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if debug_mode {
+        let rom = args.rom.unwrap_or_else(|| String::from("PONG"));
+        let symbols = args.symbols.as_deref().map(load_symbols_or_exit);
+        run_debug_mode(
+            instruction_count,
+            RomSource::parse(&rom),
+            symbols,
+            Debugger::new(run_options.cheats, run_options.speed),
+            frontend_options.display,
+            frontend_options.filters,
+            frontend_options.capture,
+        );
+    } else if let Some(rom) = args.rom {
+        run_normal_mode_on(
+            args.frontend,
+            RomSource::parse(&rom),
+            run_options,
+            frontend_options,
+        );
     } else {
         game_menu::show_game_menu();
 
@@ -54,7 +308,12 @@ fn main() {
 
         if let Some(game) = selected_rom {
             println!("Loading {}...", game);
-            run_normal_mode(game);
+            run_normal_mode_on(
+                args.frontend,
+                RomSource::parse(&game),
+                run_options,
+                frontend_options,
+            );
             // Here you would load and run the selected game
         } else {
             println!("No game selected. Exiting.");
@@ -62,115 +321,372 @@ fn main() {
     }
 }
 
-fn run_normal_mode(rom_file: String) {
-    let binary =
-        chip8_util::Chip8Util::read_rom(format!("files/roms/{}", rom_file).as_str()).unwrap();
-    let mut chip8 = chip8::Chip8::start(binary);
+/// Settings of the window and terminal frontends.
+struct FrontendOptions {
+    display: DisplayOptions,
+    filters: FilterChain,
+    capture: Capture,
+    terminal_glyphs: TerminalGlyphs,
+}
+
+/// Runs normal mode on the selected frontend.
+fn run_normal_mode_on(
+    frontend: FrontendKind,
+    rom_source: RomSource,
+    run_options: RunOptions,
+    options: FrontendOptions,
+) {
+    match frontend {
+        FrontendKind::Window => run_normal_mode(rom_source, run_options, || {
+            MinifbFrontend::new(options.display, options.filters, options.capture)
+        }),
+        FrontendKind::Terminal => run_normal_mode(rom_source, run_options, || {
+            TerminalFrontend::new(options.display.palette, options.terminal_glyphs).unwrap_or_else(
+                |e| {
+                    eprintln!("Error: could not set up the terminal: {}", e);
+                    std::process::exit(1);
+                },
+            )
+        }),
+    }
+}
 
-    let mut is_running = true;
-    let mut last_timer_update = Instant::now();
-    let frame_duration = Duration::from_millis(1000 / 60); // 60 FPS
+/// Loads the ROM from the given source, exiting with a readable message if it cannot be used.
+fn load_rom_or_exit(rom_source: &RomSource) -> Vec<u8> {
+    match rom_source.load() {
+        Ok(binary) => binary,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let mut buffer = screen::initialize_buffer();
-    let mut window = screen::initialize_window();
+/// Loads a cheat file, exiting with a readable message if it is invalid.
+fn load_cheats_or_exit(path: &Path) -> Cheats {
+    let cheats = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Cheats::parse(&text));
+    match cheats {
+        Ok(cheats) => {
+            println!(
+                "Loaded {} cheats from {}",
+                cheats.cheats.len(),
+                path.display()
+            );
+            cheats
+        }
+        Err(e) => {
+            eprintln!("Error: invalid cheat file {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    // Normal mode loop
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        chip8.reset_keyboard();
-        chip8.needs_redraw = false;
+/// Loads a symbol file and the source files it refers to, exiting with a readable message if it
+/// is invalid. Source files are looked up relative to the symbol file, and only warned about
+/// when missing.
+fn load_symbols_or_exit(path: &Path) -> Symbols {
+    let mut symbols = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Symbols::parse(&text))
+    {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            eprintln!("Error: invalid symbol file {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let sources: Vec<String> = symbols
+        .source_files()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    for file in sources {
+        match fs::read_to_string(directory.join(&file)) {
+            Ok(text) => symbols.add_source(&file, &text),
+            Err(e) => eprintln!("Source lines of {} are not shown: {}", file, e),
+        }
+    }
+    println!("Loaded the symbols of {}", path.display());
+    symbols
+}
 
-        // Check pressed keys
-        if let Some(key) = user_input::get_pressed_key(&window) {
-            chip8.keyboard[key as usize] = true;
+/// Runs a ROM on the host created by `create_host`, which is only called once the ROM is loaded.
+fn run_normal_mode<H: Host>(
+    rom_source: RomSource,
+    run_options: RunOptions,
+    create_host: impl FnOnce() -> H,
+) {
+    let RunOptions {
+        watch,
+        speed,
+        idle_skip,
+        record_input,
+        cheats,
+        profile,
+        coverage,
+    } = run_options;
+    let binary = load_rom_or_exit(&rom_source);
+    let mut rom_length = binary.len();
+    let mut chip8 = chip8::Chip8::start(binary);
+    chip8.set_idle_skip(idle_skip);
+    chip8.set_profiler(profile != Profile::Off);
+    chip8.set_coverage(coverage.is_some());
+
+    // The seed is saved with the recorded keys so the session can be replayed exactly
+    let seed: u64 = rand::random();
+    chip8.seed_rng(seed);
+    let mut input_recorder = record_input.map(|path| {
+        println!("Recording input to {}", path.display());
+        // The profiler runs frames in full, so the log records whether frames really ended early
+        InputRecorder::create(&path, seed, speed, chip8.skips_idle(), &cheats.cheats)
+            .unwrap_or_else(|e| {
+                eprintln!("Error: could not create {}: {}", path.display(), e);
+                std::process::exit(1);
+            })
+    });
+
+    let mut rom_watcher = match rom_source.path() {
+        Some(path) if watch => {
+            println!("Watching {} for changes", path.display());
+            Some(RomWatcher::new(path.to_path_buf()))
+        }
+        _ => {
+            if watch {
+                eprintln!(
+                    "--watch needs a ROM file, ignoring it for {}",
+                    rom_source.name()
+                );
+            }
+            None
         }
+    };
+
+    // Each frame polls the keyboard, runs `speed` instructions, updates the timers once
+    // and redraws the screen, at 60 frames per second to match the CHIP-8 timers.
+    // CHIP-8 programs expect roughly 500 to 1000 instructions per second, so 8 to 12 per frame.
+    // A frame ends early once the program only waits for the delay timer or a key.
+    let mut run_loop = RunLoop::new(chip8, create_host(), speed);
+    run_loop.cheats = cheats;
 
-        if window.is_key_pressed(Key::Space, minifb::KeyRepeat::Yes) {
-            is_running = !is_running;
-            if is_running {
+    // Normal mode loop
+    loop {
+        let was_paused = run_loop.is_paused();
+        let cheats_were_enabled = run_loop.cheats.enabled;
+        match run_loop.step_frame() {
+            FrameOutcome::Quit => break,
+            FrameOutcome::Ran => {
+                if let Some(recorder) = input_recorder.as_mut()
+                    && let Err(e) = recorder.record(
+                        run_loop.frames_run() - 1,
+                        &run_loop.chip8.keyboard,
+                        run_loop.cheats.enabled,
+                    )
+                {
+                    eprintln!("Input recording stopped: {}", e);
+                    input_recorder = None;
+                }
+            }
+            FrameOutcome::Paused => (),
+        }
+
+        if run_loop.is_paused() != was_paused {
+            if was_paused {
                 println!("Resuming execution");
             } else {
                 println!("Pausing execution");
             }
         }
-
-        if is_running {
-            // What is a "frame" in this context?
-            // A frame is essentially one iteration of your main loop where you update the game state and redraw the screen.
-            // Think of it as:
-            // Poll input (keyboard state)
-            // Run some number of CHIP-8 CPU cycles
-            // Update timers (delay + sound, 60Hz)
-            // Redraw the display
-            // Since the CHIP-8 was designed to run on very simple hardware, its timers tick at 60 Hz (once every 1/60th of a second),
-            // independent of how many CPU cycles are run in between.
-            // Run multiple CPU cycles per frame
-
-            // Decouple CPU cycles from timers and frames. Typically:
-            // Decide on a target FPS → usually 60 Hz, to match the CHIP-8 timers.
-            // Run multiple CPU cycles per frame → because CHIP-8 executes more than 60 instructions per second (usually ~500–1000).
-            // Decrement timers exactly once per frame.
-            // Redraw the screen at 60 Hz.
-            for _ in 0..10 {
-                // Adjust: 8–12 is common
-                chip8.tick();
+        if run_loop.cheats.enabled != cheats_were_enabled {
+            if run_loop.cheats.enabled {
+                println!("Cheats enabled");
+            } else {
+                println!("Cheats disabled");
             }
+        }
 
-            // 3. Update timers at ~60Hz
-            if last_timer_update.elapsed() >= frame_duration {
-                chip8.update_timers();
-
-                last_timer_update = Instant::now();
+        if let Some(watcher) = rom_watcher.as_mut()
+            && watcher.has_changed()
+        {
+            // A half-written file fails to load, the next change will trigger another reload
+            match rom_source.load() {
+                Ok(binary) => {
+                    rom_length = binary.len();
+                    run_loop.chip8 = chip8::Chip8::start(binary);
+                    run_loop.chip8.seed_rng(seed);
+                    run_loop.chip8.set_idle_skip(idle_skip);
+                    run_loop.chip8.set_profiler(profile != Profile::Off);
+                    run_loop.chip8.set_coverage(coverage.is_some());
+                    run_loop.chip8.request_full_redraw();
+                    let discarded: Vec<&str> = [
+                        (profile != Profile::Off, "profile"),
+                        (coverage.is_some(), "coverage map"),
+                    ]
+                    .into_iter()
+                    .filter_map(|(enabled, name)| enabled.then_some(name))
+                    .collect();
+                    if discarded.is_empty() {
+                        println!("Reloaded {}", rom_source.name());
+                    } else {
+                        println!(
+                            "Reloaded {}, the {} collected so far started again",
+                            rom_source.name(),
+                            discarded.join(" and ")
+                        );
+                    }
+                    // The log only replays the keys on the ROM given at export time
+                    if let Some(recorder) = input_recorder.take() {
+                        match recorder.finish() {
+                            Ok(()) => println!(
+                                "Input recording stopped at frame {}: the ROM was reloaded",
+                                run_loop.frames_run()
+                            ),
+                            Err(e) => eprintln!("Could not save the input recording: {}", e),
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Could not reload ROM, keeping the previous one: {}", e),
             }
-
-            chip8.tick();
         }
+    }
 
-        screen::draw_screen_if_needed(&mut buffer, &chip8);
+    if let Some(recorder) = input_recorder
+        && let Err(e) = recorder.finish()
+    {
+        eprintln!("Could not save the input recording: {}", e);
+    }
 
-        screen::update_window_with_buffer(&mut buffer, &mut window);
+    let ram = &run_loop.chip8.ram;
+    let mut output = String::new();
+    if let Some(profiler) = run_loop.chip8.profiler() {
+        output.push_str(&profiler.report(ram));
+        if profile == Profile::ReportAndDisassembly {
+            output.push('\n');
+            output.push_str(&profiler.annotated_disassembly(ram));
+        }
+    }
+    let coverage_saved = match (&coverage, run_loop.chip8.coverage()) {
+        (Some(path), Some(map)) => Some(save_coverage(path, map, ram, rom_length)),
+        _ => None,
+    };
+
+    // The frontend is closed first, so the terminal frontend restores the screen
+    drop(run_loop);
+    print!("{}", output);
+    match (coverage, coverage_saved) {
+        (Some(path), Some(Ok(hex_view_path))) => println!(
+            "Saved the coverage to {} and {}",
+            path.display(),
+            hex_view_path.display()
+        ),
+        (_, Some(Err(e))) => eprintln!("Could not save the coverage: {}", e),
+        _ => (),
     }
 }
 
-fn run_debug_mode(instruction_count: usize, rom_file: String) {
-    // let binary = read_rom("files/roms/IBM_Logo.ch8").unwrap();
-    // let binary = read_rom("files/roms/chip8-logo.ch8").unwrap();
-    // let binary = read_rom("files/roms/3-corax+.ch8").unwrap();
-    // let binary = read_rom("files/roms/5-quirks.ch8").unwrap();
-    // let binary = read_rom("files/roms/4-flags.ch8").unwrap();
-    let binary =
-        chip8_util::Chip8Util::read_rom(format!("files/roms/{}", rom_file).as_str()).unwrap();
+/// Writes the coverage report and disassembly to `path`, and the hex view to `path` with the
+/// `.html` extension, which is returned.
+fn save_coverage(
+    path: &Path,
+    coverage: &Coverage,
+    ram: &[u8],
+    rom_length: usize,
+) -> io::Result<PathBuf> {
+    let report = format!(
+        "{}\nDisassembly:\n{}",
+        coverage.report(rom_length),
+        coverage.disassembly(ram, rom_length)
+    );
+    fs::write(path, report)?;
+    let hex_view_path = path.with_extension("html");
+    fs::write(&hex_view_path, coverage.hex_view(ram, rom_length))?;
+    Ok(hex_view_path)
+}
 
-    let mut chip8 = chip8::Chip8::start(binary);
+fn run_debug_mode(
+    instruction_count: usize,
+    rom_source: RomSource,
+    symbols: Option<Symbols>,
+    mut debugger: Debugger,
+    display_options: DisplayOptions,
+    filter_chain: FilterChain,
+    capture: Capture,
+) {
+    let binary = load_rom_or_exit(&rom_source);
+    debugger.rom_length = binary.len();
+    debugger.palette = display_options.palette;
 
-    // Debug mode loop
-    let mut space_pressed = false;
+    let mut chip8 = chip8::Chip8::start(binary);
+    chip8.set_symbols(symbols);
 
     println!("CHIP-8 Debug Mode");
     println!("Controls:");
-    println!("  SPACE - Execute one instruction");
+    println!("  SPACE - Execute one instruction, or stop `continue`");
+    println!("  F1-F5 - Cheat search, F6 - Enable or disable the cheats");
     println!("  ESC   - Quit");
+    println!(
+        "Type commands in this console, e.g. `frame 60`, `break 2D4`, `list`, `mem` or `sprite`, or help"
+    );
     println!();
 
     // Execute initial instructions if instruction_count > 0
     for _ in 0..instruction_count {
-        chip8.tick();
+        debugger.step(&mut chip8);
     }
 
     chip8.enable_debug_mode(instruction_count);
 
-    let mut buffer = screen::initialize_buffer();
-    let mut window = screen::initialize_window();
+    let mut frontend = MinifbFrontend::new(display_options, filter_chain, capture);
+    let console = DebugConsole::new();
+    let mut pacer = FramePacer::default();
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let space_down = window.is_key_down(Key::Space);
-        if space_down && !space_pressed {
-            chip8.tick();
+    // Debug mode loop, Space steps one instruction instead of pausing
+    loop {
+        let input = frontend.poll_input();
+        if input.quit {
+            break;
+        }
+        if input.toggle_pause {
+            if debugger.is_running() {
+                println!("{}", debugger.stop(&chip8));
+            } else {
+                debugger.step(&mut chip8);
+            }
+        }
+        if input.toggle_cheats {
+            debugger.cheats.enabled = !debugger.cheats.enabled;
+            println!(
+                "Cheats {}",
+                if debugger.cheats.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+        }
+
+        while let Some(line) = console.poll_line() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse::<DebugCommand>() {
+                Ok(DebugCommand::Help) => println!("{}", debugger::HELP),
+                Ok(command) => println!("{}", debugger.execute(&mut chip8, command)),
+                Err(e) => println!("{}", e),
+            }
         }
-        space_pressed = space_down;
 
-        screen::draw_screen_if_needed(&mut buffer, &chip8);
+        if debugger.is_running()
+            && let Some(stopped) = debugger.run_frame(&mut chip8)
+        {
+            println!("{}", stopped);
+        }
 
-        screen::update_window_with_buffer(&mut buffer, &mut window);
+        frontend.present(&chip8);
+        chip8.clear_redraw_state();
+        pacer.wait_for_next_frame(&mut frontend);
     }
 }
-
@@ -0,0 +1,214 @@
+use std::fmt::Write;
+
+use crossterm::style::{StyledContent, Stylize};
+
+use crate::{
+    chip8::Chip8,
+    constants::{CHIP8_RAM_MEMORY_SIZE, FONT_SPRITES_SIZE, START_RAM_ADDRESS},
+};
+
+/// Number of bytes shown on each row of the memory view.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// Parts of memory highlighted by the memory view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// The hex digit sprites loaded at address 0.
+    Font,
+    /// The bytes the ROM was loaded into.
+    Rom,
+    /// The byte the I register points to.
+    IPointer,
+    /// An instruction a return address on the stack points to. The stack itself is kept
+    /// outside the 4 KiB of memory.
+    Stack,
+}
+
+impl Region {
+    fn style(self, text: String) -> StyledContent<String> {
+        match self {
+            Region::Font => text.dark_yellow(),
+            Region::Rom => text.cyan(),
+            Region::IPointer => text.black().on_white(),
+            Region::Stack => text.black().on_magenta(),
+        }
+    }
+}
+
+/// The region the byte at `address` belongs to, the most specific one when it is in several:
+/// the I pointer, then the stack, the ROM and the font. `return_addresses` are those of
+/// [`Chip8::return_addresses`], taken once for all the bytes shown.
+pub fn region(
+    chip8: &Chip8,
+    return_addresses: &[u16],
+    rom_length: usize,
+    address: usize,
+) -> Option<Region> {
+    let start = START_RAM_ADDRESS as usize;
+    let returns_to = |&return_address: &u16| {
+        let return_address = return_address as usize % CHIP8_RAM_MEMORY_SIZE;
+        address == return_address || address == (return_address + 1) % CHIP8_RAM_MEMORY_SIZE
+    };
+    if address == chip8.i_register as usize % CHIP8_RAM_MEMORY_SIZE {
+        Some(Region::IPointer)
+    } else if return_addresses.iter().any(returns_to) {
+        Some(Region::Stack)
+    } else if (start..start + rom_length).contains(&address) {
+        Some(Region::Rom)
+    } else if address < FONT_SPRITES_SIZE {
+        Some(Region::Font)
+    } else {
+        None
+    }
+}
+
+/// `rows` rows of memory from the row holding `address`, in hex and ASCII, after a legend of
+/// the regions. With `colour`, the bytes of each [`Region`] are coloured with ANSI escapes.
+pub fn render(chip8: &Chip8, rom_length: usize, address: u16, rows: usize, colour: bool) -> String {
+    let paint = |region: Option<Region>, text: String| match region {
+        Some(region) if colour => region.style(text).to_string(),
+        _ => text,
+    };
+    let rom_end = (START_RAM_ADDRESS as usize + rom_length).min(CHIP8_RAM_MEMORY_SIZE);
+    let return_addresses = chip8.return_addresses();
+    let stack: Vec<String> = return_addresses
+        .iter()
+        .map(|address| format!("{:03X}", address))
+        .collect();
+
+    let mut view = String::new();
+    write!(
+        view,
+        "{}  {}  {}",
+        paint(
+            Some(Region::Font),
+            format!("font 000-{:03X}", FONT_SPRITES_SIZE - 1)
+        ),
+        paint(
+            Some(Region::Rom),
+            format!("ROM {:03X}-{:03X}", START_RAM_ADDRESS, rom_end.max(1) - 1)
+        ),
+        paint(
+            Some(Region::IPointer),
+            format!("I={:03X}", chip8.i_register)
+        ),
+    )
+    .unwrap();
+    let stack = if stack.is_empty() {
+        String::from("stack empty")
+    } else {
+        format!("stack {}", stack.join(" "))
+    };
+    writeln!(view, "  {}", paint(Some(Region::Stack), stack)).unwrap();
+
+    let first_row = address as usize / BYTES_PER_ROW;
+    let last_row = (first_row + rows).min(CHIP8_RAM_MEMORY_SIZE / BYTES_PER_ROW);
+    for row in first_row..last_row {
+        let start = row * BYTES_PER_ROW;
+        write!(view, "{:03X} ", start).unwrap();
+        let mut ascii = String::new();
+        for address in start..start + BYTES_PER_ROW {
+            let byte = chip8.ram[address];
+            let region = region(chip8, &return_addresses, rom_length, address);
+            write!(view, " {}", paint(region, format!("{:02X}", byte))).unwrap();
+            let character = match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            };
+            ascii.push_str(&paint(region, character.to_string()));
+        }
+        writeln!(view, "  |{}|", ascii).unwrap();
+    }
+    view.trim_end().to_string()
+}
+
+/// The addresses where `pattern` starts in `ram`, `None` matching any byte.
+pub fn find(ram: &[u8], pattern: &[Option<u8>]) -> Vec<u16> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    ram.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| expected.is_none_or(|expected| expected == *byte))
+        })
+        .map(|(address, _)| address as u16)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::chip8_util::Chip8Util;
+
+    #[test]
+    fn rows_show_hex_and_ascii_with_a_legend_of_the_regions() {
+        let mut chip8 = Chip8::start(b"CHIP-8".to_vec());
+        chip8.i_register = 0x203;
+        chip8.stack[0] = 0x204;
+        chip8.stack_pointer = 1;
+        chip8.stack_depth = 1;
+
+        let returns = chip8.return_addresses();
+        let view = render(&chip8, 6, 0x205, 1, false);
+        assert_eq!(
+            view,
+            "font 000-04F  ROM 200-205  I=203  stack 204\n\
+             200  43 48 49 50 2D 38 00 00 00 00 00 00 00 00 00 00  |CHIP-8..........|"
+        );
+        assert_eq!(region(&chip8, &returns, 6, 0x203), Some(Region::IPointer));
+        assert_eq!(region(&chip8, &returns, 6, 0x205), Some(Region::Stack));
+        assert_eq!(region(&chip8, &returns, 6, 0x202), Some(Region::Rom));
+        assert_eq!(region(&chip8, &returns, 6, 0x04F), Some(Region::Font));
+        assert_eq!(region(&chip8, &returns, 6, 0x206), None);
+
+        let coloured = render(&chip8, 6, 0x205, 1, true);
+        assert!(coloured.contains(&Region::IPointer.style(String::from("50")).to_string()));
+    }
+
+    #[test]
+    fn patterns_are_found_with_wildcards() {
+        let ram = [0xA2, 0xF0, 0xA2, 0xEA, 0x00];
+        let pattern = Chip8Util::parse_hex_bytes("A2 ??", true).unwrap();
+
+        assert_eq!(find(&ram, &pattern), [0, 2]);
+        assert_eq!(
+            find(&ram, &Chip8Util::parse_hex_bytes("a2ea", false).unwrap()),
+            [2]
+        );
+        assert!(Chip8Util::parse_hex_bytes("A2 ??", false).is_err());
+        assert!(Chip8Util::parse_hex_bytes("A2F", true).is_err());
+        assert!(Chip8Util::parse_hex_bytes("A2 +F", false).is_err());
+    }
+
+    #[test]
+    fn return_addresses_are_shown_after_the_stack_wraps() {
+        // 2202: CALL 0x202, 2204: CALL 0x204, ... 20 nested calls, each to the next one
+        let rom: Vec<u8> = (1..=20u16)
+            .flat_map(|call| (0x2200 + 2 * call).to_be_bytes())
+            .collect();
+        let mut chip8 = Chip8::start(rom);
+        for _ in 0..20 {
+            chip8.tick();
+        }
+        assert_eq!(chip8.stack_pointer, 4);
+
+        let listed: Vec<String> = (4..20)
+            .map(|call| format!("{:03X}", 0x202 + 2 * call))
+            .collect();
+        let view = render(&chip8, 40, 0x200, 1, false);
+        assert!(
+            view.contains(&format!("stack {}\n", listed.join(" "))),
+            "{}",
+            view
+        );
+        let returns = chip8.return_addresses();
+        assert_eq!(region(&chip8, &returns, 40, 0x20A), Some(Region::Stack));
+        assert_eq!(region(&chip8, &returns, 40, 0x229), Some(Region::Stack));
+        assert_eq!(region(&chip8, &returns, 40, 0x202), Some(Region::Rom));
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{
+    capture::Capture,
+    cheats::{CheatSearch, Comparison, SearchStep},
+    chip8::Chip8,
+    filters::FilterChain,
+    frontend::{AudioSink, InputSource, InputState, SystemClock, TimeSource, VideoSink},
+    screen::{self, DisplayOptions, Persistence},
+    user_input,
+};
+
+/// Keys of the cheat search steps.
+const SEARCH_HOTKEYS: [(Key, SearchStep); 5] = [
+    (Key::F1, SearchStep::Start),
+    (Key::F2, SearchStep::Narrow(Comparison::Unchanged)),
+    (Key::F3, SearchStep::Narrow(Comparison::Changed)),
+    (Key::F4, SearchStep::Narrow(Comparison::Increased)),
+    (Key::F5, SearchStep::Narrow(Comparison::Decreased)),
+];
+
+/// The desktop frontend: a `minifb` window showing the screen through the persistence mode and
+/// filters, the keyboard mapped with `KEYBOARD_CODES`, and the host clock.
+///
+/// Besides the keypad it handles the window controls: Esc quits, Space pauses,
+/// F11 toggles fullscreen, F12 saves a screenshot and F10 starts or stops a GIF recording.
+/// F1 starts a cheat search, F2 to F5 narrow it to the bytes that stayed the same, changed,
+/// increased or decreased, and F6 enables or disables the cheats.
+pub struct MinifbFrontend {
+    window: Window,
+    buffer: Vec<u32>,
+    options: DisplayOptions,
+    persistence: Persistence,
+    filters: FilterChain,
+    capture: Capture,
+    clock: SystemClock,
+    screenshot_requested: bool,
+    recording_toggle_requested: bool,
+    search_step_requested: Option<SearchStep>,
+    cheat_search: Option<CheatSearch>,
+    paused: bool, // Paused frames are not added to GIF recordings, as told by the run loop
+}
+
+impl MinifbFrontend {
+    /// Opens the window.
+    pub fn new(options: DisplayOptions, filters: FilterChain, capture: Capture) -> Self {
+        Self {
+            window: screen::initialize_window(&options),
+            buffer: screen::initialize_buffer(&options),
+            persistence: Persistence::new(&options),
+            options,
+            filters,
+            capture,
+            clock: SystemClock::new(),
+            screenshot_requested: false,
+            recording_toggle_requested: false,
+            search_step_requested: None,
+            cheat_search: None,
+            paused: false,
+        }
+    }
+
+    /// The window, to read keys that are not part of the [`InputState`].
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    fn save_screenshot(&mut self, chip8: &Chip8) {
+        match self.capture.save_screenshot(chip8, &self.options) {
+            Ok(path) => println!("Saved screenshot {}", path.display()),
+            Err(e) => eprintln!("Could not save screenshot: {}", e),
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        let was_recording = self.capture.is_recording();
+        match self.capture.toggle_recording(&self.options) {
+            Ok(path) if was_recording => println!("Saved recording {}", path.display()),
+            Ok(path) => println!("Recording to {}, press F10 to stop", path.display()),
+            Err(e) => eprintln!("Could not start recording: {}", e),
+        }
+    }
+}
+
+impl InputSource for MinifbFrontend {
+    fn poll_input(&mut self) -> InputState {
+        // The buffer is kept when the window is recreated, so nothing needs to be redrawn
+        screen::toggle_fullscreen_if_requested(&mut self.window, &mut self.options);
+
+        // Captures need the screen, they are taken when the frame is presented
+        self.screenshot_requested |= self.window.is_key_pressed(Key::F12, KeyRepeat::No);
+        self.recording_toggle_requested |= self.window.is_key_pressed(Key::F10, KeyRepeat::No);
+        for (key, step) in SEARCH_HOTKEYS {
+            if self.window.is_key_pressed(key, KeyRepeat::No) {
+                self.search_step_requested = Some(step);
+            }
+        }
+
+        InputState {
+            keys: user_input::get_pressed_keys(&self.window),
+            quit: !self.window.is_open() || self.window.is_key_down(Key::Escape),
+            toggle_pause: self.window.is_key_pressed(Key::Space, KeyRepeat::No),
+            toggle_cheats: self.window.is_key_pressed(Key::F6, KeyRepeat::No),
+        }
+    }
+}
+
+impl VideoSink for MinifbFrontend {
+    fn show_run_state(&mut self, paused: bool, _cheats_enabled: bool) {
+        self.paused = paused;
+    }
+
+    fn present(&mut self, chip8: &Chip8) {
+        if std::mem::take(&mut self.screenshot_requested) {
+            self.save_screenshot(chip8);
+        }
+        if std::mem::take(&mut self.recording_toggle_requested) {
+            self.toggle_recording();
+        }
+        if let Some(step) = self.search_step_requested.take() {
+            step.apply(&mut self.cheat_search, chip8);
+            if let Some(search) = &self.cheat_search {
+                println!("Cheat search: {}", search);
+            }
+        }
+        if !self.paused
+            && let Err(e) = self.capture.record_frame(chip8, &self.options)
+        {
+            eprintln!("Recording stopped: {}", e);
+            self.capture.stop_recording();
+        }
+
+        let redrawn = screen::draw_screen_if_needed(
+            &mut self.buffer,
+            chip8,
+            &self.options,
+            &mut self.persistence,
+        );
+
+        screen::update_window_with_buffer(
+            &self.buffer,
+            &mut self.window,
+            &self.options,
+            &mut self.filters,
+            redrawn,
+        );
+    }
+}
+
+/// The window has no sound output, the buzzer is not played.
+impl AudioSink for MinifbFrontend {
+    fn set_buzzer(&mut self, _on: bool) {}
+}
+
+impl TimeSource for MinifbFrontend {
+    fn now(&mut self) -> Duration {
+        self.clock.now()
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        self.clock.wait_until(deadline);
+    }
+}
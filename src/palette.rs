@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+/// Colours used to draw the Chip-8 display, as `0x00RRGGBB` values understood by `minifb`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub foreground: u32, // Colour of pixels that are on
+    pub background: u32, // Colour of pixels that are off, also used for the letter-box borders
+}
+
+impl Palette {
+    /// White pixels on a black background, the original look of the emulator.
+    pub const CLASSIC: Palette = Palette {
+        foreground: 0xFFFFFF,
+        background: 0x000000,
+    };
+
+    /// Green phosphor monochrome monitor.
+    pub const GREEN: Palette = Palette {
+        foreground: 0x33FF66,
+        background: 0x001A0A,
+    };
+
+    /// Amber phosphor monochrome monitor.
+    pub const AMBER: Palette = Palette {
+        foreground: 0xFFB000,
+        background: 0x1A0F00,
+    };
+
+    /// Greenish LCD, like the screens of the HP-48 calculators that ran SCHIP.
+    pub const LCD: Palette = Palette {
+        foreground: 0x1F2F1A,
+        background: 0x8BAC0F,
+    };
+
+    /// Returns the colour for a pixel of the Chip-8 screen.
+    pub fn color(&self, pixel_on: bool) -> u32 {
+        if pixel_on {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+
+    /// Mixes the background and foreground colours, `brightness` going from 0.0 (background) to 1.0 (foreground).
+    pub fn blend(&self, brightness: f32) -> u32 {
+        let brightness = brightness.clamp(0.0, 1.0);
+        let mut color = 0;
+        for shift in [16, 8, 0] {
+            let background = ((self.background >> shift) & 0xFF) as f32;
+            let foreground = ((self.foreground >> shift) & 0xFF) as f32;
+            let channel = background + (foreground - background) * brightness;
+            color |= (channel.round() as u32) << shift;
+        }
+        color
+    }
+
+    /// Splits the background colour in its red, green and blue components.
+    pub fn background_rgb(&self) -> (u8, u8, u8) {
+        (
+            (self.background >> 16) as u8,
+            (self.background >> 8) as u8,
+            self.background as u8,
+        )
+    }
+
+    /// Parses a colour written as `RRGGBB`, with an optional leading `#`.
+    fn parse_hex_color(value: &str) -> Result<u32, String> {
+        let hex = value.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is not a RRGGBB colour", value));
+        }
+        Ok(u32::from_str_radix(hex, 16).expect("six hex digits fit in a u32"))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC
+    }
+}
+
+/// Accepts a theme name (`classic`, `green`, `amber`, `lcd`) or a custom
+/// `foreground,background` pair of hex colours such as `#FF00FF,#202020`.
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "classic" => Ok(Palette::CLASSIC),
+            "green" => Ok(Palette::GREEN),
+            "amber" => Ok(Palette::AMBER),
+            "lcd" => Ok(Palette::LCD),
+            _ => {
+                let (foreground, background) = value.split_once(',').ok_or_else(|| {
+                    format!(
+                        "unknown palette '{}', use classic, green, amber, lcd or FOREGROUND,BACKGROUND hex colours",
+                        value
+                    )
+                })?;
+                Ok(Palette {
+                    foreground: Palette::parse_hex_color(foreground)?,
+                    background: Palette::parse_hex_color(background)?,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_are_parsed() {
+        let custom = |foreground, background| {
+            Some(Palette {
+                foreground,
+                background,
+            })
+        };
+        let cases = [
+            ("classic", Some(Palette::CLASSIC)),
+            ("Green", Some(Palette::GREEN)),
+            ("AMBER", Some(Palette::AMBER)),
+            ("lcd", Some(Palette::LCD)),
+            ("#FF00FF,#202020", custom(0xFF00FF, 0x202020)),
+            ("ff00ff,202020", custom(0xFF00FF, 0x202020)),
+            ("#ff00ff, 202020", custom(0xFF00FF, 0x202020)),
+            ("sepia", None),
+            ("", None),
+            ("#FF00FF", None),
+            ("#FF00F,#202020", None),
+            ("#FF00FF,#2020200", None),
+            ("#FF00FF,", None),
+            ("#GG00FF,#202020", None),
+            ("+FF00F,#202020", None),
+            ("#FF00FF,#20é20", None),
+            ("#FF00FF,#202020,#000000", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Palette>().ok(), expected, "input {:?}", input);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::constants::{MAX_ROM_SIZE, ROMS_DIRECTORY};
+
+/// Where the bytes of a ROM come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RomSource {
+    /// A `.ch8` file on disk.
+    File(PathBuf),
+    /// The ROM is piped through the standard input (`--rom -`).
+    Stdin,
+    /// An entry inside a `.zip` archive. When `entry` is `None` the archive must contain a single file.
+    ZipEntry {
+        archive: PathBuf,
+        entry: Option<String>,
+    },
+}
+
+/// Errors that can happen while resolving or reading a ROM.
+#[derive(Debug)]
+pub enum RomError {
+    /// `in_bundled` tells whether `files/roms/` was looked in as well, as for bare names.
+    NotFound {
+        path: PathBuf,
+        in_bundled: bool,
+    },
+    Empty(String),
+    /// `size` is only known for files, streams are not read past the limit.
+    TooLarge {
+        name: String,
+        size: Option<usize>,
+    },
+    ZipEntryNotFound {
+        archive: PathBuf,
+        entry: String,
+    },
+    AmbiguousZip {
+        archive: PathBuf,
+        entries: Vec<String>,
+    },
+    Zip(zip::result::ZipError),
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::NotFound {
+                path,
+                in_bundled: true,
+            } => write!(
+                f,
+                "ROM '{}' was not found (also looked in '{}/')",
+                path.display(),
+                ROMS_DIRECTORY
+            ),
+            RomError::NotFound {
+                path,
+                in_bundled: false,
+            } => write!(f, "ROM '{}' was not found", path.display()),
+            RomError::Empty(name) => write!(f, "ROM '{}' is empty", name),
+            RomError::TooLarge {
+                name,
+                size: Some(size),
+            } => write!(
+                f,
+                "ROM '{}' is {} bytes, but at most {} bytes fit in memory after 0x200",
+                name, size, MAX_ROM_SIZE
+            ),
+            RomError::TooLarge { name, size: None } => write!(
+                f,
+                "ROM '{}' is larger than the {} bytes that fit in memory after 0x200",
+                name, MAX_ROM_SIZE
+            ),
+            RomError::ZipEntryNotFound { archive, entry } => write!(
+                f,
+                "'{}' does not contain an entry named '{}'",
+                archive.display(),
+                entry
+            ),
+            RomError::AmbiguousZip { archive, entries } => write!(
+                f,
+                "'{}' contains several files, pick one with '{}:<entry>': {}",
+                archive.display(),
+                archive.display(),
+                entries.join(", ")
+            ),
+            RomError::Zip(e) => write!(f, "could not read zip archive: {}", e),
+            RomError::Io(e) => write!(f, "could not read ROM: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> Self {
+        RomError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for RomError {
+    fn from(e: zip::result::ZipError) -> Self {
+        RomError::Zip(e)
+    }
+}
+
+impl RomSource {
+    /// Parses the value given to `--rom`.
+    ///
+    /// * `-` reads the ROM from stdin.
+    /// * `games.zip` or `games.zip:PONG.ch8` reads an entry from a zip archive.
+    /// * Anything else is a path. Bare names such as `PONG` that do not exist in the current
+    ///   directory are looked up in `files/roms/` so the bundled ROMs keep working.
+    pub fn parse(rom_argument: &str) -> Self {
+        if rom_argument == "-" {
+            return RomSource::Stdin;
+        }
+
+        // ASCII lowercase keeps the byte offsets of the original argument, which is split below
+        let lowercase = rom_argument.to_ascii_lowercase();
+        if let Some(index) = lowercase.find(".zip:") {
+            let (archive, entry) = rom_argument.split_at(index + ".zip".len());
+            return RomSource::ZipEntry {
+                archive: PathBuf::from(archive),
+                entry: Some(entry[1..].to_string()),
+            };
+        }
+        if lowercase.ends_with(".zip") {
+            return RomSource::ZipEntry {
+                archive: PathBuf::from(rom_argument),
+                entry: None,
+            };
+        }
+
+        let path = PathBuf::from(rom_argument);
+        if let Some(bundled_path) = bundled_path(&path)
+            && !path.exists()
+            && bundled_path.exists()
+        {
+            return RomSource::File(bundled_path);
+        }
+
+        RomSource::File(path)
+    }
+
+    /// Returns the file on disk backing this source, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            RomSource::File(path) => Some(path),
+            RomSource::ZipEntry { archive, .. } => Some(archive),
+            RomSource::Stdin => None,
+        }
+    }
+
+    /// Human readable name of the source, used in messages and the window title.
+    pub fn name(&self) -> String {
+        match self {
+            RomSource::File(path) => path.display().to_string(),
+            RomSource::Stdin => String::from("<stdin>"),
+            RomSource::ZipEntry { archive, entry } => match entry {
+                Some(entry) => format!("{}:{}", archive.display(), entry),
+                None => archive.display().to_string(),
+            },
+        }
+    }
+
+    /// Reads the ROM and checks that it fits in the Chip-8 memory. At most one byte more than
+    /// fits is read, so a large pipe or a zip bomb is rejected without being buffered whole.
+    pub fn load(&self) -> Result<Vec<u8>, RomError> {
+        if let Some(path) = self.path()
+            && !path.is_file()
+        {
+            return Err(RomError::NotFound {
+                path: path.to_path_buf(),
+                in_bundled: matches!(self, RomSource::File(_)) && bundled_path(path).is_some(),
+            });
+        }
+
+        let mut file_size = None;
+        let binary = match self {
+            RomSource::File(path) => {
+                let file = File::open(path)?;
+                file_size = Some(file.metadata()?.len() as usize);
+                read_limited(file)?
+            }
+            RomSource::Stdin => read_limited(io::stdin().lock())?,
+            RomSource::ZipEntry { archive, entry } => read_zip_entry(archive, entry.as_deref())?,
+        };
+
+        if binary.is_empty() {
+            return Err(RomError::Empty(self.name()));
+        }
+        if binary.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge {
+                name: self.name(),
+                size: file_size,
+            });
+        }
+
+        Ok(binary)
+    }
+}
+
+/// Where a bare file name such as `PONG` is looked up among the bundled ROMs. Paths with a
+/// directory are only looked up where they point.
+fn bundled_path(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => Some(Path::new(ROMS_DIRECTORY).join(name)),
+        _ => None,
+    }
+}
+
+/// Reads one entry from a zip archive. The entry is matched by its full name first,
+/// then by its file name ignoring case, so `games.zip:pong` finds `roms/PONG`.
+fn read_zip_entry(archive_path: &Path, entry: Option<&str>) -> Result<Vec<u8>, RomError> {
+    let mut archive = zip::ZipArchive::new(File::open(archive_path)?)?;
+
+    let file_names: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(String::from)
+        .collect();
+
+    let selected = match entry {
+        Some(entry) => file_names
+            .iter()
+            .find(|name| name.as_str() == entry)
+            .or_else(|| {
+                file_names.iter().find(|name| {
+                    let file_name = name.rsplit('/').next().unwrap_or(name);
+                    file_name.eq_ignore_ascii_case(entry)
+                })
+            })
+            .cloned()
+            .ok_or_else(|| RomError::ZipEntryNotFound {
+                archive: archive_path.to_path_buf(),
+                entry: entry.to_string(),
+            })?,
+        None if file_names.is_empty() => {
+            return Err(RomError::Empty(archive_path.display().to_string()));
+        }
+        None if file_names.len() == 1 => file_names[0].clone(),
+        None => {
+            return Err(RomError::AmbiguousZip {
+                archive: archive_path.to_path_buf(),
+                entries: file_names,
+            });
+        }
+    };
+
+    let zip_file = archive.by_name(&selected)?;
+    Ok(read_limited(zip_file)?)
+}
+
+/// Reads `reader` up to one byte more than a ROM can hold, enough to tell it is too large.
+fn read_limited(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    /// Writes a zip archive holding `entries` to the temporary directory.
+    fn write_zip(name: &str, entries: &[(&str, &[u8])]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.zip", name, std::process::id()));
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (entry, bytes) in entries {
+            writer
+                .start_file(*entry, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn arguments_are_parsed_into_sources() {
+        assert_eq!(RomSource::parse("-"), RomSource::Stdin);
+        assert_eq!(
+            RomSource::parse("roms/missing.ch8"),
+            RomSource::File(PathBuf::from("roms/missing.ch8"))
+        );
+        assert_eq!(
+            RomSource::parse("PONG"),
+            RomSource::File(Path::new(ROMS_DIRECTORY).join("PONG"))
+        );
+        assert_eq!(
+            RomSource::parse("games.zip"),
+            RomSource::ZipEntry {
+                archive: PathBuf::from("games.zip"),
+                entry: None,
+            }
+        );
+        assert_eq!(
+            RomSource::parse("a.ZIP:roms/PONG.ch8"),
+            RomSource::ZipEntry {
+                archive: PathBuf::from("a.ZIP"),
+                entry: Some(String::from("roms/PONG.ch8")),
+            }
+        );
+        // 'İ' is 2 bytes long but its lowercase form is 3 bytes long
+        assert_eq!(
+            RomSource::parse("İ.zip:PONG"),
+            RomSource::ZipEntry {
+                archive: PathBuf::from("İ.zip"),
+                entry: Some(String::from("PONG")),
+            }
+        );
+    }
+
+    #[test]
+    fn zip_entries_are_found_by_name_or_file_name() {
+        let single = write_zip("rom-loader-single", &[("roms/PONG", &[0x12, 0x00])]);
+        let several = write_zip(
+            "rom-loader-several",
+            &[("roms/PONG", &[0x12, 0x00]), ("roms/TETRIS", &[0x00, 0xE0])],
+        );
+
+        assert_eq!(read_zip_entry(&single, None).unwrap(), [0x12, 0x00]);
+        assert_eq!(
+            read_zip_entry(&several, Some("roms/TETRIS")).unwrap(),
+            [0x00, 0xE0]
+        );
+        assert_eq!(
+            read_zip_entry(&several, Some("pong")).unwrap(),
+            [0x12, 0x00]
+        );
+        assert!(matches!(
+            read_zip_entry(&several, None),
+            Err(RomError::AmbiguousZip { entries, .. }) if entries == ["roms/PONG", "roms/TETRIS"]
+        ));
+        assert!(matches!(
+            read_zip_entry(&several, Some("BRIX")),
+            Err(RomError::ZipEntryNotFound { entry, .. }) if entry == "BRIX"
+        ));
+
+        std::fs::remove_file(single).unwrap();
+        std::fs::remove_file(several).unwrap();
+    }
+
+    #[test]
+    fn loading_reports_missing_empty_and_oversized_roms() {
+        let directory = std::env::temp_dir();
+        let missing = directory.join(format!("rom-loader-missing-{}.ch8", std::process::id()));
+        let empty = directory.join(format!("rom-loader-empty-{}.ch8", std::process::id()));
+        let oversized = directory.join(format!("rom-loader-oversized-{}.ch8", std::process::id()));
+        std::fs::write(&empty, []).unwrap();
+        std::fs::write(&oversized, vec![0; MAX_ROM_SIZE + 10]).unwrap();
+        let oversized_zip = write_zip(
+            "rom-loader-oversized",
+            &[("BOMB", &vec![0; 4 * MAX_ROM_SIZE])],
+        );
+
+        assert!(matches!(
+            RomSource::File(missing.clone()).load(),
+            Err(RomError::NotFound { path, in_bundled: false }) if path == missing
+        ));
+        let bare_name = RomSource::parse("NO_SUCH_ROM").load().unwrap_err();
+        assert!(matches!(
+            bare_name,
+            RomError::NotFound {
+                in_bundled: true,
+                ..
+            }
+        ));
+        assert!(bare_name.to_string().contains("also looked in"));
+        let zip_error = RomSource::parse("NO_SUCH_ROM.zip").load().unwrap_err();
+        assert_eq!(zip_error.to_string(), "ROM 'NO_SUCH_ROM.zip' was not found");
+        assert!(matches!(
+            RomSource::File(empty.clone()).load(),
+            Err(RomError::Empty(_))
+        ));
+        assert!(matches!(
+            RomSource::File(oversized.clone()).load(),
+            Err(RomError::TooLarge { size: Some(size), .. }) if size == MAX_ROM_SIZE + 10
+        ));
+        assert!(matches!(
+            RomSource::parse(&oversized_zip.to_string_lossy()).load(),
+            Err(RomError::TooLarge { size: None, .. })
+        ));
+
+        let mut full = oversized.clone();
+        full.set_extension("full.ch8");
+        std::fs::write(&full, vec![0; MAX_ROM_SIZE]).unwrap();
+        assert_eq!(
+            RomSource::File(full.clone()).load().unwrap().len(),
+            MAX_ROM_SIZE
+        );
+
+        for path in [empty, oversized, oversized_zip, full] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the ROM file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls the modification time of a ROM file so it can be reloaded when it is rebuilt.
+pub struct RomWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl RomWatcher {
+    /// Starts watching the given file, remembering its current modification time.
+    pub fn new(path: PathBuf) -> Self {
+        let last_modified = Self::modified_time(&path);
+        Self {
+            path,
+            last_modified,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Returns true once every time the file's modification time changes.
+    /// The file system is only queried every `POLL_INTERVAL`, so this is cheap to call every frame.
+    pub fn has_changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = Self::modified_time(&self.path);
+        // A missing file usually means the assembler is in the middle of rewriting it
+        if modified.is_none() || modified == self.last_modified {
+            return false;
+        }
+
+        self.last_modified = modified;
+        true
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn modifications_are_reported_once() {
+        let path = std::env::temp_dir().join(format!("rom-watcher-{}.ch8", std::process::id()));
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        let mut watcher = RomWatcher::new(path.clone());
+        watcher.last_poll -= POLL_INTERVAL;
+        assert!(!watcher.has_changed());
+
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(!watcher.has_changed(), "polled before POLL_INTERVAL");
+        watcher.last_poll -= POLL_INTERVAL;
+        assert!(watcher.has_changed());
+        watcher.last_poll -= POLL_INTERVAL;
+        assert!(!watcher.has_changed());
+
+        // A missing file is being rewritten, not changed
+        fs::remove_file(&path).unwrap();
+        watcher.last_poll -= POLL_INTERVAL;
+        assert!(!watcher.has_changed());
+    }
+}
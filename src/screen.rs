@@ -1,58 +1,297 @@
-use minifb::{Window, WindowOptions};
+use std::str::FromStr;
 
-use crate::{chip8, constants::{SCREEN_HEIGHT, SCREEN_SCALE_FACTOR, SCREEN_WIDTH}};
+use minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
+
+use crate::{
+    chip8::{self, DirtyRegion},
+    constants::{DEFAULT_PHOSPHOR_DECAY, DEFAULT_SCREEN_SCALE_FACTOR, SCREEN_HEIGHT, SCREEN_WIDTH},
+    filters::FilterChain,
+    palette::Palette,
+};
+
+/// Settings that control how the Chip-8 screen is shown on the host window.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayOptions {
+    pub scale: usize, // Size in host pixels of each Chip-8 pixel
+    pub palette: Palette,
+    pub fullscreen: bool,
+    pub persistence: PersistenceMode,
+    pub phosphor_decay: f32, // Fraction of a pixel's brightness kept each frame after it is turned off
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            scale: DEFAULT_SCREEN_SCALE_FACTOR,
+            palette: Palette::default(),
+            fullscreen: false,
+            persistence: PersistenceMode::Off,
+            phosphor_decay: DEFAULT_PHOSPHOR_DECAY,
+        }
+    }
+}
+
+impl DisplayOptions {
+    /// Width of the scaled buffer in host pixels.
+    pub fn buffer_width(&self) -> usize {
+        SCREEN_WIDTH * self.scale
+    }
+
+    /// Height of the scaled buffer in host pixels.
+    pub fn buffer_height(&self) -> usize {
+        SCREEN_HEIGHT * self.scale
+    }
+}
+
+/// Anti-flicker modes. CHIP-8 games erase and redraw sprites with XOR, so moving sprites
+/// are off for part of the time and flicker when every frame is shown as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PersistenceMode {
+    /// Show each frame exactly as the interpreter left it.
+    Off,
+    /// Pixels light up instantly and fade out over several frames, like a phosphor screen.
+    Phosphor,
+    /// A pixel is shown if it was on in the current or the previous frame.
+    Or,
+}
+
+impl FromStr for PersistenceMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(PersistenceMode::Off),
+            "phosphor" => Ok(PersistenceMode::Phosphor),
+            "or" => Ok(PersistenceMode::Or),
+            _ => Err(format!(
+                "unknown persistence mode '{}', use off, phosphor or or",
+                value
+            )),
+        }
+    }
+}
+
+/// Brightness of every Chip-8 pixel across frames, used by the anti-flicker modes.
+pub struct Persistence {
+    mode: PersistenceMode,
+    decay: f32,
+    brightness: [f32; SCREEN_WIDTH * SCREEN_HEIGHT],
+    previous_frame: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+}
+
+impl Persistence {
+    /// Creates the persistence state for the mode selected in the display options.
+    pub fn new(options: &DisplayOptions) -> Self {
+        Self {
+            mode: options.persistence,
+            decay: options.phosphor_decay.clamp(0.0, 1.0),
+            brightness: [0.0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous_frame: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    /// Returns true when the screen must be redrawn every frame instead of only when it changes.
+    pub fn is_enabled(&self) -> bool {
+        self.mode != PersistenceMode::Off
+    }
+
+    /// Advances one frame and updates the brightness of each pixel from the current screen.
+    fn next_frame(&mut self, screen: &[bool; SCREEN_WIDTH * SCREEN_HEIGHT]) {
+        for (index, &pixel_on) in screen.iter().enumerate() {
+            self.brightness[index] = match self.mode {
+                PersistenceMode::Off => f32::from(u8::from(pixel_on)),
+                PersistenceMode::Phosphor if pixel_on => 1.0,
+                PersistenceMode::Phosphor => self.brightness[index] * self.decay,
+                PersistenceMode::Or => f32::from(u8::from(pixel_on || self.previous_frame[index])),
+            };
+        }
+        self.previous_frame = *screen;
+    }
+}
 
 /// Initializes and returns a new window for the Chip-8 emulator.
-pub fn initialize_window() -> Window {
+/// The window can be resized, the buffer keeps its aspect ratio and the borders are
+/// filled with the palette's background colour.
+/// In fullscreen mode the window is borderless and scaled to fit the host screen.
+pub fn initialize_window(options: &DisplayOptions) -> Window {
+    let window_options = if options.fullscreen {
+        WindowOptions {
+            borderless: true,
+            title: false,
+            topmost: true,
+            scale: Scale::FitScreen,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        }
+    } else {
+        WindowOptions {
+            resize: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        }
+    };
+
     let mut window = Window::new(
-        "Chip-8 Emulator _ Use Esc to exit",
-        SCREEN_WIDTH * SCREEN_SCALE_FACTOR,
-        SCREEN_HEIGHT * SCREEN_SCALE_FACTOR,
-        WindowOptions::default(),
+        "Chip-8 Emulator _ Use Esc to exit, F11 to toggle fullscreen",
+        options.buffer_width(),
+        options.buffer_height(),
+        window_options,
     )
     .unwrap_or_else(|e| {
         panic!("{}", e);
     });
 
-    window.set_target_fps(60);
+    let (red, green, blue) = options.palette.background_rgb();
+    window.set_background_color(red, green, blue);
+    // Frames are paced by the run loop's clock, the window must not sleep as well
+    window.set_target_fps(0);
     window
 }
 
+/// Recreates the window in or out of fullscreen mode when F11 is pressed.
+/// Returns true when the window was replaced, so the caller can redraw the screen.
+pub fn toggle_fullscreen_if_requested(window: &mut Window, options: &mut DisplayOptions) -> bool {
+    if !window.is_key_pressed(Key::F11, KeyRepeat::No) {
+        return false;
+    }
+
+    options.fullscreen = !options.fullscreen;
+    *window = initialize_window(options);
+    true
+}
+
 /// Prints debug information about the current state of the Chip-8 emulator.
-/// Only draw if `chip8.needs_redraw` is true.
-pub fn draw_screen_if_needed(buffer: &mut Vec<u32>, chip8: &chip8::Chip8) {
-    if chip8.needs_redraw {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let pixel_on = chip8.screen[y * SCREEN_WIDTH + x];
-                let color = if pixel_on { 0xFFFFFF } else { 0x000000 };
-                // Draw a block of size SCREEN_SCALE_FACTOR x SCREEN_SCALE_FACTOR
-                for dy in 0..SCREEN_SCALE_FACTOR {
-                    for dx in 0..SCREEN_SCALE_FACTOR {
-                        let scaled_x = x * SCREEN_SCALE_FACTOR + dx;
-                        let scaled_y = y * SCREEN_SCALE_FACTOR + dy;
-                        let buffer_index =
-                            scaled_y * (SCREEN_WIDTH * SCREEN_SCALE_FACTOR) + scaled_x;
-                        buffer[buffer_index] = color;
-                    }
+/// Only draw if `chip8.needs_redraw` is true, and then only the regions listed in
+/// `chip8.dirty_regions` (or the whole screen when the list is empty), unless an anti-flicker mode is enabled: then every
+/// call is a new frame and the screen is drawn with the blended brightness of each pixel.
+/// Returns true if the buffer was redrawn.
+pub fn draw_screen_if_needed(
+    buffer: &mut [u32],
+    chip8: &chip8::Chip8,
+    options: &DisplayOptions,
+    persistence: &mut Persistence,
+) -> bool {
+    if persistence.is_enabled() {
+        persistence.next_frame(&chip8.screen);
+        for (index, brightness) in persistence.brightness.iter().enumerate() {
+            let color = options.palette.blend(*brightness);
+            draw_scaled_pixel(
+                buffer,
+                options,
+                index % SCREEN_WIDTH,
+                index / SCREEN_WIDTH,
+                color,
+            );
+        }
+        true
+    } else if chip8.needs_redraw {
+        // Only repaint the areas touched by sprites and clears since the last redraw
+        let full_screen = [DirtyRegion::FULL_SCREEN];
+        let regions = if chip8.dirty_regions.is_empty() {
+            &full_screen[..]
+        } else {
+            &chip8.dirty_regions[..]
+        };
+
+        for region in regions {
+            for y in region.y..region.y + region.height {
+                for x in region.x..region.x + region.width {
+                    let pixel_on = chip8.screen[y * SCREEN_WIDTH + x];
+                    let color = options.palette.color(pixel_on);
+                    draw_scaled_pixel(buffer, options, x, y, color);
                 }
             }
         }
+        true
+    } else {
+        false
+    }
+}
+
+/// Fills the block of size scale x scale that represents the Chip-8 pixel at (x, y).
+fn draw_scaled_pixel(buffer: &mut [u32], options: &DisplayOptions, x: usize, y: usize, color: u32) {
+    let scale = options.scale;
+    let buffer_width = options.buffer_width();
+    for dy in 0..scale {
+        let row_start = (y * scale + dy) * buffer_width + x * scale;
+        buffer[row_start..row_start + scale].fill(color);
     }
 }
 
 /// Updates the window with the current buffer content.
-pub fn update_window_with_buffer(buffer: &mut Vec<u32>, window: &mut Window) {
-    window
-        .update_with_buffer(
-            &buffer,
-            SCREEN_WIDTH * SCREEN_SCALE_FACTOR,
-            SCREEN_HEIGHT * SCREEN_SCALE_FACTOR,
-        )
-        .unwrap();
+/// When post-processing filters are selected the window shows the filtered copy of the buffer,
+/// which is only recomputed when `buffer_changed` is true.
+pub fn update_window_with_buffer(
+    buffer: &[u32],
+    window: &mut Window,
+    options: &DisplayOptions,
+    filters: &mut FilterChain,
+    buffer_changed: bool,
+) {
+    let (width, height) = (options.buffer_width(), options.buffer_height());
+    let frame = if filters.is_empty() {
+        buffer
+    } else {
+        if buffer_changed || filters.output().len() != buffer.len() {
+            filters.apply(buffer, width, height, options.scale);
+        }
+        filters.output()
+    };
+
+    window.update_with_buffer(frame, width, height).unwrap();
 }
 
 /// Initializes and returns a new buffer for the Chip-8 emulator.
-pub fn initialize_buffer() -> Vec<u32> {
-    vec![0; SCREEN_WIDTH * SCREEN_SCALE_FACTOR * SCREEN_HEIGHT * SCREEN_SCALE_FACTOR]
-}
\ No newline at end of file
+pub fn initialize_buffer(options: &DisplayOptions) -> Vec<u32> {
+    vec![options.palette.background; options.buffer_width() * options.buffer_height()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persistence(mode: PersistenceMode, decay: f32) -> Persistence {
+        Persistence::new(&DisplayOptions {
+            persistence: mode,
+            phosphor_decay: decay,
+            ..DisplayOptions::default()
+        })
+    }
+
+    #[test]
+    fn phosphor_pixels_fade_by_the_decay_each_frame() {
+        let mut persistence = persistence(PersistenceMode::Phosphor, 0.5);
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[3] = true;
+
+        persistence.next_frame(&screen);
+        assert_eq!(persistence.brightness[3], 1.0);
+
+        screen[3] = false;
+        for expected in [0.5, 0.25, 0.125] {
+            persistence.next_frame(&screen);
+            assert_eq!(persistence.brightness[3], expected);
+        }
+        assert_eq!(persistence.brightness[4], 0.0);
+
+        screen[3] = true;
+        persistence.next_frame(&screen);
+        assert_eq!(persistence.brightness[3], 1.0);
+    }
+
+    #[test]
+    fn or_mode_keeps_a_pixel_lit_for_one_frame() {
+        let mut persistence = persistence(PersistenceMode::Or, DEFAULT_PHOSPHOR_DECAY);
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[3] = true;
+
+        persistence.next_frame(&screen);
+        assert_eq!(persistence.brightness[3], 1.0);
+
+        screen[3] = false;
+        persistence.next_frame(&screen);
+        assert_eq!(persistence.brightness[3], 1.0);
+        persistence.next_frame(&screen);
+        assert_eq!(persistence.brightness[3], 0.0);
+    }
+}
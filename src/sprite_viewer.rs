@@ -0,0 +1,271 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{constants::CHIP8_RAM_MEMORY_SIZE, palette::Palette};
+
+/// Number of sprites per line of a sprite sheet, for 8 pixel wide sprites.
+const SHEET_COLUMNS: usize = 16;
+
+/// Size in image pixels of each sprite pixel of a sprite sheet.
+const SHEET_SCALE: usize = 8;
+
+/// Colour of the frame around the highlighted sprite of a sprite sheet.
+const HIGHLIGHT_COLOUR: u32 = 0xFF3030;
+
+/// How the bytes of memory are cut into sprites.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpriteLayout {
+    /// 8 pixels wide, one byte per row, as drawn by Dxyn.
+    Chip8 { height: usize },
+    /// 16x16 pixels, two bytes per row, as drawn by Dxy0 on SCHIP.
+    Schip,
+}
+
+impl SpriteLayout {
+    pub fn width(self) -> usize {
+        match self {
+            SpriteLayout::Chip8 { .. } => 8,
+            SpriteLayout::Schip => 16,
+        }
+    }
+
+    pub fn height(self) -> usize {
+        match self {
+            SpriteLayout::Chip8 { height } => height,
+            SpriteLayout::Schip => 16,
+        }
+    }
+
+    /// Number of bytes of memory each sprite uses.
+    pub fn bytes(self) -> usize {
+        self.width() / 8 * self.height()
+    }
+}
+
+impl FromStr for SpriteLayout {
+    type Err = String;
+
+    /// Parses a height of 1 to 15 rows, or `16x16` for SCHIP sprites.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "16x16" => Ok(SpriteLayout::Schip),
+            height => match height.parse() {
+                Ok(height @ 1..=15) => Ok(SpriteLayout::Chip8 { height }),
+                _ => Err(format!(
+                    "invalid sprite height '{}', use 1 to 15 or 16x16",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+/// A sprite decoded from memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub address: u16,
+    pub pixels: Vec<bool>, // Row by row, `width` pixels per row
+}
+
+/// The sprites of `ram` from `start` to `end` included, the last one completed with empty rows.
+/// Addresses past the end of memory wrap around to its start, as they do when Dxyn reads them.
+pub fn decode(ram: &[u8], start: u16, end: u16, layout: SpriteLayout) -> Vec<Sprite> {
+    (start as usize..=end as usize)
+        .step_by(layout.bytes())
+        .map(|address| {
+            let pixels = (0..layout.bytes())
+                .flat_map(|offset| {
+                    let index = address + offset;
+                    let byte = if index <= end as usize {
+                        ram[index % CHIP8_RAM_MEMORY_SIZE]
+                    } else {
+                        0
+                    };
+                    (0..8).map(move |bit| byte & (0x80 >> bit) != 0)
+                })
+                .collect();
+            Sprite {
+                address: (address % CHIP8_RAM_MEMORY_SIZE) as u16,
+                pixels,
+            }
+        })
+        .collect()
+}
+
+/// The sprites side by side as text, `#` for the pixels that are on, under their addresses.
+/// The address of the sprite at `highlighted` is followed by `*`.
+pub fn render_text(sprites: &[Sprite], layout: SpriteLayout, highlighted: Option<u16>) -> String {
+    let width = layout.width();
+    let columns = 80 / (width + 2);
+    let mut text = String::new();
+    for line in sprites.chunks(columns) {
+        let headers: Vec<String> = line
+            .iter()
+            .map(|sprite| {
+                let marker = if Some(sprite.address) == highlighted {
+                    "*"
+                } else {
+                    ""
+                };
+                format!("{:<width$}", format!("{:03X}{}", sprite.address, marker))
+            })
+            .collect();
+        text.push_str(headers.join("  ").trim_end());
+        text.push('\n');
+        for row in 0..layout.height() {
+            let rows: Vec<String> = line
+                .iter()
+                .map(|sprite| {
+                    sprite.pixels[row * width..(row + 1) * width]
+                        .iter()
+                        .map(|&on| if on { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            text.push_str(&rows.join("  "));
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text.trim_end().to_string()
+}
+
+/// Saves the sprites as a PNG sprite sheet in the palette colours, in a grid whose lines use
+/// a dimmed foreground. The sprite at `highlighted` is framed in red.
+pub fn save_sheet(
+    path: &Path,
+    sprites: &[Sprite],
+    layout: SpriteLayout,
+    palette: &Palette,
+    highlighted: Option<u16>,
+) -> io::Result<()> {
+    let columns = (SHEET_COLUMNS * 8 / layout.width()).min(sprites.len().max(1));
+    let rows = sprites.len().div_ceil(columns).max(1);
+    // Each cell holds a sprite and the grid line on its top and left, in sprite pixels
+    let (cell_width, cell_height) = (layout.width() + 1, layout.height() + 1);
+    let (width, height) = (columns * cell_width + 1, rows * cell_height + 1);
+    let grid = palette.blend(0.25);
+
+    let mut colours = vec![grid; width * height];
+    for (index, sprite) in sprites.iter().enumerate() {
+        let (left, top) = (index % columns * cell_width, index / columns * cell_height);
+        if Some(sprite.address) == highlighted {
+            for x in left..=left + cell_width {
+                colours[top * width + x] = HIGHLIGHT_COLOUR;
+                colours[(top + cell_height) * width + x] = HIGHLIGHT_COLOUR;
+            }
+            for y in top..=top + cell_height {
+                colours[y * width + left] = HIGHLIGHT_COLOUR;
+                colours[y * width + left + cell_width] = HIGHLIGHT_COLOUR;
+            }
+        }
+        for (pixel, &on) in sprite.pixels.iter().enumerate() {
+            let (x, y) = (pixel % layout.width(), pixel / layout.width());
+            colours[(top + 1 + y) * width + left + 1 + x] = palette.color(on);
+        }
+    }
+
+    let mut rgb = Vec::with_capacity(width * height * 3 * SHEET_SCALE * SHEET_SCALE);
+    for y in 0..height * SHEET_SCALE {
+        for x in 0..width * SHEET_SCALE {
+            let colour = colours[y / SHEET_SCALE * width + x / SHEET_SCALE];
+            rgb.extend_from_slice(&[(colour >> 16) as u8, (colour >> 8) as u8, colour as u8]);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        (width * SHEET_SCALE) as u32,
+        (height * SHEET_SCALE) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgb))
+        .map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_are_parsed() {
+        assert_eq!("5".parse(), Ok(SpriteLayout::Chip8 { height: 5 }));
+        assert_eq!("16X16".parse(), Ok(SpriteLayout::Schip));
+        assert!("0".parse::<SpriteLayout>().is_err());
+        assert!("16".parse::<SpriteLayout>().is_err());
+        assert_eq!(SpriteLayout::Schip.bytes(), 32);
+    }
+
+    #[test]
+    fn sprites_are_decoded_and_drawn_as_text() {
+        let ram = [0x3C, 0x42, 0xFF, 0x81, 0x18];
+        let sprites = decode(&ram, 0, 4, SpriteLayout::Chip8 { height: 2 });
+
+        assert_eq!(sprites.len(), 3);
+        assert_eq!(sprites[2].address, 4);
+        assert!(
+            sprites[2].pixels[8..].iter().all(|&on| !on),
+            "padded with empty rows"
+        );
+        assert_eq!(
+            render_text(&sprites, SpriteLayout::Chip8 { height: 2 }, Some(2)),
+            "000       002*      004\n\
+             ..####..  ########  ...##...\n\
+             .#....#.  #......#  ........"
+        );
+    }
+
+    #[test]
+    fn sprites_past_the_end_of_memory_are_labelled_from_its_start() {
+        let ram = [0; CHIP8_RAM_MEMORY_SIZE];
+        let layout = SpriteLayout::Chip8 { height: 8 };
+        let sprites = decode(&ram, 0x1000, 0x100F, layout);
+
+        let addresses: Vec<u16> = sprites.iter().map(|sprite| sprite.address).collect();
+        assert_eq!(addresses, [0x000, 0x008]);
+        assert!(render_text(&sprites, layout, Some(0x008)).starts_with("000       008*"));
+    }
+
+    #[test]
+    fn sprites_past_the_end_of_memory_wrap_around() {
+        let mut ram = [0; CHIP8_RAM_MEMORY_SIZE];
+        ram[0xFFF] = 0xFF;
+        ram[0x000] = 0x81;
+        let sprites = decode(&ram, 0xFFF, 0x1000, SpriteLayout::Chip8 { height: 2 });
+
+        assert_eq!(sprites.len(), 1);
+        assert_eq!(
+            render_text(&sprites, SpriteLayout::Chip8 { height: 2 }, None),
+            "FFF\n\
+             ########\n\
+             #......#"
+        );
+    }
+
+    #[test]
+    fn sheets_are_saved_as_png() {
+        let path = std::env::temp_dir().join(format!("sprite-sheet-{}.png", std::process::id()));
+        let sprites = decode(&[0xFF; 32], 0, 31, SpriteLayout::Schip);
+        save_sheet(
+            &path,
+            &sprites,
+            SpriteLayout::Schip,
+            &Palette::CLASSIC,
+            Some(0),
+        )
+        .unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (18 * 8, 18 * 8));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    io::{self, Stdout, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+
+use crate::{
+    cheats::{CheatSearch, Comparison, SearchStep},
+    chip8::Chip8,
+    constants::{KEYBOARD_KEYS_COUNT, SCREEN_HEIGHT, SCREEN_WIDTH, TERMINAL_KEYBOARD_CODES},
+    frontend::{AudioSink, InputSource, InputState, SystemClock, TimeSource, VideoSink},
+    palette::Palette,
+};
+
+/// Most terminals only report key presses, then auto-repeat them while the key is held.
+/// Without release events a key counts as held for this many frames after its last press,
+/// which covers the delay before the terminal starts repeating.
+const KEY_HOLD_FRAMES: u8 = 30;
+
+/// Cheat search steps of F1 to F5, as in the window.
+const SEARCH_HOTKEYS: [SearchStep; 5] = [
+    SearchStep::Start,
+    SearchStep::Narrow(Comparison::Unchanged),
+    SearchStep::Narrow(Comparison::Changed),
+    SearchStep::Narrow(Comparison::Increased),
+    SearchStep::Narrow(Comparison::Decreased),
+];
+
+/// How the 64x32 screen is drawn with text characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerminalGlyphs {
+    /// `▀` with the top pixel as foreground and the bottom one as background: 64x16 cells, in colour.
+    HalfBlock,
+    /// Braille patterns of 2x4 dots: 32x8 cells, in the palette's foreground colour only.
+    Braille,
+}
+
+impl FromStr for TerminalGlyphs {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "halfblock" | "half-block" => Ok(TerminalGlyphs::HalfBlock),
+            "braille" => Ok(TerminalGlyphs::Braille),
+            _ => Err(format!(
+                "unknown terminal glyphs '{}', use halfblock or braille",
+                value
+            )),
+        }
+    }
+}
+
+/// A frontend for a text terminal, e.g. over SSH. The screen is drawn with Unicode
+/// characters and 24-bit ANSI colours, followed by a status line with the registers.
+///
+/// Keys are read in raw mode with the same layout as the window (1234/QWER/ASDF/ZXCV).
+/// Esc or Ctrl+C quits and Space pauses. F1 to F6 control the cheat search and the cheats as
+/// in the window, with the search results shown below the registers.
+/// The buzzer rings the terminal bell.
+/// The terminal is restored when the frontend is dropped.
+pub struct TerminalFrontend {
+    stdout: Stdout,
+    palette: Palette,
+    glyphs: TerminalGlyphs,
+    clock: SystemClock,
+    key_timers: [u8; KEYBOARD_KEYS_COUNT], // Frames left during which each key counts as held
+    reports_releases: bool,                // The terminal sends key release events
+    buzzer_on: bool,
+    paused: bool,         // Shown in the status line, as told by the run loop
+    cheats_enabled: bool, // Shown in the status line, as told by the run loop
+    search_step_requested: Option<SearchStep>,
+    cheat_search: Option<CheatSearch>,
+    screen_stale: bool, // The terminal was cleared, the screen must be drawn even if it did not change
+    draw_error: Option<io::Error>, // Printed once the alternate screen is left, or it would be lost
+}
+
+impl TerminalFrontend {
+    /// Switches the terminal to raw mode on the alternate screen.
+    pub fn new(palette: Palette, glyphs: TerminalGlyphs) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        queue!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+
+        // Terminals implementing the kitty keyboard protocol report releases, so keys are exact
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            queue!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        stdout.flush()?;
+
+        Ok(Self {
+            stdout,
+            palette,
+            glyphs,
+            clock: SystemClock::new(),
+            key_timers: [0; KEYBOARD_KEYS_COUNT],
+            reports_releases,
+            buzzer_on: false,
+            paused: false,
+            cheats_enabled: true,
+            search_step_requested: None,
+            cheat_search: None,
+            screen_stale: true,
+            draw_error: None,
+        })
+    }
+
+    /// Updates the held keys from a key event, and `input` with the controls it triggers.
+    fn handle_key(&mut self, key: KeyEvent, input: &mut InputState) {
+        let is_release = key.kind == KeyEventKind::Release;
+        match key.code {
+            KeyCode::Esc => input.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.quit = true
+            }
+            KeyCode::Char(' ') if key.kind == KeyEventKind::Press => input.toggle_pause = true,
+            KeyCode::F(number @ 1..=5) if key.kind == KeyEventKind::Press => {
+                self.search_step_requested = Some(SEARCH_HOTKEYS[number as usize - 1]);
+            }
+            KeyCode::F(6) if key.kind == KeyEventKind::Press => input.toggle_cheats = true,
+            KeyCode::Char(character) => {
+                let character = character.to_ascii_lowercase();
+                if let Some((_, hex_code)) = TERMINAL_KEYBOARD_CODES
+                    .iter()
+                    .find(|(key_char, _)| *key_char == character)
+                {
+                    self.key_timers[*hex_code as usize] = match (is_release, self.reports_releases)
+                    {
+                        (true, _) => 0,
+                        (false, true) => u8::MAX,
+                        (false, false) => KEY_HOLD_FRAMES,
+                    };
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn draw_screen(&mut self, chip8: &Chip8) -> io::Result<()> {
+        match self.glyphs {
+            TerminalGlyphs::HalfBlock => draw_half_blocks(&mut self.stdout, chip8, &self.palette),
+            TerminalGlyphs::Braille => draw_braille(&mut self.stdout, chip8, &self.palette),
+        }
+    }
+
+    fn draw_status(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let screen_rows = match self.glyphs {
+            TerminalGlyphs::HalfBlock => SCREEN_HEIGHT / 2,
+            TerminalGlyphs::Braille => SCREEN_HEIGHT / 4,
+        };
+        let registers: Vec<String> = chip8
+            .v_registers
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("V{:X}:{:02X}", index, value))
+            .collect();
+        let state = if self.paused { "PAUSED" } else { "running" };
+        let cheats = if self.cheats_enabled {
+            ""
+        } else {
+            "  cheats off"
+        };
+        let search = match &self.cheat_search {
+            Some(search) => format!("Search: {}", search),
+            None => String::from("F1: cheat search  F2-F5: same/changed/up/down  F6: cheats"),
+        };
+
+        queue!(
+            self.stdout,
+            cursor::MoveTo(0, screen_rows as u16),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(format!(
+                "PC:{:04X} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X}  {}{}",
+                chip8.pc,
+                chip8.i_register,
+                chip8.stack_pointer,
+                chip8.delay_timer,
+                chip8.sound_timer,
+                state,
+                cheats
+            )),
+            cursor::MoveTo(0, screen_rows as u16 + 1),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(registers[..8].join(" ")),
+            cursor::MoveTo(0, screen_rows as u16 + 2),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(registers[8..].join(" ")),
+            cursor::MoveTo(0, screen_rows as u16 + 3),
+            terminal::Clear(ClearType::CurrentLine),
+            Print("Esc: quit  Space: pause  Keys: 1234 QWER ASDF ZXCV"),
+            cursor::MoveTo(0, screen_rows as u16 + 4),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(search),
+        )
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = queue!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = self.stdout.flush();
+        let _ = terminal::disable_raw_mode();
+        if let Some(e) = self.draw_error.take() {
+            eprintln!("Could not draw to the terminal: {}", e);
+        }
+    }
+}
+
+impl InputSource for TerminalFrontend {
+    fn poll_input(&mut self) -> InputState {
+        let mut input = InputState::default();
+
+        for timer in self.key_timers.iter_mut() {
+            if *timer != u8::MAX {
+                *timer = timer.saturating_sub(1);
+            }
+        }
+
+        loop {
+            match event::poll(Duration::ZERO) {
+                Ok(true) => match event::read() {
+                    Ok(Event::Key(key)) => self.handle_key(key, &mut input),
+                    Ok(Event::Resize(_, _)) => {
+                        let _ = queue!(self.stdout, terminal::Clear(ClearType::All));
+                        self.screen_stale = true;
+                    }
+                    Ok(_) => (),
+                    Err(_) => input.quit = true,
+                },
+                Ok(false) => break,
+                Err(_) => {
+                    input.quit = true;
+                    break;
+                }
+            }
+        }
+
+        for (held, timer) in input.keys.iter_mut().zip(self.key_timers) {
+            *held = timer > 0;
+        }
+        input
+    }
+}
+
+impl VideoSink for TerminalFrontend {
+    fn show_run_state(&mut self, paused: bool, cheats_enabled: bool) {
+        self.paused = paused;
+        self.cheats_enabled = cheats_enabled;
+    }
+
+    fn present(&mut self, chip8: &Chip8) {
+        if let Some(step) = self.search_step_requested.take() {
+            step.apply(&mut self.cheat_search, chip8);
+        }
+        // Redrawing the screen is a few kilobytes of escape codes, too much to send every frame over SSH
+        let result = if chip8.needs_redraw || self.screen_stale {
+            self.screen_stale = false;
+            self.draw_screen(chip8)
+        } else {
+            Ok(())
+        }
+        .and_then(|_| self.draw_status(chip8))
+        .and_then(|_| self.stdout.flush());
+        if let Err(e) = result {
+            self.draw_error.get_or_insert(e);
+        }
+    }
+}
+
+impl AudioSink for TerminalFrontend {
+    fn set_buzzer(&mut self, on: bool) {
+        // Rings once each time the buzzer starts
+        if on && !self.buzzer_on {
+            let _ = queue!(self.stdout, Print('\x07'));
+        }
+        self.buzzer_on = on;
+    }
+}
+
+impl TimeSource for TerminalFrontend {
+    fn now(&mut self) -> Duration {
+        self.clock.now()
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        self.clock.wait_until(deadline);
+    }
+}
+
+/// Draws the screen with `▀` characters, two pixels per cell, from the top left of the terminal.
+fn draw_half_blocks(out: &mut impl Write, chip8: &Chip8, palette: &Palette) -> io::Result<()> {
+    for row in 0..SCREEN_HEIGHT / 2 {
+        queue!(out, cursor::MoveTo(0, row as u16))?;
+        // Colours are only sent when they differ from the previous cell
+        let mut current_colors = None;
+        for x in 0..SCREEN_WIDTH {
+            let top = chip8.screen[2 * row * SCREEN_WIDTH + x];
+            let bottom = chip8.screen[(2 * row + 1) * SCREEN_WIDTH + x];
+            if current_colors != Some((top, bottom)) {
+                current_colors = Some((top, bottom));
+                queue!(
+                    out,
+                    SetForegroundColor(rgb(palette.color(top))),
+                    SetBackgroundColor(rgb(palette.color(bottom)))
+                )?;
+            }
+            queue!(out, Print('▀'))?;
+        }
+        queue!(out, ResetColor)?;
+    }
+    Ok(())
+}
+
+/// Draws the screen with braille patterns, 2x4 pixels per cell, from the top left of the terminal.
+fn draw_braille(out: &mut impl Write, chip8: &Chip8, palette: &Palette) -> io::Result<()> {
+    // Bit of each dot in a braille cell, by row then column (Unicode braille dot numbering)
+    const DOT_BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    queue!(
+        out,
+        SetForegroundColor(rgb(palette.foreground)),
+        SetBackgroundColor(rgb(palette.background))
+    )?;
+    for row in 0..SCREEN_HEIGHT / 4 {
+        queue!(out, cursor::MoveTo(0, row as u16))?;
+        for column in 0..SCREEN_WIDTH / 2 {
+            let mut pattern = 0;
+            for (dy, bits) in DOT_BITS.iter().enumerate() {
+                for (dx, bit) in bits.iter().enumerate() {
+                    if chip8.screen[(4 * row + dy) * SCREEN_WIDTH + 2 * column + dx] {
+                        pattern |= bit;
+                    }
+                }
+            }
+            let cell = char::from_u32(0x2800 + pattern).unwrap_or(' ');
+            queue!(out, Print(cell))?;
+        }
+    }
+    queue!(out, ResetColor)
+}
+
+/// Converts a `0x00RRGGBB` palette colour.
+fn rgb(color: u32) -> Color {
+    Color::Rgb {
+        r: (color >> 16) as u8,
+        g: (color >> 8) as u8,
+        b: color as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A screen with the pixels at (0, 0) and (1, 3) on.
+    fn two_pixels() -> Chip8 {
+        let mut chip8 = Chip8::start(Vec::new());
+        chip8.screen[0] = true;
+        chip8.screen[3 * SCREEN_WIDTH + 1] = true;
+        chip8
+    }
+
+    #[test]
+    fn half_blocks_hold_two_pixels_per_cell() {
+        let mut out = Vec::new();
+        draw_half_blocks(&mut out, &two_pixels(), &Palette::CLASSIC).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        let (white, black) = ("255;255;255", "0;0;0");
+        assert!(text.starts_with(&format!(
+            "\x1b[1;1H\x1b[38;2;{white}m\x1b[48;2;{black}m▀\x1b[38;2;{black}m\x1b[48;2;{black}m{}\x1b[0m\x1b[2;1H\x1b[38;2;{black}m\x1b[48;2;{black}m▀\x1b[38;2;{black}m\x1b[48;2;{white}m▀\x1b[38;2;{black}m\x1b[48;2;{black}m{}\x1b[0m\x1b[3;1H",
+            "▀".repeat(63),
+            "▀".repeat(62),
+        )));
+        assert_eq!(text.matches('▀').count(), SCREEN_WIDTH * SCREEN_HEIGHT / 2);
+    }
+
+    #[test]
+    fn braille_cells_hold_eight_pixels() {
+        let mut out = Vec::new();
+        draw_braille(&mut out, &two_pixels(), &Palette::CLASSIC).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.starts_with(&format!(
+            "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\x1b[1;1H⢁{}\x1b[2;1H",
+            "⠀".repeat(31)
+        )));
+        let cells = text.chars().filter(|c| ('⠀'..='⣿').contains(c)).count();
+        assert_eq!(cells, SCREEN_WIDTH * SCREEN_HEIGHT / 8);
+        assert!(text.ends_with("\x1b[0m"));
+    }
+}
@@ -1,14 +1,15 @@
 use minifb::Window;
 
-use crate::constants::KEYBOARD_CODES;
+use crate::constants::{KEYBOARD_CODES, KEYBOARD_KEYS_COUNT};
 
-/// Checks the current state of the keyboard and returns the first pressed key's corresponding hex code.
-pub fn get_pressed_key(window: &Window) -> Option<u8> {
+/// Checks the current state of the keyboard and returns which Chip-8 keys are held down, indexed by hex code.
+pub fn get_pressed_keys(window: &Window) -> [bool; KEYBOARD_KEYS_COUNT] {
+    let mut keys = [false; KEYBOARD_KEYS_COUNT];
     for (key, hex_code) in KEYBOARD_CODES.iter() {
         if window.is_key_down(*key) {
-            return Some(*hex_code);
+            keys[*hex_code as usize] = true;
         }
     }
 
-    None
-}
\ No newline at end of file
+    keys
+}
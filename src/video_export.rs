@@ -0,0 +1,424 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    cheats::Cheats,
+    chip8::Chip8,
+    constants::FRAMES_PER_SECOND,
+    filters::FilterChain,
+    frontend::{AudioSink, InputSource, InputState, RunLoop, TimeSource, VideoSink},
+    headless::VirtualClock,
+    input_log::InputLog,
+    screen::{self, DisplayOptions, Persistence},
+};
+
+const AUDIO_SAMPLE_RATE: usize = 44_100;
+const AUDIO_SAMPLES_PER_FRAME: usize = AUDIO_SAMPLE_RATE / FRAMES_PER_SECOND;
+const BEEP_FREQUENCY: usize = 440;
+const BEEP_AMPLITUDE: i16 = i16::MAX / 4;
+
+/// Settings of a headless export.
+pub struct ExportSettings {
+    pub frames: usize,
+}
+
+/// Replays a ROM with a recorded input log at exactly 60 fps and the recorded speed and idle
+/// skip setting, without opening a window, writing every frame to `output` and the buzzer to a WAV file next to it.
+///
+/// When `output` ends in `.y4m` the frames are written as a single YUV4MPEG2 stream (4:4:4),
+/// otherwise `output` is a directory that receives numbered `frame_00000.ppm` images.
+/// The frames are rendered like in the window, with the palette, anti-flicker mode and filters.
+/// Returns the path of the WAV file.
+pub fn export_video(
+    rom_binary: Vec<u8>,
+    input_log: &InputLog,
+    output: &Path,
+    settings: &ExportSettings,
+    options: &DisplayOptions,
+    filters: &mut FilterChain,
+) -> io::Result<PathBuf> {
+    let (width, height) = (options.buffer_width(), options.buffer_height());
+    let video: Box<dyn FrameWriter> = if is_y4m(output) {
+        Box::new(Y4mWriter::create(output, width, height)?)
+    } else {
+        Box::new(PpmWriter::create(output, width, height)?)
+    };
+
+    let wav_path = if is_y4m(output) {
+        output.with_extension("wav")
+    } else {
+        output.join("audio.wav")
+    };
+    let audio = WavWriter::create(&wav_path)?;
+
+    let mut chip8 = Chip8::start(rom_binary);
+    chip8.seed_rng(input_log.seed);
+    // Idle skip shifts when waits end, so the recorded session's setting is kept
+    chip8.set_idle_skip(input_log.idle_skip);
+    chip8.request_full_redraw();
+
+    let host = ExportHost {
+        input_log,
+        options,
+        filters,
+        buffer: screen::initialize_buffer(options),
+        persistence: Persistence::new(options),
+        video,
+        audio,
+        frame: 0,
+        cheats_enabled: true,
+        clock: VirtualClock::default(),
+        error: None,
+    };
+    let mut run_loop = RunLoop::new(chip8, host, input_log.speed);
+    run_loop.cheats = Cheats {
+        cheats: input_log.cheats.clone(),
+        enabled: true,
+    };
+    run_loop.run_frames(settings.frames);
+
+    let mut host = run_loop.host;
+    if let Some(error) = host.error {
+        return Err(error);
+    }
+    host.video.finish()?;
+    host.audio.finish()?;
+    Ok(wav_path)
+}
+
+/// Frontend of the export: the keys come from the input log, and each frame and its
+/// audio are written to the output files. Time is virtual, so frames run as fast as possible.
+struct ExportHost<'a> {
+    input_log: &'a InputLog,
+    options: &'a DisplayOptions,
+    filters: &'a mut FilterChain,
+    buffer: Vec<u32>,
+    persistence: Persistence,
+    video: Box<dyn FrameWriter>,
+    audio: WavWriter,
+    frame: usize,
+    cheats_enabled: bool, // State of the cheats in the run loop, toggled when the log changes it
+    clock: VirtualClock,
+    error: Option<io::Error>, // First write error, which stops the run loop
+}
+
+impl InputSource for ExportHost<'_> {
+    fn poll_input(&mut self) -> InputState {
+        let cheats_enabled = self.input_log.cheats_enabled_at(self.frame);
+        let toggle_cheats = cheats_enabled != self.cheats_enabled;
+        self.cheats_enabled = cheats_enabled;
+        InputState {
+            keys: self.input_log.keys_at(self.frame),
+            quit: self.error.is_some(),
+            toggle_cheats,
+            ..InputState::default()
+        }
+    }
+}
+
+impl AudioSink for ExportHost<'_> {
+    fn set_buzzer(&mut self, on: bool) {
+        if self.error.is_none()
+            && let Err(e) = self.audio.write_frame(on)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl VideoSink for ExportHost<'_> {
+    fn present(&mut self, chip8: &Chip8) {
+        let options = self.options;
+        let redrawn =
+            screen::draw_screen_if_needed(&mut self.buffer, chip8, options, &mut self.persistence);
+
+        let pixels = if self.filters.is_empty() {
+            &self.buffer[..]
+        } else {
+            if redrawn || self.filters.output().len() != self.buffer.len() {
+                self.filters.apply(
+                    &self.buffer,
+                    options.buffer_width(),
+                    options.buffer_height(),
+                    options.scale,
+                );
+            }
+            self.filters.output()
+        };
+
+        if self.error.is_none()
+            && let Err(e) = self.video.write_frame(self.frame, pixels)
+        {
+            self.error = Some(e);
+        }
+        self.frame += 1;
+    }
+}
+
+impl TimeSource for ExportHost<'_> {
+    fn now(&mut self) -> Duration {
+        self.clock.now()
+    }
+
+    fn wait_until(&mut self, deadline: Duration) {
+        self.clock.wait_until(deadline);
+    }
+}
+
+fn is_y4m(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("y4m"))
+}
+
+/// Destination of the exported `0x00RRGGBB` frames.
+trait FrameWriter {
+    fn write_frame(&mut self, frame: usize, pixels: &[u32]) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Writes a YUV4MPEG2 stream, which tools such as ffmpeg can encode directly.
+struct Y4mWriter {
+    writer: BufWriter<File>,
+    planes: Vec<u8>,
+}
+
+impl Y4mWriter {
+    fn create(path: &Path, width: usize, height: usize) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+            width, height, FRAMES_PER_SECOND
+        )?;
+        Ok(Self {
+            writer,
+            planes: vec![0; width * height * 3],
+        })
+    }
+}
+
+impl FrameWriter for Y4mWriter {
+    fn write_frame(&mut self, _frame: usize, pixels: &[u32]) -> io::Result<()> {
+        // BT.601 limited range, each plane at full resolution
+        let plane_size = pixels.len();
+        for (index, color) in pixels.iter().enumerate() {
+            let (r, g, b) = (
+                ((color >> 16) & 0xFF) as i32,
+                ((color >> 8) & 0xFF) as i32,
+                (color & 0xFF) as i32,
+            );
+            self.planes[index] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            self.planes[plane_size + index] =
+                (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            self.planes[2 * plane_size + index] =
+                (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.planes)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes every frame as a binary PPM image in a directory.
+struct PpmWriter {
+    directory: PathBuf,
+    width: usize,
+    height: usize,
+}
+
+impl PpmWriter {
+    fn create(directory: &Path, width: usize, height: usize) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            width,
+            height,
+        })
+    }
+}
+
+impl FrameWriter for PpmWriter {
+    fn write_frame(&mut self, frame: usize, pixels: &[u32]) -> io::Result<()> {
+        let path = self.directory.join(format!("frame_{:05}.ppm", frame));
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for color in pixels {
+            writer.write_all(&[(color >> 16) as u8, (color >> 8) as u8, *color as u8])?;
+        }
+        writer.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the buzzer as a 16-bit mono PCM WAV file with a square wave.
+/// The chunk sizes in the header are filled in by `finish`.
+struct WavWriter {
+    writer: BufWriter<File>,
+    samples_written: usize,
+}
+
+impl WavWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?; // RIFF chunk size, set by finish
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&1u16.to_le_bytes())?; // Mono
+        writer.write_all(&(AUDIO_SAMPLE_RATE as u32).to_le_bytes())?;
+        writer.write_all(&(AUDIO_SAMPLE_RATE as u32 * 2).to_le_bytes())?; // Bytes per second
+        writer.write_all(&2u16.to_le_bytes())?; // Bytes per sample
+        writer.write_all(&16u16.to_le_bytes())?; // Bits per sample
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?; // data chunk size, set by finish
+        Ok(Self {
+            writer,
+            samples_written: 0,
+        })
+    }
+
+    /// Writes one frame of audio: the beep if the buzzer is on, silence otherwise.
+    fn write_frame(&mut self, buzzer_on: bool) -> io::Result<()> {
+        let half_period = AUDIO_SAMPLE_RATE / BEEP_FREQUENCY / 2;
+        for _ in 0..AUDIO_SAMPLES_PER_FRAME {
+            // The wave keeps its phase across frames so consecutive beep frames join smoothly
+            let sample = match (buzzer_on, (self.samples_written / half_period) % 2) {
+                (false, _) => 0,
+                (true, 0) => BEEP_AMPLITUDE,
+                (true, _) => -BEEP_AMPLITUDE,
+            };
+            self.writer.write_all(&sample.to_le_bytes())?;
+            self.samples_written += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let data_size = (self.samples_written * 2) as u32;
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(36 + data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    /// Starts a 3 frame beep, draws the font's 0 at the top left and loops.
+    const BEEP_AND_DRAW: [u8; 10] = [0x60, 0x03, 0xF0, 0x18, 0xA0, 0x00, 0xD1, 0x15, 0x12, 0x08];
+    const FRAME_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+    const WAV_HEADER_SIZE: usize = 44;
+
+    fn export(input_log: &InputLog, output: &Path, frames: usize) -> PathBuf {
+        let settings = ExportSettings { frames };
+        let options = DisplayOptions {
+            scale: 1,
+            ..DisplayOptions::default()
+        };
+        export_video(
+            BEEP_AND_DRAW.to_vec(),
+            input_log,
+            output,
+            &settings,
+            &options,
+            &mut FilterChain::new(&[]),
+        )
+        .unwrap()
+    }
+
+    fn assert_wav(wav: &[u8], frames: usize) {
+        let data_size = frames * AUDIO_SAMPLES_PER_FRAME * 2;
+        assert_eq!(wav.len(), WAV_HEADER_SIZE + data_size);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[4..8], &(36 + data_size as u32).to_le_bytes());
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[40..44], &(data_size as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn y4m_exports_replay_at_the_recorded_speed() {
+        let output = std::env::temp_dir().join(format!("export-{}.y4m", std::process::id()));
+        let input_log = InputLog {
+            speed: 1,
+            ..InputLog::default()
+        };
+        let wav_path = export(&input_log, &output, 4);
+
+        let video = fs::read(&output).unwrap();
+        let wav = fs::read(&wav_path).unwrap();
+        fs::remove_file(&output).unwrap();
+        fs::remove_file(&wav_path).unwrap();
+
+        let header = b"YUV4MPEG2 W64 H32 F60:1 Ip A1:1 C444\n";
+        assert_eq!(&video[..header.len()], header);
+        let frames: Vec<&[u8]> = video[header.len()..]
+            .chunks(b"FRAME\n".len() + FRAME_SIZE)
+            .collect();
+        assert_eq!(frames.len(), 4);
+        for frame in &frames {
+            assert_eq!(&frame[..6], b"FRAME\n");
+            assert_eq!(frame.len(), 6 + FRAME_SIZE);
+        }
+
+        // One instruction per frame, so the sprite appears in the fourth frame. Y is 16 for
+        // black and 235 for white.
+        let luma = |frame: usize, x: usize, y: usize| frames[frame][6 + y * SCREEN_WIDTH + x];
+        assert_eq!(luma(2, 0, 0), 16);
+        assert_eq!(luma(3, 0, 0), 235);
+        assert_eq!(luma(3, 4, 0), 16);
+        assert_wav(&wav, 4);
+    }
+
+    #[test]
+    fn directory_exports_write_numbered_ppm_frames() {
+        let output = std::env::temp_dir().join(format!("export-{}", std::process::id()));
+        let input_log = InputLog {
+            speed: 100,
+            ..InputLog::default()
+        };
+        let wav_path = export(&input_log, &output, 3);
+        assert_eq!(wav_path, output.join("audio.wav"));
+
+        let header = b"P6\n64 32\n255\n";
+        for frame in 0..3 {
+            let ppm = fs::read(output.join(format!("frame_{:05}.ppm", frame))).unwrap();
+            assert_eq!(&ppm[..header.len()], header);
+            assert_eq!(ppm.len(), header.len() + FRAME_SIZE);
+            assert_eq!(&ppm[header.len()..header.len() + 3], &[0xFF; 3]);
+        }
+        assert!(!output.join("frame_00003.ppm").exists());
+
+        let wav = fs::read(&wav_path).unwrap();
+        fs::remove_dir_all(&output).unwrap();
+        assert_wav(&wav, 3);
+        let sample = |index: usize| {
+            i16::from_le_bytes([
+                wav[WAV_HEADER_SIZE + index * 2],
+                wav[WAV_HEADER_SIZE + index * 2 + 1],
+            ])
+        };
+        assert_eq!(
+            sample(0).abs(),
+            BEEP_AMPLITUDE,
+            "the beep starts in the first frame"
+        );
+    }
+}